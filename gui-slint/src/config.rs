@@ -37,6 +37,9 @@ pub struct AppConfig {
     pub cached_models: Vec<String>,
     #[serde(default)]
     pub hooks_enabled: bool,
+    /// External copilot-api-server executable, used instead of the embedded one.
+    #[serde(default)]
+    pub server_exe_path: String,
 }
 
 impl Default for AppConfig {
@@ -69,6 +72,7 @@ impl Default for AppConfig {
             fast_model: "gpt-5-mini".to_string(),
             cached_models: Vec::new(),
             hooks_enabled: true,
+            server_exe_path: String::new(),
         }
     }
 }
@@ -89,7 +93,7 @@ impl AppConfig {
     pub fn normalized_account_type(&self) -> String {
         let value = self.account_type.trim().to_lowercase();
         match value.as_str() {
-            "enterprise" | "business" | "individual" => value,
+            "enterprise" | "business" | "individual" | "auto" => value,
             _ => "enterprise".to_string(),
         }
     }
@@ -52,6 +52,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     ui.set_show_azure_section(config.show_azure_section);
     ui.set_hooks_enabled(config.hooks_enabled);
     ui.set_hooks_config_path(hooks_config::hooks_config_path_string().into());
+    ui.set_server_exe_path(config.server_exe_path.clone().into());
+    set_server_missing(&ui, server::missing_server_message(&config));
     
     // Initialize model selection
     setup_model_selection(&ui, &config);
@@ -236,9 +238,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             set_status(&ui, "Starting Copilot auth flow...");
             
             // Run auth command from embedded server
+            let config = config_from_ui(&ui);
             let ui_weak = ui.as_weak();
             std::thread::spawn(move || {
-                match run_auth_command() {
+                match run_auth_command(&config) {
                     Ok((code, url)) => {
                         let _ = slint::invoke_from_event_loop(move || {
                             if let Some(ui) = ui_weak.upgrade() {
@@ -290,6 +293,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    let ui_handle = ui.as_weak();
+    ui.on_filter_log(move |filter| {
+        if let Some(ui) = ui_handle.upgrade() {
+            let filtered = filter_log_lines(&get_log_text(), &filter);
+            ui.set_log_text(filtered.into());
+        }
+    });
+
     let ui_handle = ui.as_weak();
     ui.on_open_hooks_config(move || {
         if let Some(ui) = ui_handle.upgrade() {
@@ -302,10 +313,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    let ui_handle = ui.as_weak();
+    ui.on_locate_server(move || {
+        if let Some(ui) = ui_handle.upgrade() {
+            let mut dialog = rfd::FileDialog::new().set_title("Locate copilot-api-server");
+            if cfg!(windows) {
+                dialog = dialog.add_filter("Executable", &["exe"]);
+            }
+            let Some(path) = dialog.pick_file() else {
+                return;
+            };
+            ui.set_server_exe_path(path.display().to_string().into());
+            let config = config_from_ui(&ui);
+            let missing = server::missing_server_message(&config);
+            let saved = save_config(&config);
+            let message = match (&missing, saved) {
+                (Some(reason), _) => reason.clone(),
+                (None, Ok(_)) => format!("Using server executable {}", path.display()),
+                (None, Err(err)) => format!("Server located, but saving config failed: {}", err),
+            };
+            set_server_missing(&ui, missing);
+            set_status(&ui, &message);
+        }
+    });
+
     ui.run()?;
     Ok(())
 }
 
+/// Keeps the "no server" banner and the Start button in step with the config.
+fn set_server_missing(ui: &AppWindow, message: Option<String>) {
+    ui.set_server_missing(message.is_some());
+    ui.set_server_missing_text(message.unwrap_or_default().into());
+}
+
 fn open_url(url: &str) -> std::io::Result<()> {
     #[cfg(target_os = "windows")]
     {
@@ -327,12 +368,12 @@ fn open_url(url: &str) -> std::io::Result<()> {
 }
 
 /// Run the auth command from the embedded server to get device code
-fn run_auth_command() -> Result<(String, String), String> {
+fn run_auth_command(config: &AppConfig) -> Result<(String, String), String> {
     use std::io::{BufRead, BufReader};
     use std::sync::mpsc;
     use std::time::{Duration, Instant};
     
-    let server_exe = server::get_server_exe_path()?;
+    let server_exe = server::get_server_exe_path(config)?;
     
     let mut cmd = std::process::Command::new(&server_exe);
     cmd.arg("auth")
@@ -454,7 +495,7 @@ fn append_log(ui: &slint::Weak<AppWindow>, line: &str) {
                     let new_start = buffer.len() - 80_000;
                     *buffer = buffer[new_start..].to_string();
                 }
-                ui.set_log_text(buffer.clone().into());
+                ui.set_log_text(filter_log_lines(&buffer, &ui.get_log_filter()).into());
             }
         }
     });
@@ -467,6 +508,20 @@ fn clear_log_buffer(ui: &AppWindow) {
     }
 }
 
+/// Lines of `buffer` containing `filter` (case-insensitive); the whole buffer
+/// when the filter is blank.
+fn filter_log_lines(buffer: &str, filter: &str) -> String {
+    let needle = filter.trim().to_lowercase();
+    if needle.is_empty() {
+        return buffer.to_string();
+    }
+    buffer
+        .lines()
+        .filter(|line| line.to_lowercase().contains(&needle))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
 fn get_log_text() -> String {
     LOG_BUFFER.lock().map(|b| b.clone()).unwrap_or_default()
 }
@@ -637,6 +692,7 @@ fn config_from_ui(ui: &AppWindow) -> AppConfig {
         // Preserve cached models from existing config
         cached_models: load_config().map(|c| c.cached_models).unwrap_or_default(),
         hooks_enabled: ui.get_hooks_enabled(),
+        server_exe_path: ui.get_server_exe_path().to_string(),
     }
 }
 
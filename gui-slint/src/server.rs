@@ -13,7 +13,7 @@ const CREATE_NO_WINDOW: u32 = 0x08000000;
 static EMBEDDED_SERVER: &[u8] = include_bytes!("server_embedded.gz");
 
 pub fn start_server(config: &AppConfig) -> Result<Child, String> {
-    let server_exe = get_server_exe()?;
+    let server_exe = get_server_exe(config)?;
     
     let mut cmd = Command::new(&server_exe);
     
@@ -89,11 +89,40 @@ pub fn start_server(config: &AppConfig) -> Result<Child, String> {
 }
 
 /// Public version for auth command
-pub fn get_server_exe_path() -> Result<PathBuf, String> {
-    get_server_exe()
+pub fn get_server_exe_path(config: &AppConfig) -> Result<PathBuf, String> {
+    get_server_exe(config)
 }
 
-fn get_server_exe() -> Result<PathBuf, String> {
+const MISSING_SERVER: &str =
+    "This build has no embedded server. Use \"Locate server executable...\" to select copilot-api-server.exe.";
+
+/// Explains why the server cannot be started, without extracting anything.
+/// `None` means Start can proceed.
+pub fn missing_server_message(config: &AppConfig) -> Option<String> {
+    if !config.server_exe_path.trim().is_empty() {
+        return external_server_exe(&config.server_exe_path).err();
+    }
+    if cfg!(embedded_server) {
+        None
+    } else {
+        Some(MISSING_SERVER.to_string())
+    }
+}
+
+fn external_server_exe(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path.trim());
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("Server executable not found: {}", path.display()))
+    }
+}
+
+fn get_server_exe(config: &AppConfig) -> Result<PathBuf, String> {
+    if !config.server_exe_path.trim().is_empty() {
+        return external_server_exe(&config.server_exe_path);
+    }
+
     #[cfg(embedded_server)]
     {
         // Extract embedded server to temp directory
@@ -131,7 +160,6 @@ fn get_server_exe() -> Result<PathBuf, String> {
     
     #[cfg(not(embedded_server))]
     {
-        // Fallback: look for external server
-        Err("Server not embedded and no external server found".to_string())
+        Err(MISSING_SERVER.to_string())
     }
 }
@@ -1,8 +1,15 @@
+use std::future::Future;
+
+use tokio_util::sync::CancellationToken;
+
 use crate::{
     errors::{ApiError, ApiResult},
-    services::github::{get_copilot_token, get_github_user},
-    state::AppState,
-    token_store::read_github_token,
+    services::{
+        copilot::{ensure_success, send_chat_completions, send_responses, ChatCompletionsPayload, ResponsesPayload},
+        github::{get_copilot_token, get_copilot_usage, get_github_user},
+    },
+    state::{AppConfig, AppState},
+    token_store::{read_github_token, read_github_token_file},
 };
 
 pub async fn ensure_github_token(state: &AppState) -> ApiResult<String> {
@@ -10,23 +17,66 @@ pub async fn ensure_github_token(state: &AppState) -> ApiResult<String> {
         return Ok(token);
     }
 
+    if let Some(token) = read_github_token_file().await? {
+        let mut config = state.config.write().await;
+        config.github_token = Some(token.clone());
+        return Ok(token);
+    }
+
     if let Some(token) = read_github_token().await? {
         let mut config = state.config.write().await;
         config.github_token = Some(token.clone());
         return Ok(token);
     }
 
-    Err(ApiError::Unauthorized(
+    Err(ApiError::ServiceUnavailable(
         "GitHub token not found. Run device auth first.".to_string(),
     ))
 }
 
+/// Maps `copilot_plan` from `/copilot_internal/user` to the account type that
+/// selects the API subdomain. Unknown plans are treated as `individual`.
+pub fn account_type_for_plan(plan: &str) -> &'static str {
+    match plan.trim().to_ascii_lowercase().as_str() {
+        "business" => "business",
+        "enterprise" => "enterprise",
+        _ => "individual",
+    }
+}
+
+/// Replaces `--account-type auto` with the type matching the user's plan
+/// before the first Copilot call builds a base URL from it.
+async fn resolve_auto_account_type(state: &AppState, github_token: &str) {
+    let config = state.config.read().await.clone();
+    if config.account_type != "auto" {
+        return;
+    }
+    let detected = match get_copilot_usage(&state.client, &config, github_token).await {
+        Ok(usage) => {
+            let plan = usage.get("copilot_plan").and_then(|v| v.as_str()).unwrap_or("unknown");
+            let account_type = account_type_for_plan(plan);
+            tracing::info!("Detected Copilot plan {:?}; using account type {}", plan, account_type);
+            account_type
+        }
+        Err(err) => {
+            tracing::warn!("Failed to detect account type, falling back to individual: {}", err);
+            "individual"
+        }
+    };
+    state.config.write().await.account_type = detected.to_string();
+}
+
 pub async fn ensure_copilot_token(state: &AppState) -> ApiResult<String> {
     if let Some(token) = state.config.read().await.copilot_token.clone() {
         return Ok(token);
     }
+    let _refreshing = state.token_refresh.lock().await;
+    if let Some(token) = state.config.read().await.copilot_token.clone() {
+        return Ok(token);
+    }
 
     let github_token = ensure_github_token(state).await?;
+    resolve_auto_account_type(state, &github_token).await;
     let config_snapshot = state.config.read().await.clone();
 
     let response = get_copilot_token(&state.client, &config_snapshot, &github_token).await?;
@@ -47,39 +97,269 @@ pub async fn ensure_copilot_token(state: &AppState) -> ApiResult<String> {
     Ok(response.token)
 }
 
-fn schedule_copilot_refresh(state: AppState, refresh_in: u64) {
-    tokio::spawn(async move {
-        let mut next_refresh = refresh_in;
-        loop {
-            let wait_secs = next_refresh.saturating_sub(60);
-            if wait_secs > 0 {
-                tokio::time::sleep(std::time::Duration::from_secs(wait_secs)).await;
-            }
+/// Replaces the rejected `stale` token. The refresh loop started by
+/// `ensure_copilot_token` keeps running, so none is scheduled here.
+pub async fn refresh_copilot_token(state: &AppState, stale: &str) -> ApiResult<String> {
+    refresh_token_once(state, stale, || async {
+        let github_token = ensure_github_token(state).await?;
+        let config_snapshot = state.config.read().await.clone();
+        Ok(get_copilot_token(&state.client, &config_snapshot, &github_token).await?.token)
+    })
+    .await
+}
+
+/// Concurrent callers queue on `token_refresh`; whoever gets in after a fresh
+/// token was stored reuses it instead of fetching again. The old token stays
+/// cached until the new one replaces it, so a failed fetch leaves it in place.
+async fn refresh_token_once<F, Fut>(state: &AppState, stale: &str, fetch: F) -> ApiResult<String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = ApiResult<String>>,
+{
+    let _refreshing = state.token_refresh.lock().await;
+    if let Some(token) = state.config.read().await.copilot_token.clone().filter(|token| token != stale) {
+        return Ok(token);
+    }
+    let token = fetch().await?;
+    state.config.write().await.copilot_token = Some(token.clone());
+    Ok(token)
+}
 
+pub async fn send_with_token_retry<F, Fut>(state: &AppState, token: &str, send: F) -> ApiResult<reqwest::Response>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ApiResult<reqwest::Response>>,
+{
+    retry_unauthorized(token.to_string(), send, || refresh_copilot_token(state, token)).await
+}
+
+async fn retry_unauthorized<F, Fut, R, RFut>(token: String, mut send: F, refresh: R) -> ApiResult<reqwest::Response>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ApiResult<reqwest::Response>>,
+    R: FnOnce() -> RFut,
+    RFut: Future<Output = ApiResult<String>>,
+{
+    let resp = send(token).await?;
+    if resp.status() != reqwest::StatusCode::UNAUTHORIZED {
+        return Ok(resp);
+    }
+    tracing::warn!("Copilot rejected the token with 401; refreshing and retrying once");
+    let token = refresh().await?;
+    send(token).await
+}
+
+pub async fn create_chat_completions_with_retry(
+    state: &AppState,
+    config: &AppConfig,
+    token: &str,
+    payload: &ChatCompletionsPayload,
+) -> ApiResult<reqwest::Response> {
+    let resp = send_with_token_retry(state, token, |token| async move {
+        send_chat_completions(&state.client, config, &token, payload).await
+    })
+    .await?;
+    ensure_success(resp, "Failed to create chat completions").await
+}
+
+pub async fn create_responses_with_retry(
+    state: &AppState,
+    config: &AppConfig,
+    token: &str,
+    payload: &ResponsesPayload,
+) -> ApiResult<reqwest::Response> {
+    let resp = send_with_token_retry(state, token, |token| async move {
+        send_responses(&state.client, config, &token, payload).await
+    })
+    .await?;
+    ensure_success(resp, "Failed to create responses").await
+}
+
+fn schedule_copilot_refresh(state: AppState, refresh_in: u64) {
+    let shutdown = state.shutdown.clone();
+    tokio::spawn(run_refresh_loop(shutdown, refresh_in, move |next_refresh| {
+        let state = state.clone();
+        async move {
             let github_token = match ensure_github_token(&state).await {
                 Ok(token) => token,
                 Err(err) => {
                     tracing::warn!("Failed to refresh Copilot token (no GitHub token): {}", err);
-                    continue;
+                    return next_refresh;
                 }
             };
 
+            let _refreshing = state.token_refresh.lock().await;
             let config_snapshot = state.config.read().await.clone();
             match get_copilot_token(&state.client, &config_snapshot, &github_token).await {
                 Ok(response) => {
-                    next_refresh = response.refresh_in;
                     let mut config = state.config.write().await;
                     config.copilot_token = Some(response.token.clone());
                     if config.show_token {
                         tracing::info!("Refreshed Copilot token: {}", response.token);
                     }
+                    response.refresh_in
                 }
                 Err(err) => {
                     tracing::warn!("Failed to refresh Copilot token: {}", err);
                     // Backoff a bit before retry
-                    next_refresh = 300;
+                    300
+                }
+            }
+        }
+    }));
+}
+
+/// Calls `refresh` a minute before each token expiry until `shutdown` is
+/// cancelled. `refresh` gets the current interval and returns the next one.
+async fn run_refresh_loop<F, Fut>(shutdown: CancellationToken, refresh_in: u64, mut refresh: F)
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = u64>,
+{
+    let mut next_refresh = refresh_in;
+    loop {
+        let wait = std::time::Duration::from_secs(next_refresh.saturating_sub(60));
+        tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(wait) => {}
+        }
+        next_refresh = refresh(next_refresh).await;
+    }
+    tracing::debug!("Copilot token refresh stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{account_type_for_plan, ensure_copilot_token, refresh_token_once, retry_unauthorized, run_refresh_loop};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn cancelling_shutdown_stops_refresh_loop() {
+        let shutdown = CancellationToken::new();
+        let calls = Arc::new(AtomicU32::new(0));
+        let (token, counter) = (shutdown.clone(), calls.clone());
+        let refresh = move |next: u64| {
+            let (token, counter) = (token.clone(), counter.clone());
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) + 1 == 3 {
+                    token.cancel();
                 }
+                next
             }
+        };
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), run_refresh_loop(shutdown, 0, refresh))
+            .await
+            .expect("loop exits once cancelled");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn cancelled_before_first_refresh_never_refreshes() {
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        run_refresh_loop(shutdown, 1800, move |next| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move { next }
+        })
+        .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    fn response(status: u16) -> reqwest::Response {
+        reqwest::Response::from(axum::http::Response::builder().status(status).body("{}").unwrap())
+    }
+
+    #[tokio::test]
+    async fn unauthorized_refreshes_token_and_retries_once() {
+        let seen = Mutex::new(Vec::new());
+        let send = |token: String| {
+            let status = if token == "fresh" { 200 } else { 401 };
+            seen.lock().unwrap().push(token);
+            async move { Ok(response(status)) }
+        };
+
+        let resp = retry_unauthorized("stale".to_string(), send, || async { Ok("fresh".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(*seen.lock().unwrap(), vec!["stale".to_string(), "fresh".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn second_unauthorized_is_returned_without_another_refresh() {
+        let refreshes = AtomicU32::new(0);
+        let resp = retry_unauthorized(
+            "stale".to_string(),
+            |_| async { Ok(response(401)) },
+            || async {
+                refreshes.fetch_add(1, Ordering::SeqCst);
+                Ok("fresh".to_string())
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), 401);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn maps_copilot_plan_to_account_type() {
+        assert_eq!(account_type_for_plan("business"), "business");
+        assert_eq!(account_type_for_plan("Enterprise"), "enterprise");
+        assert_eq!(account_type_for_plan("individual"), "individual");
+        assert_eq!(account_type_for_plan("individual_pro"), "individual");
+        assert_eq!(account_type_for_plan("free"), "individual");
+        assert_eq!(account_type_for_plan(""), "individual");
+    }
+
+    fn test_state(token: &str) -> crate::state::AppState {
+        let config = crate::state::AppConfig { copilot_token: Some(token.to_string()), ..Default::default() };
+        crate::state::AppState::new(config, reqwest::Client::new(), None)
+    }
+
+    #[tokio::test]
+    async fn concurrent_unauthorized_share_one_refresh() {
+        let state = test_state("stale");
+        let fetches = Arc::new(AtomicU32::new(0));
+        let refreshes = (0..8).map(|_| {
+            let (state, fetches) = (state.clone(), fetches.clone());
+            tokio::spawn(async move {
+                refresh_token_once(&state, "stale", || async {
+                    fetches.fetch_add(1, Ordering::SeqCst);
+                    // Requests arriving mid-refresh still see the old token, so
+                    // they never take the fetch-and-schedule path.
+                    assert_eq!(state.config.read().await.copilot_token.as_deref(), Some("stale"));
+                    assert_eq!(ensure_copilot_token(&state).await.unwrap(), "stale");
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    Ok("fresh".to_string())
+                })
+                .await
+            })
+        });
+
+        for refresh in futures::future::join_all(refreshes).await {
+            assert_eq!(refresh.unwrap().unwrap(), "fresh");
         }
-    });
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(state.config.read().await.copilot_token.as_deref(), Some("fresh"));
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_keeps_the_old_token() {
+        let state = test_state("stale");
+        let err = refresh_token_once(&state, "stale", || async {
+            Err(crate::errors::ApiError::Upstream("boom".to_string()))
+        })
+        .await;
+        assert!(err.is_err());
+        assert_eq!(state.config.read().await.copilot_token.as_deref(), Some("stale"));
+        assert_eq!(ensure_copilot_token(&state).await.unwrap(), "stale");
+    }
 }
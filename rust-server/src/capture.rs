@@ -0,0 +1,133 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE};
+
+/// COPILOT_CAPTURE_DIR: when set, upstream requests and responses are written
+/// there verbatim for debugging translators.
+pub fn capture_dir() -> Option<PathBuf> {
+    std::env::var("COPILOT_CAPTURE_DIR").ok().filter(|d| !d.trim().is_empty()).map(PathBuf::from)
+}
+
+#[derive(Debug)]
+pub struct Capture {
+    dir: PathBuf,
+    prefix: String,
+}
+
+impl Capture {
+    /// Writes `<timestamp>-<model>-request.json` if capturing is enabled.
+    pub fn start(model: &str, headers: &HeaderMap, body: &impl serde::Serialize) -> Option<Self> {
+        Self::start_in(&capture_dir()?, model, headers, body)
+    }
+
+    fn start_in(dir: &Path, model: &str, headers: &HeaderMap, body: &impl serde::Serialize) -> Option<Self> {
+        let safe_model: String = model
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+            .collect();
+        let prefix = format!("{}-{}", chrono::Local::now().format("%Y%m%dT%H%M%S%.3f"), safe_model);
+        let headers: serde_json::Map<String, serde_json::Value> = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if name == AUTHORIZATION { "[redacted]" } else { value.to_str().unwrap_or("[binary]") };
+                (name.to_string(), serde_json::json!(value))
+            })
+            .collect();
+        let request = serde_json::json!({ "headers": headers, "body": body });
+
+        let result = std::fs::create_dir_all(dir).and_then(|_| {
+            std::fs::write(
+                dir.join(format!("{prefix}-request.json")),
+                serde_json::to_vec_pretty(&request).unwrap_or_default(),
+            )
+        });
+        if let Err(err) = result {
+            tracing::warn!("Failed to write capture to {}: {}", dir.display(), err);
+            return None;
+        }
+        Some(Self { dir: dir.to_path_buf(), prefix })
+    }
+
+    /// Tees the response body into `-response.json` (or `-response.sse` for
+    /// event streams) as the caller reads it.
+    pub fn wrap(self, resp: reqwest::Response) -> reqwest::Response {
+        let is_sse = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let path = self.dir.join(format!("{}-response.{}", self.prefix, if is_sse { "sse" } else { "json" }));
+        let mut file = match std::fs::File::create(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                tracing::warn!("Failed to create capture file {}: {}", path.display(), err);
+                return resp;
+            }
+        };
+
+        let mut builder = axum::http::Response::builder().status(resp.status()).version(resp.version());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = resp.headers().clone();
+        }
+        let body = resp.bytes_stream().inspect(move |chunk| {
+            if let Ok(bytes) = chunk {
+                let _ = file.write_all(bytes);
+            }
+        });
+        let response = builder
+            .body(reqwest::Body::wrap_stream(body))
+            .expect("parts copied from a valid response");
+        reqwest::Response::from(response)
+    }
+}
+
+/// Wraps `resp` when `capture` is active.
+pub fn tee(capture: Option<Capture>, resp: reqwest::Response) -> reqwest::Response {
+    match capture {
+        Some(capture) => capture.wrap(resp),
+        None => resp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Capture;
+    use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+
+    #[tokio::test]
+    async fn writes_request_and_response_captures() {
+        let dir = std::env::temp_dir().join(format!("copilot-capture-{}", std::process::id()));
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer tid=secret"));
+        headers.insert("x-initiator", HeaderValue::from_static("user"));
+
+        let capture = Capture::start_in(&dir, "gpt-4o/mini", &headers, &serde_json::json!({"model": "gpt-4o"}))
+            .expect("capture enabled");
+        let upstream = reqwest::Response::from(
+            axum::http::Response::builder()
+                .header("content-type", "application/json")
+                .body(r#"{"id":"chatcmpl-1"}"#)
+                .unwrap(),
+        );
+        let resp = capture.wrap(upstream);
+        assert_eq!(resp.text().await.unwrap(), r#"{"id":"chatcmpl-1"}"#);
+
+        let mut files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].ends_with("-gpt-4o_mini-request.json"));
+        assert!(files[1].ends_with("-gpt-4o_mini-response.json"));
+
+        let request: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join(&files[0])).unwrap()).unwrap();
+        assert_eq!(request["headers"]["authorization"], "[redacted]");
+        assert_eq!(request["headers"]["x-initiator"], "user");
+        assert_eq!(request["body"]["model"], "gpt-4o");
+        assert_eq!(std::fs::read_to_string(dir.join(&files[1])).unwrap(), r#"{"id":"chatcmpl-1"}"#);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -9,6 +9,7 @@ pub struct Cli {
     #[arg(long, default_value = "127.0.0.1:4141")]
     pub addr: String,
 
+    /// individual, business, enterprise, or auto to detect it from the Copilot plan
     #[arg(long, default_value = "individual")]
     pub account_type: String,
 
@@ -35,6 +36,50 @@ pub struct Cli {
 
     #[arg(long, default_value_t = false)]
     pub claude_code: bool,
+
+    /// Claude Code main model; skips the interactive prompt
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Claude Code small/fast model; skips the interactive prompt
+    #[arg(long)]
+    pub small_model: Option<String>,
+
+    /// Claude Code helper: skip prompts and only print the env blocks
+    #[arg(long, alias = "dry-run", default_value_t = false)]
+    pub non_interactive: bool,
+
+    /// Allow binding to a non-loopback address without COPILOT_API_KEY
+    #[arg(long, default_value_t = false)]
+    pub allow_insecure: bool,
+
+    /// Load the named tokenizer (e.g. o200k_base) at startup
+    #[arg(long)]
+    pub preload_tokenizer: Option<String>,
+
+    /// Write the bound port to this file once listening (handy with port 0)
+    #[arg(long)]
+    pub port_file: Option<String>,
+
+    /// Hold back the "listening" log and port file until the token and models are loaded
+    #[arg(long, default_value_t = false)]
+    pub wait_ready: bool,
+
+    /// Retry binding the listen address this many times (500ms apart) before giving up
+    #[arg(long, default_value_t = 0)]
+    pub bind_retries: u32,
+
+    /// Serve /v1/models from this JSON file (ModelsResponse shape) instead of Copilot
+    #[arg(long)]
+    pub models_file: Option<String>,
+
+    /// Log output format: text (default) or json
+    #[arg(long, global = true, value_parser = ["text", "json"])]
+    pub log_format: Option<String>,
+
+    /// Only log errors; --verbose and COPILOT_LOG still win
+    #[arg(long, short = 'q', global = true, default_value_t = false)]
+    pub quiet: bool,
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -44,13 +89,26 @@ pub enum Command {
     /// Run GitHub device auth flow
     Auth(AuthArgs),
     /// Show Copilot usage/quota information
-    CheckUsage,
+    CheckUsage(CheckUsageArgs),
+    /// List available models
+    Models(ModelsArgs),
     /// Print debug information
     Debug(DebugArgs),
     /// Run Claude hooks processor
+    #[command(alias = "hooks")]
     Hook(HookArgs),
     /// Sync everything-claude-code skills into .claude/skills
     SyncSkills,
+    /// Print the version, optionally checking for a newer release
+    Version(VersionArgs),
+    /// Print where the token, config and hook files live
+    Paths(PathsArgs),
+    /// List or prune session files saved by the hook builtins
+    Sessions(SessionsArgs),
+    /// Print the Claude Code environment in a machine-readable format
+    Export(ExportArgs),
+    /// Show locally recorded requests and tokens per model
+    Stats(StatsArgs),
 }
 
 #[derive(Debug, Clone, Args)]
@@ -61,6 +119,7 @@ pub struct StartArgs {
     #[arg(long, default_value = "127.0.0.1")]
     pub host: String,
 
+    /// individual, business, enterprise, or auto to detect it from the Copilot plan
     #[arg(long, default_value = "individual")]
     pub account_type: String,
 
@@ -87,6 +146,42 @@ pub struct StartArgs {
 
     #[arg(long, default_value_t = false)]
     pub claude_code: bool,
+
+    /// Claude Code main model; skips the interactive prompt
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Claude Code small/fast model; skips the interactive prompt
+    #[arg(long)]
+    pub small_model: Option<String>,
+
+    /// Claude Code helper: skip prompts and only print the env blocks
+    #[arg(long, alias = "dry-run", default_value_t = false)]
+    pub non_interactive: bool,
+
+    /// Allow binding to a non-loopback address without COPILOT_API_KEY
+    #[arg(long, default_value_t = false)]
+    pub allow_insecure: bool,
+
+    /// Load the named tokenizer (e.g. o200k_base) at startup
+    #[arg(long)]
+    pub preload_tokenizer: Option<String>,
+
+    /// Write the bound port to this file once listening (handy with port 0)
+    #[arg(long)]
+    pub port_file: Option<String>,
+
+    /// Hold back the "listening" log and port file until the token and models are loaded
+    #[arg(long, default_value_t = false)]
+    pub wait_ready: bool,
+
+    /// Retry binding the listen address this many times (500ms apart) before giving up
+    #[arg(long, default_value_t = 0)]
+    pub bind_retries: u32,
+
+    /// Serve /v1/models from this JSON file (ModelsResponse shape) instead of Copilot
+    #[arg(long)]
+    pub models_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Args)]
@@ -94,6 +189,10 @@ pub struct AuthArgs {
     #[arg(long, default_value_t = false)]
     pub show_token: bool,
 
+    /// Open the verification URL in the browser and copy the code to the clipboard
+    #[arg(long, default_value_t = false)]
+    pub open: bool,
+
     #[arg(long, short = 'v', default_value_t = false)]
     pub verbose: bool,
 }
@@ -104,6 +203,37 @@ pub struct DebugArgs {
     pub json: bool,
 }
 
+#[derive(Debug, Clone, Args)]
+pub struct CheckUsageArgs {
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct VersionArgs {
+    /// Check GitHub for a newer release
+    #[arg(long, default_value_t = false)]
+    pub check: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PathsArgs {
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ModelsArgs {
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct StatsArgs {
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
 #[derive(Debug, Clone, Args)]
 pub struct HookArgs {
     #[arg(long)]
@@ -111,4 +241,69 @@ pub struct HookArgs {
 
     #[arg(long)]
     pub config: Option<String>,
+
+    /// Read the hook input JSON from a file instead of stdin
+    #[arg(long)]
+    pub input: Option<String>,
+
+    /// Print every hook result (exit code, stdout, stderr) as JSON
+    #[arg(long, default_value_t = false)]
+    pub print_results: bool,
+
+    #[command(subcommand)]
+    pub action: Option<HookAction>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportArgs {
+    #[arg(long, default_value = "bash", value_parser = ["json", "bash", "powershell", "dotenv"])]
+    pub format: String,
+
+    #[arg(long, default_value = "http://127.0.0.1:4141")]
+    pub server_url: String,
+
+    #[arg(long)]
+    pub model: Option<String>,
+
+    #[arg(long)]
+    pub small_model: Option<String>,
+
+    /// Write to this file (e.g. .env) instead of stdout
+    #[arg(long, short = 'o')]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct SessionsArgs {
+    #[command(subcommand)]
+    pub action: SessionsAction,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum SessionsAction {
+    /// Show saved session files with their modification dates
+    List,
+    /// Delete session and pre-compact files older than the given age
+    Clean(SessionsCleanArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct SessionsCleanArgs {
+    /// Age in days; files modified before this are removed
+    #[arg(long)]
+    pub older_than: u32,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum HookAction {
+    /// Check a hooks.json against the bundled JSON Schema
+    Validate(HookValidateArgs),
+    /// Print the bundled hooks.json JSON Schema
+    Schema,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct HookValidateArgs {
+    /// Defaults to the hooks.json the server would load
+    pub path: Option<String>,
 }
@@ -1,13 +1,21 @@
 use crate::{
+    config::{http_client_builder, GITHUB_API_BASE_URL},
     auth_flow::ensure_github_token,
     auth_flow::ensure_copilot_token,
-    errors::ApiResult,
-    paths::get_paths,
+    errors::{ApiError, ApiResult},
+    hooks::{self, claude_paths, executor::resolve_hooks_path},
+    paths::{ensure_paths, get_paths},
+    routes::models::openai_model_list,
     services::{github::get_copilot_usage, copilot::get_models},
     state::AppState,
     token_store::read_github_token,
 };
+use chrono::{DateTime, Local};
 use dialoguer::Select;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use serde::{Deserialize, Serialize};
 
 pub async fn run_debug(json: bool) -> ApiResult<()> {
     let version = env!("CARGO_PKG_VERSION");
@@ -50,11 +58,238 @@ pub async fn run_debug(json: bool) -> ApiResult<()> {
     Ok(())
 }
 
-pub async fn run_check_usage(state: &AppState) -> ApiResult<()> {
+pub async fn run_paths(json: bool) -> ApiResult<()> {
+    let paths = get_paths()?;
+    let entries = [
+        ("APP_DIR", paths.app_dir),
+        ("GITHUB_TOKEN_PATH", paths.github_token_path),
+        ("USAGE_STATS", paths.usage_stats_path),
+        ("OBSERVATIONS_FILE", claude_paths::observations_file()?),
+        ("SESSIONS_DIR", claude_paths::sessions_dir()?),
+        ("LEARNED_SKILLS_DIR", claude_paths::learned_skills_dir()?),
+        ("HOOKS_JSON", resolve_hooks_path(None)?),
+    ];
+
+    if json {
+        let map: serde_json::Map<String, serde_json::Value> = entries
+            .iter()
+            .map(|(name, path)| (name.to_string(), serde_json::json!(path.to_string_lossy())))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&map).unwrap_or_else(|_| "{}".to_string()));
+    } else {
+        for (name, path) in &entries {
+            println!("{}: {}", name, path.display());
+        }
+    }
+    Ok(())
+}
+
+pub fn run_stats(json: bool) -> ApiResult<()> {
+    let path = crate::stats::stats_path()?;
+    let stats = crate::stats::UsageStats::load(&path);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats).unwrap_or_else(|_| "{}".to_string()));
+        return Ok(());
+    }
+    if stats.models.is_empty() {
+        println!("No usage recorded in {}", path.display());
+        return Ok(());
+    }
+    println!("{:<32} {:>10} {:>14} {:>14}", "MODEL", "REQUESTS", "INPUT", "OUTPUT");
+    for (model, counts) in &stats.models {
+        println!(
+            "{:<32} {:>10} {:>14} {:>14}",
+            model, counts.requests, counts.input_tokens, counts.output_tokens
+        );
+    }
+    Ok(())
+}
+
+/// Files written by the `session_end` and `pre_compact` builtins, oldest first.
+fn session_files(dir: &Path) -> ApiResult<Vec<(PathBuf, DateTime<Local>)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).max_depth(1) {
+        let entry = entry.map_err(|e| ApiError::Internal(format!("Failed to read sessions dir: {e}")))?;
+        let name = entry.file_name().to_string_lossy();
+        let is_session = name.ends_with("-session.tmp") || (name.starts_with("pre-compact-") && name.ends_with(".json"));
+        if !entry.file_type().is_file() || !is_session {
+            continue;
+        }
+        if let Some(modified) = entry.metadata().ok().and_then(|m| m.modified().ok()) {
+            files.push((entry.path().to_path_buf(), modified.into()));
+        }
+    }
+    files.sort_by_key(|(_, modified)| *modified);
+    Ok(files)
+}
+
+fn clean_sessions(dir: &Path, cutoff: DateTime<Local>) -> ApiResult<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for (path, modified) in session_files(dir)? {
+        if modified < cutoff {
+            std::fs::remove_file(&path)
+                .map_err(|e| ApiError::Internal(format!("Failed to remove {}: {e}", path.display())))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+pub fn run_sessions_list() -> ApiResult<()> {
+    let dir = claude_paths::sessions_dir()?;
+    let files = session_files(&dir)?;
+    if files.is_empty() {
+        println!("No sessions in {}", dir.display());
+    }
+    for (path, modified) in &files {
+        println!("{}  {}", modified.format("%Y-%m-%d %H:%M"), path.display());
+    }
+    Ok(())
+}
+
+pub fn run_sessions_clean(older_than_days: u32) -> ApiResult<()> {
+    let dir = claude_paths::sessions_dir()?;
+    let cutoff = Local::now() - chrono::Duration::days(older_than_days.into());
+    let removed = clean_sessions(&dir, cutoff)?;
+    for path in &removed {
+        println!("Removed {}", path.display());
+    }
+    println!("Removed {} session file(s)", removed.len());
+    Ok(())
+}
+
+/// Prints schema errors for a hooks.json; returns whether it is valid.
+pub fn run_hook_validate(path: Option<&str>) -> ApiResult<bool> {
+    let path = resolve_hooks_path(path.map(std::path::PathBuf::from))?;
+    let source = std::fs::read_to_string(&path)
+        .map_err(|e| ApiError::Internal(format!("Failed to read {}: {e}", path.display())))?;
+    let errors = hooks::schema::validate_hooks_json(&source);
+    if errors.is_empty() {
+        println!("{}: ok", path.display());
+        return Ok(true);
+    }
+    for error in &errors {
+        eprintln!("{}: {}", path.display(), error);
+    }
+    Ok(false)
+}
+
+const RELEASES_REPO: &str = "GTAStudio/copilot-api-rust";
+
+pub async fn run_version(check: bool) -> ApiResult<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    println!("copilot-api-rs {}", current);
+    if !check {
+        return Ok(());
+    }
+
+    match fetch_latest_release().await {
+        Some((tag, url)) if is_newer(&tag, current) => {
+            println!("Update available: {} -> {}\n{}", current, tag.trim_start_matches('v'), url);
+        }
+        Some(_) => println!("You are running the latest release."),
+        None => {}
+    }
+    Ok(())
+}
+
+async fn fetch_latest_release() -> Option<(String, String)> {
+    let client = http_client_builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .ok()?;
+    let release = client
+        .get(format!("{GITHUB_API_BASE_URL}/repos/{RELEASES_REPO}/releases/latest"))
+        .header("accept", "application/vnd.github+json")
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json::<serde_json::Value>()
+        .await
+        .ok()?;
+    let tag = release.get("tag_name")?.as_str()?.to_string();
+    let url = release
+        .get("html_url")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("https://github.com/{RELEASES_REPO}/releases/tag/{tag}"));
+    Some((tag, url))
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+fn is_newer(latest: &str, current: &str) -> bool {
+    let (mut latest, mut current) = (parse_version(latest), parse_version(current));
+    let len = latest.len().max(current.len());
+    latest.resize(len, 0);
+    current.resize(len, 0);
+    latest > current
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+struct QuotaUsage {
+    used: f64,
+    entitlement: f64,
+    percent_used: f64,
+    percent_remaining: f64,
+}
+
+fn parse_quota(usage: &serde_json::Value, name: &str) -> Option<QuotaUsage> {
+    let map = usage
+        .get("quota_snapshots")
+        .and_then(|s| s.get(name))
+        .and_then(|v| v.as_object())?;
+    let entitlement = map.get("entitlement").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let remaining = map.get("remaining").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let percent_remaining = map.get("percent_remaining").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let used = entitlement - remaining;
+    let percent_used = if entitlement > 0.0 { (used / entitlement) * 100.0 } else { 0.0 };
+    Some(QuotaUsage {
+        used,
+        entitlement,
+        percent_used,
+        percent_remaining,
+    })
+}
+
+fn usage_summary(usage: &serde_json::Value) -> serde_json::Value {
+    let field = |key: &str| usage.get(key).and_then(|v| v.as_str()).unwrap_or("unknown").to_string();
+    serde_json::json!({
+        "plan": field("copilot_plan"),
+        "reset": field("quota_reset_date"),
+        "quotas": {
+            "premium": parse_quota(usage, "premium_interactions"),
+            "chat": parse_quota(usage, "chat"),
+            "completions": parse_quota(usage, "completions"),
+        },
+    })
+}
+
+pub async fn run_check_usage(state: &AppState, json: bool) -> ApiResult<()> {
     let github_token = ensure_github_token(state).await?;
     let config = state.config.read().await.clone();
     let usage = get_copilot_usage(&state.client, &config, &github_token).await?;
 
+    if json {
+        println!("{}", serde_json::to_string_pretty(&usage_summary(&usage)).unwrap_or_else(|_| "{}".to_string()));
+        return Ok(());
+    }
+
     let plan = usage
         .get("copilot_plan")
         .and_then(|v| v.as_str())
@@ -65,25 +300,18 @@ pub async fn run_check_usage(state: &AppState) -> ApiResult<()> {
         .and_then(|v| v.as_str())
         .unwrap_or("unknown");
 
-    let snapshots = usage.get("quota_snapshots").and_then(|v| v.as_object());
-
     let format_quota = |name: &str| -> String {
-        if let Some(map) = snapshots.and_then(|s| s.get(name)).and_then(|v| v.as_object()) {
-            let entitlement = map.get("entitlement").and_then(|v| v.as_f64()).unwrap_or(0.0);
-            let remaining = map.get("remaining").and_then(|v| v.as_f64()).unwrap_or(0.0);
-            let percent_remaining = map.get("percent_remaining").and_then(|v| v.as_f64()).unwrap_or(0.0);
-            let used = entitlement - remaining;
-            let percent_used = if entitlement > 0.0 { (used / entitlement) * 100.0 } else { 0.0 };
-            return format!(
+        match parse_quota(&usage, name) {
+            Some(quota) => format!(
                 "{}: {}/{} used ({:.1}% used, {:.1}% remaining)",
                 name,
-                used.round(),
-                entitlement.round(),
-                percent_used,
-                percent_remaining,
-            );
+                quota.used.round(),
+                quota.entitlement.round(),
+                quota.percent_used,
+                quota.percent_remaining,
+            ),
+            None => format!("{}: N/A", name),
         }
-        format!("{}: N/A", name)
     };
 
     let premium = format_quota("premium_interactions");
@@ -98,7 +326,69 @@ pub async fn run_check_usage(state: &AppState) -> ApiResult<()> {
     Ok(())
 }
 
-pub async fn run_claude_code_helper(state: &AppState, server_url: &str) -> ApiResult<()> {
+pub async fn run_models(state: &AppState, json: bool) -> ApiResult<()> {
+    let token = ensure_copilot_token(state).await?;
+    let config = state.config.read().await.clone();
+    let models = get_models(&state.client, &config, &token).await?;
+    let data = openai_model_list(&models);
+
+    if json {
+        let list = serde_json::json!({
+            "object": "list",
+            "data": data,
+            "has_more": false,
+        });
+        println!("{}", serde_json::to_string_pretty(&list).unwrap_or_else(|_| "{}".to_string()));
+    } else {
+        for model in &data {
+            if let Some(id) = model.get("id").and_then(|v| v.as_str()) {
+                println!("{}", id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ClaudeCodeSelection {
+    model: Option<String>,
+    small_model: Option<String>,
+}
+
+async fn load_claude_code_selection() -> ClaudeCodeSelection {
+    let Ok(paths) = get_paths() else {
+        return ClaudeCodeSelection::default();
+    };
+    tokio::fs::read_to_string(paths.claude_code_models_path)
+        .await
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+async fn save_claude_code_selection(selection: &ClaudeCodeSelection) -> ApiResult<()> {
+    let paths = ensure_paths().await?;
+    let json = serde_json::to_string_pretty(selection)
+        .map_err(|e| ApiError::Internal(format!("Failed to serialize model selection: {e}")))?;
+    tokio::fs::write(paths.claude_code_models_path, json)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to save model selection: {e}")))
+}
+
+fn saved_index(model_ids: &[String], saved: Option<&str>) -> usize {
+    saved
+        .and_then(|saved| model_ids.iter().position(|id| id == saved))
+        .unwrap_or(0)
+}
+
+pub async fn run_claude_code_helper(
+    state: &AppState,
+    server_url: &str,
+    model_override: Option<&str>,
+    small_model_override: Option<&str>,
+    non_interactive: bool,
+) -> ApiResult<()> {
     let token = ensure_copilot_token(state).await?;
 
     if state.config.read().await.models.is_none() {
@@ -115,24 +405,71 @@ pub async fn run_claude_code_helper(state: &AppState, server_url: &str) -> ApiRe
         return Ok(());
     }
 
-    let selected = Select::new()
-        .with_prompt("Select a model to use with Claude Code")
-        .items(&model_ids)
-        .default(0)
-        .interact()
-        .unwrap_or(0);
+    if non_interactive || !std::io::stdin().is_terminal() {
+        let (model, small_model) = non_interactive_selection(&model_ids, model_override, small_model_override);
+        print_claude_code_env(server_url, &model, &small_model);
+        return Ok(());
+    }
+
+    let saved = load_claude_code_selection().await;
+
+    let model = match model_override {
+        Some(model) => model.to_string(),
+        None => {
+            let selected = Select::new()
+                .with_prompt("Select a model to use with Claude Code")
+                .items(&model_ids)
+                .default(saved_index(&model_ids, saved.model.as_deref()))
+                .interact()
+                .unwrap_or(0);
+            model_ids[selected].clone()
+        }
+    };
+
+    let small_model = match small_model_override {
+        Some(small_model) => small_model.to_string(),
+        None => {
+            let default = saved_index(&model_ids, saved.small_model.as_deref().or(Some(model.as_str())));
+            let selected_small = Select::new()
+                .with_prompt("Select a small model to use with Claude Code")
+                .items(&model_ids)
+                .default(default)
+                .interact()
+                .unwrap_or(default);
+            model_ids[selected_small].clone()
+        }
+    };
 
-    let selected_small = Select::new()
-        .with_prompt("Select a small model to use with Claude Code")
-        .items(&model_ids)
-        .default(selected)
-        .interact()
-        .unwrap_or(selected);
+    let selection = ClaudeCodeSelection {
+        model: Some(model.clone()),
+        small_model: Some(small_model.clone()),
+    };
+    if let Err(err) = save_claude_code_selection(&selection).await {
+        tracing::warn!("Failed to persist Claude Code model selection: {}", err);
+    }
 
-    let model = &model_ids[selected];
-    let small_model = &model_ids[selected_small];
+    print_claude_code_env(server_url, &model, &small_model);
+    Ok(())
+}
 
-    let envs = vec![
+fn non_interactive_selection(
+    model_ids: &[String],
+    model_override: Option<&str>,
+    small_model_override: Option<&str>,
+) -> (String, String) {
+    let model = match model_override {
+        Some(model) => model.to_string(),
+        None => {
+            tracing::warn!("No --model given in non-interactive mode, using {}", model_ids[0]);
+            model_ids[0].clone()
+        }
+    };
+    let small_model = small_model_override.map(str::to_string).unwrap_or_else(|| model.clone());
+    (model, small_model)
+}
+
+fn claude_code_env(server_url: &str, model: &str, small_model: &str) -> Vec<(&'static str, String)> {
+    vec![
         ("ANTHROPIC_BASE_URL", server_url.to_string()),
         ("ANTHROPIC_AUTH_TOKEN", "dummy".to_string()),
         ("ANTHROPIC_MODEL", model.to_string()),
@@ -141,21 +478,28 @@ pub async fn run_claude_code_helper(state: &AppState, server_url: &str) -> ApiRe
         ("ANTHROPIC_DEFAULT_HAIKU_MODEL", small_model.to_string()),
         ("DISABLE_NON_ESSENTIAL_MODEL_CALLS", "1".to_string()),
         ("CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC", "1".to_string()),
-    ];
+    ]
+}
 
-    let bash_cmd = envs
-        .iter()
-        .map(|(k, v)| format!("export {}=\"{}\"", k, v))
-        .collect::<Vec<_>>()
-        .join("\n")
-        + "\nclaude\n";
+/// Renders the env map as `json`, `powershell`, `dotenv` or (default) `bash`.
+fn render_env(envs: &[(&str, String)], format: &str) -> String {
+    let line = |(k, v): &(&str, String)| match format {
+        "powershell" => format!("$env:{}=\"{}\"", k, v),
+        "dotenv" => format!("{}={}", k, v),
+        _ => format!("export {}=\"{}\"", k, v),
+    };
+    if format == "json" {
+        let map: serde_json::Map<String, serde_json::Value> =
+            envs.iter().map(|(k, v)| (k.to_string(), serde_json::json!(v))).collect();
+        return serde_json::to_string_pretty(&map).unwrap_or_else(|_| "{}".to_string());
+    }
+    envs.iter().map(line).collect::<Vec<_>>().join("\n")
+}
 
-    let ps_cmd = envs
-        .iter()
-        .map(|(k, v)| format!("$env:{}=\"{}\"", k, v))
-        .collect::<Vec<_>>()
-        .join("\n")
-        + "\nclaude\n";
+fn print_claude_code_env(server_url: &str, model: &str, small_model: &str) {
+    let envs = claude_code_env(server_url, model, small_model);
+    let bash_cmd = render_env(&envs, "bash") + "\nclaude\n";
+    let ps_cmd = render_env(&envs, "powershell") + "\nclaude\n";
 
     if std::env::var("COPILOT_CLIPBOARD")
         .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
@@ -168,6 +512,148 @@ pub async fn run_claude_code_helper(state: &AppState, server_url: &str) -> ApiRe
 
     println!("\nClaude Code environment (bash/zsh):\n{}", bash_cmd);
     println!("Claude Code environment (PowerShell):\n{}", ps_cmd);
+}
 
-    Ok(())
+/// Emits the Claude Code env without prompting: explicit models win, then the
+/// saved selection, then the first model Copilot lists.
+pub async fn run_export(
+    state: &AppState,
+    server_url: &str,
+    model_override: Option<&str>,
+    small_model_override: Option<&str>,
+    format: &str,
+    output: Option<&str>,
+) -> ApiResult<()> {
+    let saved = load_claude_code_selection().await;
+    let model = match model_override.map(str::to_string).or(saved.model) {
+        Some(model) => model,
+        None => {
+            let token = ensure_copilot_token(state).await?;
+            let config = state.config.read().await.clone();
+            let models = get_models(&state.client, &config, &token).await?;
+            let model_ids: Vec<String> = models.data.iter().map(|m| m.id.clone()).collect();
+            if model_ids.is_empty() {
+                return Err(ApiError::Upstream("No models available".to_string()));
+            }
+            non_interactive_selection(&model_ids, None, None).0
+        }
+    };
+    let small_model = small_model_override
+        .map(str::to_string)
+        .or(saved.small_model)
+        .unwrap_or_else(|| model.clone());
+
+    let rendered = render_env(&claude_code_env(server_url, &model, &small_model), format) + "\n";
+    match output {
+        Some(path) => std::fs::write(path, rendered)
+            .map_err(|e| ApiError::Internal(format!("Failed to write {path}: {e}"))),
+        None => {
+            print!("{}", rendered);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{claude_code_env, clean_sessions, is_newer, render_env, non_interactive_selection, saved_index, session_files, usage_summary};
+
+    #[test]
+    fn non_interactive_selection_uses_overrides_or_first_model() {
+        let ids = vec!["gpt-4o".to_string(), "claude-sonnet-4".to_string()];
+        assert_eq!(
+            non_interactive_selection(&ids, Some("claude-sonnet-4"), Some("gpt-4o")),
+            ("claude-sonnet-4".to_string(), "gpt-4o".to_string())
+        );
+        assert_eq!(
+            non_interactive_selection(&ids, Some("claude-sonnet-4"), None),
+            ("claude-sonnet-4".to_string(), "claude-sonnet-4".to_string())
+        );
+        assert_eq!(non_interactive_selection(&ids, None, None), ("gpt-4o".to_string(), "gpt-4o".to_string()));
+    }
+
+    #[test]
+    fn clean_sessions_removes_only_files_older_than_cutoff() {
+        let dir = std::env::temp_dir().join(format!("copilot-sessions-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(10 * 24 * 60 * 60);
+        for name in ["2024-01-01-abcd1234-session.tmp", "pre-compact-old.json", "notes.txt"] {
+            let file = std::fs::File::create(dir.join(name)).unwrap();
+            file.set_modified(old).unwrap();
+        }
+        std::fs::write(dir.join("2099-01-01-efgh5678-session.tmp"), "{}").unwrap();
+
+        assert_eq!(session_files(&dir).unwrap().len(), 3);
+        let cutoff = chrono::Local::now() - chrono::Duration::days(7);
+        let removed = clean_sessions(&dir, cutoff).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(!dir.join("pre-compact-old.json").exists());
+        assert!(!dir.join("2024-01-01-abcd1234-session.tmp").exists());
+        assert!(dir.join("2099-01-01-efgh5678-session.tmp").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(session_files(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn export_formats_render_every_key() {
+        let envs = claude_code_env("http://127.0.0.1:4141", "gpt-4o", "gpt-4o-mini");
+        let json: serde_json::Value = serde_json::from_str(&render_env(&envs, "json")).unwrap();
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        for (key, value) in &envs {
+            assert!(keys.contains(key), "missing {key}");
+            assert_eq!(json[key], value.as_str());
+        }
+        assert_eq!(keys.len(), 8);
+        assert_eq!(json["ANTHROPIC_SMALL_FAST_MODEL"], "gpt-4o-mini");
+
+        let dotenv = render_env(&envs, "dotenv");
+        assert!(dotenv.lines().any(|l| l == "ANTHROPIC_BASE_URL=http://127.0.0.1:4141"));
+        assert_eq!(dotenv.lines().count(), 8);
+        assert!(render_env(&envs, "bash").starts_with("export ANTHROPIC_BASE_URL=\"http://127.0.0.1:4141\""));
+        assert!(render_env(&envs, "powershell").starts_with("$env:ANTHROPIC_BASE_URL=\"http://127.0.0.1:4141\""));
+    }
+
+    #[test]
+    fn compares_release_versions() {
+        assert!(is_newer("v0.2.0", "0.1.9"));
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(is_newer("v1.0", "0.99.99"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("0.1", "0.1.0"));
+        assert!(!is_newer("v0.1.0-beta.1", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+    }
+
+    #[test]
+    fn saved_index_preselects_saved_model_or_falls_back_to_first() {
+        let ids = vec!["gpt-4o".to_string(), "gpt-5.1".to_string(), "claude-sonnet-4".to_string()];
+        assert_eq!(saved_index(&ids, Some("claude-sonnet-4")), 2);
+        assert_eq!(saved_index(&ids, Some("retired-model")), 0);
+        assert_eq!(saved_index(&ids, None), 0);
+    }
+
+    #[test]
+    fn usage_summary_emits_numeric_quotas() {
+        let usage = serde_json::json!({
+            "copilot_plan": "business",
+            "quota_reset_date": "2025-07-01",
+            "quota_snapshots": {
+                "premium_interactions": { "entitlement": 300.0, "remaining": 240.0, "percent_remaining": 80.0 },
+                "chat": { "entitlement": 0.0, "remaining": 0.0, "percent_remaining": 100.0 }
+            }
+        });
+
+        let summary = usage_summary(&usage);
+        assert_eq!(summary["plan"], "business");
+        assert_eq!(summary["reset"], "2025-07-01");
+        let premium = &summary["quotas"]["premium"];
+        assert_eq!(premium["used"], 60.0);
+        assert_eq!(premium["entitlement"], 300.0);
+        assert_eq!(premium["percent_used"], 20.0);
+        assert_eq!(premium["percent_remaining"], 80.0);
+        assert_eq!(summary["quotas"]["chat"]["percent_used"], 0.0);
+        assert!(summary["quotas"]["completions"].is_null());
+    }
 }
@@ -19,22 +19,51 @@ pub fn standard_headers() -> Vec<(String, String)> {
 }
 
 pub fn copilot_base_url(config: &AppConfig) -> String {
-    if config.account_type == "individual" {
-        "https://api.githubcopilot.com".to_string()
-    } else {
-        format!("https://api.{}.githubcopilot.com", config.account_type)
+    if let Some(base) = std::env::var("COPILOT_API_BASE_URL").ok().filter(|v| !v.trim().is_empty()) {
+        return base.trim().trim_end_matches('/').to_string();
     }
+    account_base_url(&config.account_type).to_string()
+}
+
+fn account_base_url(account_type: &str) -> &'static str {
+    match account_type {
+        "individual" => "https://api.githubcopilot.com",
+        "business" => "https://api.business.githubcopilot.com",
+        "enterprise" => "https://api.enterprise.githubcopilot.com",
+        other => {
+            tracing::warn!("Unknown account type {:?}, using the individual Copilot API base", other);
+            "https://api.githubcopilot.com"
+        }
+    }
+}
+
+fn env_override(key: &str, default: String) -> String {
+    std::env::var(key)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or(default)
+}
+
+/// User-agent for outbound calls that don't carry Copilot's own headers.
+/// COPILOT_HTTP_USER_AGENT overrides it for proxies that block unknown agents.
+pub fn http_user_agent() -> String {
+    env_override("COPILOT_HTTP_USER_AGENT", "copilot-api-rs".to_string())
+}
+
+pub fn http_client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder().user_agent(http_user_agent())
 }
 
 pub fn copilot_headers(config: &AppConfig, token: &str, vision: bool) -> Vec<(String, String)> {
-    let editor_plugin_version = format!("copilot-chat/{}", COPILOT_VERSION);
-    let user_agent = format!("GitHubCopilotChat/{}", COPILOT_VERSION);
+    let editor_version = env_override("COPILOT_EDITOR_VERSION", format!("vscode/{}", config.vscode_version));
+    let editor_plugin_version = env_override("COPILOT_PLUGIN_VERSION", format!("copilot-chat/{}", COPILOT_VERSION));
+    let user_agent = env_override("COPILOT_USER_AGENT", format!("GitHubCopilotChat/{}", COPILOT_VERSION));
 
     let mut headers = vec![
         ("authorization".to_string(), format!("Bearer {}", token)),
         ("content-type".to_string(), "application/json".to_string()),
         ("copilot-integration-id".to_string(), "vscode-chat".to_string()),
-        ("editor-version".to_string(), format!("vscode/{}", config.vscode_version)),
+        ("editor-version".to_string(), editor_version),
         ("editor-plugin-version".to_string(), editor_plugin_version),
         ("user-agent".to_string(), user_agent),
         ("openai-intent".to_string(), "conversation-panel".to_string()),
@@ -81,3 +110,110 @@ pub fn apply_headers(map: &mut HeaderMap, headers: Vec<(String, String)>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{copilot_base_url, copilot_headers, http_client_builder};
+    use crate::state::AppConfig;
+    use crate::utils::env_lock;
+
+    const OVERRIDE_KEYS: [&str; 3] = ["COPILOT_EDITOR_VERSION", "COPILOT_PLUGIN_VERSION", "COPILOT_USER_AGENT"];
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> &'a str {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .expect("header present")
+    }
+
+    #[test]
+    fn copilot_headers_use_computed_defaults() {
+        let _lock = env_lock();
+        for key in OVERRIDE_KEYS {
+            unsafe { std::env::remove_var(key) };
+        }
+
+        let headers = copilot_headers(&AppConfig::default(), "tok", false);
+        assert_eq!(header(&headers, "editor-version"), "vscode/1.104.3");
+        assert_eq!(header(&headers, "editor-plugin-version"), "copilot-chat/0.26.7");
+        assert_eq!(header(&headers, "user-agent"), "GitHubCopilotChat/0.26.7");
+    }
+
+    #[test]
+    fn copilot_headers_honor_env_overrides() {
+        let _lock = env_lock();
+        unsafe {
+            std::env::set_var("COPILOT_EDITOR_VERSION", "vscode/1.200.0");
+            std::env::set_var("COPILOT_PLUGIN_VERSION", "copilot-chat/0.99.0");
+            std::env::set_var("COPILOT_USER_AGENT", "GitHubCopilotChat/0.99.0");
+        }
+
+        let headers = copilot_headers(&AppConfig::default(), "tok", false);
+        assert_eq!(header(&headers, "editor-version"), "vscode/1.200.0");
+        assert_eq!(header(&headers, "editor-plugin-version"), "copilot-chat/0.99.0");
+        assert_eq!(header(&headers, "user-agent"), "GitHubCopilotChat/0.99.0");
+
+        for key in OVERRIDE_KEYS {
+            unsafe { std::env::remove_var(key) };
+        }
+    }
+
+    fn config_for(account_type: &str) -> AppConfig {
+        AppConfig { account_type: account_type.to_string(), ..AppConfig::default() }
+    }
+
+    #[test]
+    fn base_url_per_account_type() {
+        let _lock = env_lock();
+        unsafe { std::env::remove_var("COPILOT_API_BASE_URL") };
+
+        assert_eq!(copilot_base_url(&config_for("individual")), "https://api.githubcopilot.com");
+        assert_eq!(copilot_base_url(&config_for("business")), "https://api.business.githubcopilot.com");
+        assert_eq!(copilot_base_url(&config_for("enterprise")), "https://api.enterprise.githubcopilot.com");
+        assert_eq!(copilot_base_url(&config_for("team")), "https://api.githubcopilot.com");
+    }
+
+    #[test]
+    fn base_url_override_wins() {
+        let _lock = env_lock();
+        unsafe { std::env::set_var("COPILOT_API_BASE_URL", "https://copilot-api.example.ghe.com/") };
+        let base = copilot_base_url(&config_for("enterprise"));
+        unsafe { std::env::remove_var("COPILOT_API_BASE_URL") };
+
+        assert_eq!(base, "https://copilot-api.example.ghe.com");
+    }
+
+    async fn sent_user_agent(client: reqwest::Client) -> String {
+        use axum::{http::HeaderMap, routing::get, Router};
+
+        let app = Router::new().route(
+            "/",
+            get(|headers: HeaderMap| async move {
+                headers.get("user-agent").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        client.get(format!("http://{}/", addr)).send().await.unwrap().text().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn http_user_agent_override_applies_to_built_clients() {
+        let (default_client, override_client) = {
+            let _lock = env_lock();
+            unsafe { std::env::remove_var("COPILOT_HTTP_USER_AGENT") };
+            let default_client = http_client_builder().build().unwrap();
+            unsafe { std::env::set_var("COPILOT_HTTP_USER_AGENT", "corp-approved/1.0") };
+            let override_client = http_client_builder().build().unwrap();
+            unsafe { std::env::remove_var("COPILOT_HTTP_USER_AGENT") };
+            (default_client, override_client)
+        };
+
+        assert_eq!(sent_user_agent(default_client).await, "copilot-api-rs");
+        assert_eq!(sent_user_agent(override_client).await, "corp-approved/1.0");
+    }
+}
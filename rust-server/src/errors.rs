@@ -15,6 +15,13 @@ pub enum ApiError {
     Upstream(String),
     #[error("{0}")]
     Internal(String),
+    /// The server is up but has no credentials yet (not authenticated, or
+    /// prewarm still running), as opposed to a token that was rejected.
+    #[error("{0}")]
+    ServiceUnavailable(String),
+    /// An upstream error response relayed to the client with its own status.
+    #[error("{1}")]
+    UpstreamStatus(StatusCode, String),
 }
 
 #[derive(Debug, Serialize)]
@@ -35,6 +42,8 @@ impl ApiError {
             ApiError::NotFound(_) => StatusCode::NOT_FOUND,
             ApiError::Upstream(_) => StatusCode::BAD_GATEWAY,
             ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::UpstreamStatus(status, _) => *status,
         }
     }
 }
@@ -52,3 +61,22 @@ impl IntoResponse for ApiError {
 }
 
 pub type ApiResult<T> = Result<T, ApiError>;
+
+#[cfg(test)]
+mod tests {
+    use super::ApiError;
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    #[tokio::test]
+    async fn service_unavailable_maps_to_503() {
+        let err = ApiError::ServiceUnavailable("GitHub token not found. Run device auth first.".to_string());
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_ne!(err.status_code(), ApiError::Unauthorized(String::new()).status_code());
+
+        let resp = err.into_response();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"]["message"], "GitHub token not found. Run device auth first.");
+    }
+}
@@ -8,7 +8,41 @@ use crate::errors::ApiResult;
 use crate::hooks::{claude_paths, types::{HookInput, HookResult}};
 use crate::errors::ApiError;
 
+pub const BUILTIN_NAMES: &[&str] = &[
+    "session_start",
+    "session_end",
+    "pre_compact",
+    "suggest_compact",
+    "evaluate_session",
+    "check_console_log",
+    "warn_console_log",
+    "block_doc_creation",
+    "tmux_dev_block",
+    "tmux_reminder",
+    "git_push_reminder",
+    "pr_create_notice",
+];
+
 pub fn run_builtin(name: &str, input: &HookInput) -> ApiResult<HookResult> {
+    run_builtin_unless(name, input, &disabled_builtins())
+}
+
+/// Builtins listed in COPILOT_DISABLED_BUILTINS (comma-separated) succeed
+/// with no output, whatever hooks.json says.
+pub fn disabled_builtins() -> Vec<String> {
+    std::env::var("COPILOT_DISABLED_BUILTINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn run_builtin_unless(name: &str, input: &HookInput, disabled: &[String]) -> ApiResult<HookResult> {
+    if disabled.iter().any(|d| d == name) {
+        return Ok(HookResult { exit_code: 0, stdout: String::new(), stderr: String::new() });
+    }
     match name {
         "session_start" => session_start(),
         "session_end" => session_end(input),
@@ -22,7 +56,23 @@ pub fn run_builtin(name: &str, input: &HookInput) -> ApiResult<HookResult> {
         "tmux_reminder" => tmux_reminder(),
         "git_push_reminder" => git_push_reminder(),
         "pr_create_notice" => pr_create_notice(input),
-        _ => Ok(HookResult { exit_code: 0, stdout: String::new(), stderr: format!("[Hook] Unknown builtin: {}", name) }),
+        _ => Ok(unknown_builtin(name, strict_hooks())),
+    }
+}
+
+fn strict_hooks() -> bool {
+    std::env::var("COPILOT_STRICT_HOOKS")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Unknown builtins are a no-op unless COPILOT_STRICT_HOOKS is set, in which
+/// case they fail (and so block) like any other hook.
+fn unknown_builtin(name: &str, strict: bool) -> HookResult {
+    HookResult {
+        exit_code: if strict { 1 } else { 0 },
+        stdout: String::new(),
+        stderr: format!("[Hook] Unknown builtin: {}", name),
     }
 }
 
@@ -258,3 +308,46 @@ fn pr_create_notice(input: &HookInput) -> ApiResult<HookResult> {
 fn is_script_file(file: &str) -> bool {
     file.ends_with(".js") || file.ends_with(".jsx") || file.ends_with(".ts") || file.ends_with(".tsx")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{run_builtin, run_builtin_unless, unknown_builtin, BUILTIN_NAMES};
+    use crate::hooks::types::HookInput;
+
+    #[test]
+    fn unknown_builtin_is_lenient_by_default() {
+        let result = unknown_builtin("no_such_hook", false);
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.stderr, "[Hook] Unknown builtin: no_such_hook");
+    }
+
+    #[test]
+    fn unknown_builtin_fails_in_strict_mode() {
+        let result = unknown_builtin("no_such_hook", true);
+        assert_eq!(result.exit_code, 1);
+        assert_eq!(result.stderr, "[Hook] Unknown builtin: no_such_hook");
+    }
+
+    #[test]
+    fn listed_builtins_are_dispatched() {
+        assert!(BUILTIN_NAMES.contains(&"tmux_reminder"));
+        let result = run_builtin("tmux_reminder", &HookInput::default()).unwrap();
+        assert!(!result.stderr.contains("Unknown builtin"));
+    }
+
+    #[test]
+    fn disabled_builtin_is_a_no_op() {
+        let input = HookInput {
+            tool_input: Some(serde_json::json!({ "file_path": "notes/plan.md" })),
+            ..HookInput::default()
+        };
+        let blocked = run_builtin_unless("block_doc_creation", &input, &[]).unwrap();
+        assert_eq!(blocked.exit_code, 1);
+
+        let disabled = ["tmux_dev_block".to_string(), "block_doc_creation".to_string()];
+        let result = run_builtin_unless("block_doc_creation", &input, &disabled).unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert!(result.stdout.is_empty());
+        assert!(result.stderr.is_empty());
+    }
+}
@@ -1,28 +1,45 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
 
 use crate::errors::{ApiError, ApiResult};
-use crate::hooks::{builtins, matcher::evaluator, observe, types::{HookInput, HookResult, HooksJson}};
+use crate::hooks::{builtins, matcher::evaluator, observe, schema, types::{HookInput, HookResult, HooksJson}};
 
 #[derive(Debug, Clone)]
 pub struct HookExecutor {
-    pub config: HooksJson,
+    /// Swapped whole on reload; readers clone the `Arc` and drop the lock
+    /// before running any hook.
+    pub config: Arc<RwLock<Arc<HooksJson>>>,
+    pub path: PathBuf,
     pub observer: Option<observe::ObservationHub>,
 }
 
 impl HookExecutor {
     pub fn load(config_path: Option<PathBuf>, observer: Option<observe::ObservationHub>) -> ApiResult<Self> {
         let path = resolve_hooks_path(config_path)?;
-        let config = if path.exists() {
-            let content = std::fs::read_to_string(&path)
-                .map_err(|e| ApiError::Internal(format!("Failed to read hooks.json: {e}")))?;
-            serde_json::from_str::<HooksJson>(&content)
-                .map_err(|e| ApiError::Internal(format!("Invalid hooks.json: {e}")))?
-        } else {
-            HooksJson::default()
-        };
+        let config = read_config(&path)?;
+        Ok(Self { config: Arc::new(RwLock::new(Arc::new(config))), path, observer })
+    }
 
-        Ok(Self { config, observer })
+    /// Re-reads hooks.json from the path it was loaded from and swaps it in.
+    /// A file that fails schema validation leaves the current config active.
+    pub async fn reload(&self) -> ApiResult<HooksSummary> {
+        let source = read_source(&self.path)?;
+        if let Some(source) = &source {
+            let errors = schema::validate_hooks_json(source);
+            if !errors.is_empty() {
+                let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+                return Err(ApiError::BadRequest(format!("Invalid hooks.json: {}", errors.join("; "))));
+            }
+        }
+        let config = parse_config(&self.path, source.as_deref()).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        let summary = HooksSummary {
+            events: config.hooks.len(),
+            entries: config.hooks.values().map(Vec::len).sum(),
+        };
+        *self.config.write().await = Arc::new(config);
+        Ok(summary)
     }
 
     pub async fn execute_event(&self, event: &str, input: &HookInput) -> ApiResult<Vec<HookResult>> {
@@ -30,8 +47,17 @@ impl HookExecutor {
             observer.emit(observe::build_event(event, input));
         }
 
+        let with_event;
+        let input = if input.hook_type.is_none() {
+            with_event = HookInput { hook_type: Some(event.to_string()), ..input.clone() };
+            &with_event
+        } else {
+            input
+        };
+
         let mut results = Vec::new();
-        if let Some(entries) = self.config.hooks.get(event) {
+        let config = self.config.read().await.clone();
+        if let Some(entries) = config.hooks.get(event) {
             for config in entries {
                 let matched = evaluator::evaluate(&config.matcher, input)
                     .unwrap_or(false);
@@ -61,7 +87,45 @@ impl HookExecutor {
     }
 }
 
-fn resolve_hooks_path(explicit: Option<PathBuf>) -> ApiResult<PathBuf> {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HooksSummary {
+    pub events: usize,
+    pub entries: usize,
+}
+
+fn read_config(path: &Path) -> ApiResult<HooksJson> {
+    let source = read_source(path)?;
+    parse_config(path, source.as_deref())
+}
+
+/// `None` when the file does not exist, which means "no hooks".
+fn read_source(path: &Path) -> ApiResult<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path)
+        .map(Some)
+        .map_err(|e| ApiError::Internal(format!("Failed to read hooks.json: {e}")))
+}
+
+fn parse_config(path: &Path, source: Option<&str>) -> ApiResult<HooksJson> {
+    let config = match source {
+        Some(content) => serde_json::from_str::<HooksJson>(content)
+            .map_err(|e| ApiError::Internal(format!("Invalid hooks.json: {e}")))?,
+        None => HooksJson::default(),
+    };
+    for (event, configs) in &config.hooks {
+        let builtins = configs.iter().flat_map(|c| &c.hooks).filter(|h| h.hook_type == "builtin");
+        for name in builtins.map(|h| h.name.as_deref().unwrap_or_default()) {
+            if !builtins::BUILTIN_NAMES.contains(&name) {
+                tracing::warn!("{} in {} references unknown builtin \"{}\"", event, path.display(), name);
+            }
+        }
+    }
+    Ok(config)
+}
+
+pub(crate) fn resolve_hooks_path(explicit: Option<PathBuf>) -> ApiResult<PathBuf> {
     if let Some(path) = explicit {
         return Ok(path);
     }
@@ -111,3 +175,70 @@ async fn run_command(command: &str, input: &HookInput, timeout: Option<u64>) ->
         stderr: String::from_utf8_lossy(&output.stderr).to_string(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::HookExecutor;
+    use crate::hooks::types::HookInput;
+
+    const BLOCKING: &str = r#"{"hooks":{"PreToolUse":[{"matcher":"*","hooks":[{"type":"command","command":"exit 2"}]}]}}"#;
+
+    #[tokio::test]
+    async fn reload_picks_up_changed_config() {
+        let path = std::env::temp_dir().join(format!("copilot-hooks-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"hooks":{}}"#).unwrap();
+        let executor = HookExecutor::load(Some(path.clone()), None).unwrap();
+        let input = HookInput::default();
+        assert!(executor.execute_event("PreToolUse", &input).await.unwrap().is_empty());
+
+        std::fs::write(&path, BLOCKING).unwrap();
+        let summary = executor.reload().await.unwrap();
+        assert_eq!((summary.events, summary.entries), (1, 1));
+        let results = executor.execute_event("PreToolUse", &input).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_ne!(results[0].exit_code, 0);
+
+        std::fs::write(&path, r#"{"hooks":{"PreToolUse":"nope"}}"#).unwrap();
+        let err = executor.reload().await.expect_err("invalid config rejected");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(err.to_string().starts_with("Invalid hooks.json"));
+        assert_eq!(executor.execute_event("PreToolUse", &input).await.unwrap().len(), 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reload_does_not_wait_for_running_hooks() {
+        let path = std::env::temp_dir().join(format!("copilot-hooks-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"hooks":{"PreToolUse":[{"matcher":"*","hooks":[{"type":"command","command":"sleep 3"}]}]}}"#).unwrap();
+        let executor = std::sync::Arc::new(HookExecutor::load(Some(path.clone()), None).unwrap());
+        let running = executor.clone();
+        let hook = tokio::spawn(async move { running.execute_event("PreToolUse", &HookInput::default()).await });
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        std::fs::write(&path, r#"{"hooks":{}}"#).unwrap();
+        let summary = tokio::time::timeout(std::time::Duration::from_secs(1), executor.reload())
+            .await
+            .expect("reload blocked behind a running hook")
+            .unwrap();
+        assert_eq!(summary.events, 0);
+        assert!(!hook.is_finished());
+
+        hook.abort();
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn reload_of_a_removed_file_clears_hooks() {
+        let path = std::env::temp_dir().join(format!("copilot-hooks-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, BLOCKING).unwrap();
+        let executor = HookExecutor::load(Some(path.clone()), None).unwrap();
+        assert_eq!(executor.execute_event("PreToolUse", &HookInput::default()).await.unwrap().len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+        let summary = executor.reload().await.unwrap();
+        assert_eq!((summary.events, summary.entries), (0, 0));
+        assert!(executor.execute_event("PreToolUse", &HookInput::default()).await.unwrap().is_empty());
+    }
+}
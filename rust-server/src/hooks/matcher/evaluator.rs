@@ -96,6 +96,12 @@ fn resolve_field(input: &HookInput, field: &str) -> Option<String> {
     if field == "tool" {
         return input.tool.clone();
     }
+    if field == "event" {
+        return input.hook_type.clone();
+    }
+    if field == "session" {
+        return input.resolved_session_id();
+    }
     if field.starts_with("tool_input.") {
         let path = &field["tool_input.".len()..];
         return resolve_json_path(input.tool_input.as_ref(), path);
@@ -119,3 +125,50 @@ fn resolve_json_path(value: Option<&serde_json::Value>, path: &str) -> Option<St
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+    use crate::hooks::types::HookInput;
+    use crate::utils::env_lock;
+
+    fn bash_input(event: &str) -> HookInput {
+        HookInput {
+            hook_type: Some(event.to_string()),
+            tool: Some("Bash".to_string()),
+            tool_input: Some(serde_json::json!({ "command": "ls -la" })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_on_event_name() {
+        let expr = r#"event == "PreToolUse" && tool == "Bash""#;
+        assert_eq!(evaluate(expr, &bash_input("PreToolUse")), Ok(true));
+        assert_eq!(evaluate(expr, &bash_input("PostToolUse")), Ok(false));
+        assert_eq!(evaluate(r#"event matches "^Post""#, &bash_input("PostToolUse")), Ok(true));
+        assert_eq!(evaluate(r#"event == "Stop" || !(tool != "Bash")"#, &bash_input("PreToolUse")), Ok(true));
+        assert_eq!(evaluate(r#"tool_input.command matches "^ls""#, &bash_input("PreToolUse")), Ok(true));
+    }
+
+    #[test]
+    fn matches_on_session_id() {
+        let _lock = env_lock();
+        unsafe { std::env::remove_var("CLAUDE_SESSION_ID") };
+        let expr = r#"session == "abc123""#;
+
+        let mut input = bash_input("PreToolUse");
+        assert_eq!(evaluate(expr, &input), Ok(false));
+
+        input.session_id = Some("abc123".to_string());
+        assert_eq!(evaluate(expr, &input), Ok(true));
+        input.session_id = Some("other".to_string());
+        assert_eq!(evaluate(expr, &input), Ok(false));
+
+        input.session_id = None;
+        unsafe { std::env::set_var("CLAUDE_SESSION_ID", "abc123") };
+        let from_env = evaluate(expr, &input);
+        unsafe { std::env::remove_var("CLAUDE_SESSION_ID") };
+        assert_eq!(from_env, Ok(true));
+    }
+}
@@ -3,6 +3,7 @@ pub mod claude_paths;
 pub mod executor;
 pub mod matcher;
 pub mod observe;
+pub mod schema;
 pub mod types;
 
 pub use executor::HookExecutor;
@@ -2,9 +2,16 @@ use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 
-use crate::errors::ApiResult;
+use crate::config::http_client_builder;
+use crate::errors::{ApiError, ApiResult};
 use crate::hooks::claude_paths;
 
+pub mod sink;
+
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+use sink::{JsonlSink, ObservationSink, WebhookSink};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObservationEvent {
     pub timestamp: String,
@@ -26,24 +33,53 @@ impl ObservationHub {
     }
 }
 
+/// COPILOT_OBSERVE_SINK picks the destination: `file` (default, the
+/// observations JSONL), `stdout`, or `webhook` (COPILOT_OBSERVE_WEBHOOK_URL).
 pub async fn start_observer() -> ApiResult<ObservationHub> {
-    let (sender, mut receiver) = broadcast::channel(128);
-    let path = claude_paths::observations_file()?;
-    tokio::spawn(async move {
-        let mut file = match tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await {
-            Ok(f) => f,
-            Err(_) => return,
-        };
-        while let Ok(event) = receiver.recv().await {
-            if let Ok(line) = serde_json::to_string(&event) {
-                let _ = tokio::io::AsyncWriteExt::write_all(&mut file, line.as_bytes()).await;
-                let _ = tokio::io::AsyncWriteExt::write_all(&mut file, b"\n").await;
+    let (sender, receiver) = broadcast::channel(128);
+    match std::env::var("COPILOT_OBSERVE_SINK").unwrap_or_default().trim() {
+        "stdout" => {
+            tokio::spawn(run_sink(JsonlSink::new(tokio::io::stdout()), receiver));
+        }
+        "webhook" => {
+            let url = std::env::var("COPILOT_OBSERVE_WEBHOOK_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty())
+                .ok_or_else(|| ApiError::Internal("COPILOT_OBSERVE_WEBHOOK_URL is required for the webhook sink".to_string()))?;
+            let client = http_client_builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .map_err(|e| ApiError::Internal(format!("Failed to build webhook client: {e}")))?;
+            tokio::spawn(run_sink(WebhookSink::new(client, url.trim().to_string()), receiver));
+        }
+        other => {
+            if !other.is_empty() && other != "file" {
+                tracing::warn!("Unknown COPILOT_OBSERVE_SINK {:?}, writing observations to file", other);
             }
+            let path = claude_paths::observations_file()?;
+            tokio::spawn(async move {
+                let Ok(file) = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await else {
+                    return;
+                };
+                run_sink(JsonlSink::new(file), receiver).await;
+            });
         }
-    });
+    }
     Ok(ObservationHub { sender })
 }
 
+async fn run_sink<S: ObservationSink>(mut sink: S, mut receiver: broadcast::Receiver<ObservationEvent>) {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => sink.write(&event).await,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::debug!("Observation sink fell behind; skipped {} events", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
 pub fn build_event(
     event: &str,
     input: &crate::hooks::types::HookInput,
@@ -57,3 +93,32 @@ pub fn build_event(
         output: input.tool_output.clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{build_event, run_sink, sink::ObservationSink, ObservationEvent};
+    use crate::hooks::types::HookInput;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::broadcast;
+
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    impl ObservationSink for Collect {
+        async fn write(&mut self, event: &ObservationEvent) {
+            self.0.lock().unwrap().push(event.event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn sink_keeps_running_after_lagging() {
+        let (sender, receiver) = broadcast::channel(2);
+        for name in ["a", "b", "c", "d"] {
+            sender.send(build_event(name, &HookInput::default())).unwrap();
+        }
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let task = tokio::spawn(run_sink(Collect(seen.clone()), receiver));
+        drop(sender);
+        task.await.unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec!["c", "d"]);
+    }
+}
@@ -0,0 +1,154 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Semaphore;
+
+use super::ObservationEvent;
+
+/// Where observation events end up. `start_observer` drives one sink from the
+/// broadcast channel.
+pub trait ObservationSink: Send + 'static {
+    fn write(&mut self, event: &ObservationEvent) -> impl Future<Output = ()> + Send;
+}
+
+/// One JSON object per line; backs both the observations file and stdout.
+pub struct JsonlSink<W> {
+    writer: W,
+}
+
+impl<W> JsonlSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send + 'static> ObservationSink for JsonlSink<W> {
+    async fn write(&mut self, event: &ObservationEvent) {
+        if let Ok(mut line) = serde_json::to_vec(event) {
+            line.push(b'\n');
+            let _ = self.writer.write_all(&line).await;
+            let _ = self.writer.flush().await;
+        }
+    }
+}
+
+const MAX_IN_FLIGHT_POSTS: usize = 8;
+
+/// POSTs each event as JSON. Delivery is best-effort and never holds up the
+/// observer: failures, and events arriving while `MAX_IN_FLIGHT_POSTS` are
+/// still pending, are logged at debug level and dropped.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    in_flight: Arc<Semaphore>,
+}
+
+impl WebhookSink {
+    pub fn new(client: reqwest::Client, url: String) -> Self {
+        Self { client, url, in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT_POSTS)) }
+    }
+}
+
+impl ObservationSink for WebhookSink {
+    async fn write(&mut self, event: &ObservationEvent) {
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            tracing::debug!("Observation webhook busy; dropping {} event", event.event);
+            return;
+        };
+        let request = self.client.post(&self.url).json(event);
+        tokio::spawn(async move {
+            if let Err(err) = request.send().await {
+                tracing::debug!("Observation webhook failed: {}", err);
+            }
+            drop(permit);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonlSink, ObservationSink, WebhookSink};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use crate::hooks::observe::ObservationEvent;
+
+    fn event(name: &str) -> ObservationEvent {
+        ObservationEvent {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            event: name.to_string(),
+            session: Some("abc".to_string()),
+            tool: Some("Bash".to_string()),
+            input: None,
+            output: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn jsonl_sink_writes_one_line_per_event() {
+        let mut sink = JsonlSink::new(Vec::new());
+        sink.write(&event("PreToolUse")).await;
+        sink.write(&event("PostToolUse")).await;
+
+        let out = String::from_utf8(sink.writer).unwrap();
+        let lines: Vec<serde_json::Value> = out.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "PreToolUse");
+        assert_eq!(lines[1]["event"], "PostToolUse");
+        assert_eq!(lines[1]["tool"], "Bash");
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_posts_events() {
+        use axum::{routing::post, Json, Router};
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+        let app = Router::new().route(
+            "/events",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                let _ = tx.send(body);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let mut sink = WebhookSink::new(reqwest::Client::new(), format!("http://{}/events", addr));
+        sink.write(&event("SessionStart")).await;
+
+        let body = tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+        assert_eq!(body["event"], "SessionStart");
+        assert_eq!(body["session"], "abc");
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_drops_events_while_posts_are_pending() {
+        use axum::{routing::post, Router};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/events",
+            post(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                std::future::pending::<()>()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let mut sink = WebhookSink::new(reqwest::Client::new(), format!("http://{}/events", addr));
+        sink.in_flight = Arc::new(tokio::sync::Semaphore::new(1));
+        for _ in 0..3 {
+            sink.write(&event("PreToolUse")).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(sink.in_flight.available_permits(), 0);
+    }
+}
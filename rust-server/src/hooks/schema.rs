@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::hooks::builtins::BUILTIN_NAMES;
+
+/// JSON Schema for hooks.json, shipped with the binary.
+pub const HOOKS_SCHEMA: &str = include_str!("hooks.schema.json");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    pub line: usize,
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        write!(f, "line {}: {}: {}", self.line, path, self.message)
+    }
+}
+
+/// Validates hooks.json source text against [`HOOKS_SCHEMA`]. Errors carry the
+/// JSON pointer of the offending value and the line it starts on.
+pub fn validate_hooks_json(source: &str) -> Vec<SchemaError> {
+    let value: Value = match serde_json::from_str(source) {
+        Ok(value) => value,
+        Err(err) => {
+            return vec![SchemaError { line: err.line(), path: String::new(), message: err.to_string() }];
+        }
+    };
+    let schema: Value = serde_json::from_str(HOOKS_SCHEMA).expect("bundled hooks schema");
+
+    let mut errors = Vec::new();
+    check(&schema, &schema, &value, "", &mut errors);
+    check_builtin_names(&value, &mut errors);
+
+    let lines = value_lines(source);
+    errors
+        .into_iter()
+        .map(|(path, message)| SchemaError { line: lines.get(&path).copied().unwrap_or(1), path, message })
+        .collect()
+}
+
+// Covers the subset of draft-07 the bundled schema uses.
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, errors: &mut Vec<(String, String)>) {
+    if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+        if let Some(target) = reference.strip_prefix('#').and_then(|pointer| root.pointer(pointer)) {
+            check(root, target, value, path, errors);
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type").and_then(|t| t.as_str()).filter(|t| !type_matches(t, value)) {
+        errors.push((path.to_string(), format!("expected {expected}, found {}", type_name(value))));
+        return;
+    }
+    if let Some(allowed) = schema.get("enum").and_then(|e| e.as_array()).filter(|a| !a.contains(value)) {
+        errors.push((path.to_string(), format!("{value} is not one of {}", Value::Array(allowed.clone()))));
+    }
+    if let Some(expected) = schema.get("const").filter(|c| *c != value) {
+        errors.push((path.to_string(), format!("expected {expected}")));
+    }
+    let length = value.as_str().map(|text| text.chars().count() as u64);
+    if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()).filter(|min| length.is_some_and(|len| len < *min)) {
+        errors.push((path.to_string(), format!("must be at least {min} characters")));
+    }
+    if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()).filter(|min| value.as_f64().is_some_and(|n| n < *min)) {
+        errors.push((path.to_string(), format!("must be >= {min}")));
+    }
+
+    if let Some(object) = value.as_object() {
+        for key in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten() {
+            if let Some(key) = key.as_str().filter(|key| !object.contains_key(*key)) {
+                errors.push((path.to_string(), format!("missing required property \"{key}\"")));
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, child) in object {
+            let child_path = format!("{path}/{}", escape_pointer(key));
+            if let Some(names) = schema.get("propertyNames") {
+                let mut name_errors = Vec::new();
+                check(root, names, &Value::String(key.clone()), &child_path, &mut name_errors);
+                if !name_errors.is_empty() {
+                    errors.push((child_path.clone(), format!("unknown key \"{key}\"")));
+                    continue;
+                }
+            }
+            match (properties.and_then(|p| p.get(key)), schema.get("additionalProperties")) {
+                (Some(child_schema), _) => check(root, child_schema, child, &child_path, errors),
+                (None, Some(Value::Bool(false))) => {
+                    errors.push((child_path, format!("unknown property \"{key}\"")));
+                }
+                (None, Some(extra)) if extra.is_object() => check(root, extra, child, &child_path, errors),
+                _ => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            check(root, items, item, &format!("{path}/{index}"), errors);
+        }
+    }
+
+    if let Some(condition) = schema.get("if") {
+        let mut condition_errors = Vec::new();
+        check(root, condition, value, path, &mut condition_errors);
+        let branch = if condition_errors.is_empty() { schema.get("then") } else { schema.get("else") };
+        if let Some(branch) = branch {
+            check(root, branch, value, path, errors);
+        }
+    }
+}
+
+// Builtin names live in code rather than the schema.
+fn check_builtin_names(value: &Value, errors: &mut Vec<(String, String)>) {
+    let Some(events) = value.get("hooks").and_then(|h| h.as_object()) else {
+        return;
+    };
+    for (event, configs) in events {
+        for (i, config) in configs.as_array().into_iter().flatten().enumerate() {
+            for (j, hook) in config.get("hooks").and_then(|h| h.as_array()).into_iter().flatten().enumerate() {
+                if hook.get("type").and_then(|t| t.as_str()) != Some("builtin") {
+                    continue;
+                }
+                if let Some(name) = hook.get("name").and_then(|n| n.as_str()).filter(|name| !BUILTIN_NAMES.contains(name)) {
+                    let path = format!("/hooks/{}/{i}/hooks/{j}/name", escape_pointer(event));
+                    errors.push((path, format!("unknown builtin \"{name}\"")));
+                }
+            }
+        }
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "integer" => value.is_u64() || value.is_i64(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Maps the JSON pointer of every value in `source` to the line it starts on.
+/// Only called on text serde_json has already accepted.
+fn value_lines(source: &str) -> HashMap<String, usize> {
+    let mut scanner = LineScanner { source, pos: 0, line: 1, lines: HashMap::new() };
+    scanner.value(String::new());
+    scanner.lines
+}
+
+struct LineScanner<'a> {
+    source: &'a str,
+    pos: usize,
+    line: usize,
+    lines: HashMap<String, usize>,
+}
+
+impl LineScanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.peek().filter(|b| b.is_ascii_whitespace()) {
+            if byte == b'\n' {
+                self.line += 1;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self, path: String) {
+        self.skip_whitespace();
+        self.lines.insert(path.clone(), self.line);
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b'"') => {}
+                        Some(b'}') | None => break,
+                        Some(_) => self.pos += 1,
+                    }
+                    if self.peek() != Some(b'"') {
+                        continue;
+                    }
+                    let key = self.string();
+                    self.skip_whitespace();
+                    self.pos += 1; // ':'
+                    self.value(format!("{path}/{}", escape_pointer(&key)));
+                }
+                self.pos += 1;
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b']') | None => break,
+                        Some(b',') => self.pos += 1,
+                        Some(_) => {
+                            self.value(format!("{path}/{index}"));
+                            index += 1;
+                        }
+                    }
+                }
+                self.pos += 1;
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            Some(_) => {
+                while self.peek().is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace()) {
+                    self.pos += 1;
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(byte) = self.peek() {
+            self.pos += if byte == b'\\' { 2 } else { 1 };
+            if byte == b'"' {
+                break;
+            }
+        }
+        serde_json::from_str(&self.source[start..self.pos]).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_hooks_json, HOOKS_SCHEMA};
+
+    #[test]
+    fn bundled_schema_is_valid_json() {
+        let schema: serde_json::Value = serde_json::from_str(HOOKS_SCHEMA).unwrap();
+        assert_eq!(schema["title"], "hooks.json");
+    }
+
+    #[test]
+    fn accepts_valid_config() {
+        let source = r#"{
+  "hooks": {
+    "PreToolUse": [
+      {
+        "matcher": "tool == \"Bash\"",
+        "hooks": [
+          { "type": "command", "command": "echo ok", "timeout": 5 },
+          { "type": "builtin", "name": "tmux_dev_block", "enabled": false }
+        ]
+      }
+    ]
+  }
+}"#;
+        assert_eq!(validate_hooks_json(source), vec![]);
+    }
+
+    #[test]
+    fn reports_missing_matcher_with_line() {
+        let source = r#"{
+  "hooks": {
+    "PostToolUse": [
+      {
+        "hooks": [{ "type": "builtin", "name": "warn_console_log" }]
+      }
+    ]
+  }
+}"#;
+        let errors = validate_hooks_json(source);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "/hooks/PostToolUse/0");
+        assert_eq!(errors[0].line, 4);
+        assert_eq!(errors[0].to_string(), "line 4: /hooks/PostToolUse/0: missing required property \"matcher\"");
+    }
+
+    #[test]
+    fn reports_unknown_event_and_hook_type() {
+        let source = r#"{
+  "hooks": {
+    "BeforeEverything": [],
+    "Stop": [
+      {
+        "matcher": "*",
+        "hooks": [
+          { "type": "script", "name": "x" },
+          { "type": "command" }
+        ]
+      }
+    ]
+  }
+}"#;
+        let errors: Vec<String> = validate_hooks_json(source).iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 3: /hooks/BeforeEverything: unknown key \"BeforeEverything\"".to_string(),
+                "line 8: /hooks/Stop/0/hooks/0/type: \"script\" is not one of [\"builtin\",\"command\"]".to_string(),
+                "line 9: /hooks/Stop/0/hooks/1: missing required property \"command\"".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_line() {
+        let errors = validate_hooks_json("{\n  \"hooks\": {\n    \"Stop\": [,]\n  }\n}");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+    }
+
+    #[test]
+    fn reports_wrong_types() {
+        let errors = validate_hooks_json(r#"{"hooks": {"Stop": [{"matcher": 1, "hooks": [{"type": "command", "command": "x", "timeout": -1}]}]}}"#);
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["must be >= 0", "expected string, found number"]);
+    }
+
+    #[test]
+    fn reports_unknown_builtin() {
+        let source = "{\"hooks\": {\"Stop\": [{\"matcher\": \"*\", \"hooks\": [\n  {\"type\": \"builtin\", \"name\": \"evaluate_sesion\"}]}]}}";
+        let errors: Vec<String> = validate_hooks_json(source).iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec!["line 2: /hooks/Stop/0/hooks/0/name: unknown builtin \"evaluate_sesion\"".to_string()]);
+    }
+}
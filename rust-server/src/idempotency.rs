@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+const DEFAULT_TTL_SECS: u64 = 60;
+const MAX_ENTRIES: usize = 1000;
+/// Larger responses are passed through without being stored.
+const MAX_STORED_BODY_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    request_hash: u64,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+    stored: Instant,
+}
+
+/// Successful non-streaming responses keyed by caller, path and the inbound
+/// `Idempotency-Key`.
+#[derive(Debug)]
+pub struct IdempotencyCache {
+    ttl: Duration,
+    max_entries: usize,
+    request_limit: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+    /// One lock per key with a request in flight, so a retry waits for the
+    /// original instead of reaching upstream alongside it.
+    in_flight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        let secs = std::env::var("COPILOT_IDEMPOTENCY_TTL")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_TTL_SECS);
+        Self { request_limit: crate::max_body_bytes(), ..Self::with_ttl(Duration::from_secs(secs)) }
+    }
+}
+
+impl IdempotencyCache {
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            max_entries: MAX_ENTRIES,
+            request_limit: usize::MAX,
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str, now: Instant) -> Option<Entry> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| now.saturating_duration_since(entry.stored) < self.ttl);
+        entries.get(key).cloned()
+    }
+
+    /// Drops expired entries, then the oldest ones once `max_entries` is reached.
+    fn insert(&self, key: String, entry: Entry) {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, entry| now.saturating_duration_since(entry.stored) < self.ttl);
+        while entries.len() >= self.max_entries {
+            let Some(oldest) = entries.iter().min_by_key(|(_, entry)| entry.stored).map(|(key, _)| key.clone()) else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(key, entry);
+    }
+
+    fn key_lock(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.in_flight.lock().unwrap().entry(key.to_string()).or_default().clone()
+    }
+
+    /// Forgets the key's lock once no other request is waiting on it.
+    fn release(&self, key: &str, lock: Arc<tokio::sync::Mutex<()>>) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if Arc::strong_count(&lock) == 2 {
+            in_flight.remove(key);
+        }
+    }
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+/// Hash of the credentials the request carries, so callers never share keys.
+fn caller_scope(headers: &HeaderMap) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for name in ["authorization", "x-api-key"] {
+        headers.get(name).map(HeaderValue::as_bytes).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": { "message": message } }))).into_response()
+}
+
+/// Replays the stored response for a repeated `Idempotency-Key` instead of
+/// calling upstream again. Streams and failures are never stored; reusing a
+/// key with a different body is rejected with 422.
+pub async fn replay(State(cache): State<Arc<IdempotencyCache>>, req: Request, next: Next) -> Response {
+    let key = req
+        .headers()
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| format!("{:016x} {} {}", caller_scope(req.headers()), req.uri().path(), v.trim()));
+    let Some(key) = key.filter(|_| req.method() == Method::POST && !cache.ttl.is_zero()) else {
+        return next.run(req).await;
+    };
+
+    let (parts, body) = req.into_parts();
+    let body = match axum::body::to_bytes(body, cache.request_limit).await {
+        Ok(body) => body,
+        Err(_) => return error_response(StatusCode::PAYLOAD_TOO_LARGE, "request body too large"),
+    };
+    let request_hash = hash_bytes(&body);
+    let req = Request::from_parts(parts, Body::from(body));
+
+    let lock = cache.key_lock(&key);
+    let guard = lock.lock().await;
+    let resp = match cache.get(&key, Instant::now()) {
+        Some(entry) if entry.request_hash != request_hash => error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Idempotency-Key was already used with a different request body",
+        ),
+        Some(entry) => {
+            tracing::debug!("Replaying response for idempotency key {}", key);
+            let mut resp = Response::new(Body::from(entry.body));
+            *resp.status_mut() = entry.status;
+            *resp.headers_mut() = entry.headers;
+            resp.headers_mut().insert("idempotency-replayed", HeaderValue::from_static("true"));
+            resp
+        }
+        None => store(&cache, &key, request_hash, next.run(req).await).await,
+    };
+    drop(guard);
+    cache.release(&key, lock);
+    resp
+}
+
+async fn store(cache: &IdempotencyCache, key: &str, request_hash: u64, resp: Response) -> Response {
+    let storable = resp.status().is_success()
+        && !is_event_stream(resp.headers())
+        && resp.body().size_hint().exact().is_some_and(|len| len <= MAX_STORED_BODY_BYTES);
+    if !storable {
+        return resp;
+    }
+    let (parts, body) = resp.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_STORED_BODY_BYTES as usize).await {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::warn!("Failed to buffer response for idempotency key {}: {}", key, err);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    cache.insert(
+        key.to_string(),
+        Entry { request_hash, status: parts.status, headers: parts.headers.clone(), body: body.clone(), stored: Instant::now() },
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replay, Entry, IdempotencyCache};
+    use axum::{routing::post, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Serves an upstream stub that numbers its responses behind the middleware.
+    async fn serve(calls: Arc<AtomicUsize>, delay: Duration) -> String {
+        let app = Router::new()
+            .route(
+                "/v1/messages",
+                post(move || {
+                    let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move {
+                        tokio::time::sleep(delay).await;
+                        Json(serde_json::json!({ "id": format!("msg_{n}") }))
+                    }
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::new(IdempotencyCache::with_ttl(Duration::from_secs(60))),
+                replay,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/messages", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        url
+    }
+
+    fn entry(stored: Instant) -> Entry {
+        Entry {
+            request_hash: 0,
+            status: axum::http::StatusCode::OK,
+            headers: Default::default(),
+            body: bytes::Bytes::from_static(b"{}"),
+            stored,
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_key_is_served_once_from_upstream() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = serve(calls.clone(), Duration::ZERO).await;
+        let client = reqwest::Client::new();
+        let send = |key: &'static str| client.post(&url).header("Idempotency-Key", key).body("{}").send();
+
+        let first = send("abc").await.unwrap();
+        assert!(first.headers().get("idempotency-replayed").is_none());
+        assert_eq!(first.json::<serde_json::Value>().await.unwrap()["id"], "msg_1");
+
+        let second = send("abc").await.unwrap();
+        assert_eq!(second.headers()["idempotency-replayed"], "true");
+        assert_eq!(second.json::<serde_json::Value>().await.unwrap()["id"], "msg_1");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let other = send("def").await.unwrap();
+        assert_eq!(other.json::<serde_json::Value>().await.unwrap()["id"], "msg_2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_the_caller() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = serve(calls.clone(), Duration::ZERO).await;
+        let client = reqwest::Client::new();
+        let send = |auth: &'static str| client.post(&url).bearer_auth(auth).header("Idempotency-Key", "abc").body("{}").send();
+
+        let alice = send("alice").await.unwrap();
+        assert_eq!(alice.json::<serde_json::Value>().await.unwrap()["id"], "msg_1");
+        let bob = send("bob").await.unwrap();
+        assert!(bob.headers().get("idempotency-replayed").is_none());
+        assert_eq!(bob.json::<serde_json::Value>().await.unwrap()["id"], "msg_2");
+    }
+
+    #[tokio::test]
+    async fn reused_key_with_a_different_body_is_rejected() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = serve(calls.clone(), Duration::ZERO).await;
+        let client = reqwest::Client::new();
+        let send = |body: &'static str| client.post(&url).header("Idempotency-Key", "abc").body(body).send();
+
+        assert_eq!(send(r#"{"n":1}"#).await.unwrap().status(), 200);
+        let changed = send(r#"{"n":2}"#).await.unwrap();
+        assert_eq!(changed.status(), 422);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_retries_reach_upstream_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let url = serve(calls.clone(), Duration::from_millis(100)).await;
+        let client = reqwest::Client::new();
+        let send = || client.post(&url).header("Idempotency-Key", "abc").body("{}").send();
+
+        let (first, second) = tokio::join!(send(), send());
+        let replayed = [first.unwrap(), second.unwrap()].map(|resp| resp.headers().contains_key("idempotency-replayed"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(replayed.iter().filter(|r| **r).count(), 1);
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let cache = IdempotencyCache::with_ttl(Duration::from_secs(60));
+        let stored = Instant::now();
+        cache.insert("k".to_string(), entry(stored));
+        assert!(cache.get("k", stored + Duration::from_secs(59)).is_some());
+        assert!(cache.get("k", stored + Duration::from_secs(60)).is_none());
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted_at_the_cap() {
+        let cache = IdempotencyCache { max_entries: 2, ..IdempotencyCache::with_ttl(Duration::from_secs(60)) };
+        let now = Instant::now();
+        for (i, key) in ["a", "b", "c"].into_iter().enumerate() {
+            cache.insert(key.to_string(), entry(now + Duration::from_millis(i as u64)));
+        }
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+        assert!(cache.get("a", now).is_none());
+        assert!(cache.get("c", now).is_some());
+    }
+}
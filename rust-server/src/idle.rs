@@ -0,0 +1,80 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{extract::Request, extract::State, middleware::Next, response::Response};
+
+#[derive(Debug)]
+pub struct ActivityTracker {
+    last: Mutex<Instant>,
+}
+
+impl Default for ActivityTracker {
+    fn default() -> Self {
+        Self { last: Mutex::new(Instant::now()) }
+    }
+}
+
+impl ActivityTracker {
+    pub fn touch(&self) {
+        *self.last.lock().unwrap() = Instant::now();
+    }
+
+    pub fn last_activity(&self) -> Instant {
+        *self.last.lock().unwrap()
+    }
+}
+
+pub fn idle_timeout() -> Option<Duration> {
+    std::env::var("COPILOT_IDLE_TIMEOUT")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+pub fn is_idle(last_activity: Instant, now: Instant, timeout: Duration) -> bool {
+    now.saturating_duration_since(last_activity) >= timeout
+}
+
+pub async fn track_activity(State(activity): State<Arc<ActivityTracker>>, req: Request, next: Next) -> Response {
+    activity.touch();
+    let resp = next.run(req).await;
+    activity.touch();
+    resp
+}
+
+/// Resolves once no request has been seen for `timeout`; used as the
+/// graceful-shutdown signal.
+pub async fn wait_for_idle(activity: Arc<ActivityTracker>, timeout: Duration) {
+    loop {
+        let last = activity.last_activity();
+        let now = Instant::now();
+        if is_idle(last, now, timeout) {
+            tracing::info!("No requests for {}s (COPILOT_IDLE_TIMEOUT), shutting down", timeout.as_secs());
+            return;
+        }
+        tokio::time::sleep(timeout - now.duration_since(last)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_idle;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn idle_once_window_has_elapsed() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(300);
+        assert!(!is_idle(start, start, timeout));
+        assert!(!is_idle(start, start + Duration::from_secs(299), timeout));
+        assert!(is_idle(start, start + Duration::from_secs(300), timeout));
+        assert!(is_idle(start, start + Duration::from_secs(301), timeout));
+    }
+
+    #[test]
+    fn activity_after_check_point_is_not_idle() {
+        let now = Instant::now();
+        assert!(!is_idle(now + Duration::from_secs(5), now, Duration::from_secs(1)));
+    }
+}
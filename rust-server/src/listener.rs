@@ -0,0 +1,176 @@
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use socket2::{Domain, Socket, Type};
+
+const DEFAULT_BACKLOG: i32 = 1024;
+const BIND_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+pub fn listen_backlog() -> i32 {
+    std::env::var("COPILOT_LISTEN_BACKLOG")
+        .ok()
+        .and_then(|v| v.trim().parse::<i32>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_BACKLOG)
+}
+
+/// Whether `addr` (`host:port`) only accepts connections from this machine.
+pub fn is_loopback_addr(addr: &str) -> bool {
+    let host = addr.rsplit_once(':').map(|(host, _)| host).unwrap_or(addr);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    host.parse::<IpAddr>().map(|ip| ip.is_loopback()).unwrap_or(false)
+}
+
+pub async fn bind(addr: &str) -> std::io::Result<tokio::net::TcpListener> {
+    let addrs = tokio::net::lookup_host(addr).await?;
+    bind_any(addrs, listen_backlog())
+        .unwrap_or_else(|| Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Could not resolve {addr}"))))
+}
+
+/// Binds the first address that works, like `TcpListener::bind`, so `localhost`
+/// still binds `127.0.0.1` when it resolves to `::1` first on a host without
+/// IPv6. Returns the last error if none work, `None` if `addrs` is empty.
+fn bind_any(
+    addrs: impl IntoIterator<Item = SocketAddr>,
+    backlog: i32,
+) -> Option<std::io::Result<tokio::net::TcpListener>> {
+    let mut last_err = None;
+    for addr in addrs {
+        match bind_addr(addr, backlog) {
+            Ok(listener) => return Some(Ok(listener)),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    last_err.map(Err)
+}
+
+/// `bind`, retried up to `retries` more times for a port that a previous
+/// instance has not released yet.
+pub async fn bind_with_retries(addr: &str, retries: u32) -> std::io::Result<tokio::net::TcpListener> {
+    retry(retries, BIND_RETRY_DELAY, || bind(addr)).await
+}
+
+async fn retry<T, F, Fut>(retries: u32, delay: Duration, mut attempt: F) -> std::io::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::io::Result<T>>,
+{
+    let mut remaining = retries;
+    loop {
+        match attempt().await {
+            Err(err) if remaining > 0 => {
+                tracing::warn!("Bind failed: {}; retrying in {}ms ({} left)", err, delay.as_millis(), remaining);
+                remaining -= 1;
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Writes the port actually bound (useful with `--port 0`) for supervisors.
+pub fn write_port_file(path: &std::path::Path, port: u16) -> std::io::Result<()> {
+    std::fs::write(path, format!("{port}\n"))
+}
+
+fn bind_addr(addr: SocketAddr, backlog: i32) -> std::io::Result<tokio::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    // On Unix this only lets a restart reclaim a port stuck in TIME_WAIT. On
+    // Windows it would let a second server bind a port that is still
+    // listening, and Windows already allows rebinding over TIME_WAIT.
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(backlog)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bind, bind_addr, bind_any, is_loopback_addr, retry, write_port_file};
+    use std::time::Duration;
+
+    #[test]
+    fn classifies_loopback_addresses() {
+        assert!(is_loopback_addr("127.0.0.1:4141"));
+        assert!(is_loopback_addr("localhost:4141"));
+        assert!(is_loopback_addr("[::1]:4141"));
+        assert!(!is_loopback_addr("0.0.0.0:4141"));
+        assert!(!is_loopback_addr("[::]:4141"));
+        assert!(!is_loopback_addr("192.168.1.20:4141"));
+        assert!(!is_loopback_addr("example.com:4141"));
+    }
+
+    #[tokio::test]
+    async fn binds_the_first_address_that_works() {
+        // 192.0.2.1 (TEST-NET-1) is never assigned to a local interface.
+        let unavailable = "192.0.2.1:0".parse().unwrap();
+        let listener = bind_any([unavailable, "127.0.0.1:0".parse().unwrap()], 16)
+            .expect("addresses")
+            .expect("falls through to loopback");
+        assert!(listener.local_addr().unwrap().ip().is_loopback());
+
+        assert!(bind_any([unavailable], 16).expect("addresses").is_err());
+        assert!(bind_any([], 16).is_none());
+    }
+
+    #[tokio::test]
+    async fn rebinds_immediately_after_close() {
+        let listener = bind_addr("127.0.0.1:0".parse().unwrap(), 16).expect("bind");
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::net::TcpStream::connect(addr).await.expect("connect");
+        let (server_side, _) = listener.accept().await.expect("accept");
+        drop(server_side);
+        drop(client);
+        drop(listener);
+
+        bind_addr(addr, 16).expect("rebind with SO_REUSEADDR");
+    }
+
+    #[tokio::test]
+    async fn cannot_bind_a_port_that_is_still_listening() {
+        let listener = bind_addr("127.0.0.1:0".parse().unwrap(), 16).expect("bind");
+        let err = bind_addr(listener.local_addr().unwrap(), 16).expect_err("second listener refused");
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    }
+
+    #[tokio::test]
+    async fn retries_until_an_attempt_succeeds() {
+        let mut attempts = 0;
+        let result = retry(2, Duration::ZERO, || {
+            attempts += 1;
+            let n = attempts;
+            async move {
+                if n < 3 { Err(std::io::Error::from(std::io::ErrorKind::AddrInUse)) } else { Ok(n) }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        let mut attempts = 0;
+        let result: std::io::Result<()> = retry(1, Duration::ZERO, || {
+            attempts += 1;
+            async { Err(std::io::Error::from(std::io::ErrorKind::AddrInUse)) }
+        })
+        .await;
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::AddrInUse);
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn port_zero_binds_ephemeral_port() {
+        let listener = bind("127.0.0.1:0").await.expect("bind");
+        let port = listener.local_addr().unwrap().port();
+        assert_ne!(port, 0);
+
+        let path = std::env::temp_dir().join(format!("copilot-api-port-{}", std::process::id()));
+        write_port_file(&path, port).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), port.to_string());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,56 +1,101 @@
-use axum::{routing::{get, post}, Router};
+use axum::{extract::DefaultBodyLimit, routing::{get, post}, Router};
 use clap::Parser;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use tower_http::{cors::{Any, CorsLayer}, trace::TraceLayer};
-use cli::{Command, StartArgs, AuthArgs, DebugArgs};
+use tower_http::{
+    compression::{predicate::{NotForContentType, SizeAbove}, CompressionLayer, Predicate},
+    cors::{AllowHeaders, AllowMethods, Any, CorsLayer},
+    decompression::RequestDecompressionLayer,
+    trace::TraceLayer,
+};
+use cli::{Command, StartArgs, AuthArgs, CheckUsageArgs, DebugArgs, HookAction, HookArgs, ModelsArgs, PathsArgs, SessionsAction, SessionsArgs, StatsArgs, VersionArgs};
 use hooks::{HookExecutor, types::HookInput};
 use std::io::Read;
 
 mod approval;
+mod capture;
 mod commands;
 mod cli;
 mod auth_flow;
 mod config;
 mod errors;
+mod listener;
+mod metrics;
 mod paths;
 mod rate_limit;
+mod response_cache;
 mod routes;
 mod services;
 mod state;
+mod stats;
 mod token_store;
 mod utils;
 mod tokenizer;
 mod hooks;
+mod idempotency;
+mod idle;
+mod warmup;
 mod skills_sync;
 
 #[tokio::main]
 async fn main() {
     let cli = cli::Cli::parse();
 
-    init_tracing(resolve_verbose(&cli));
+    init_tracing(resolve_verbose(&cli), cli.quiet, log_format(cli.log_format.clone(), std::env::var("COPILOT_LOG_FORMAT").ok()));
 
     if let Some(Command::Auth(args)) = &cli.command {
         run_auth_flow(args).await;
         return;
     }
 
-    if let Some(Command::CheckUsage) = &cli.command {
-        let client = reqwest::Client::builder()
-            .user_agent("copilot-api-rs")
+    if let Some(Command::CheckUsage(CheckUsageArgs { json })) = &cli.command {
+        let client = config::http_client_builder()
             .build()
             .expect("reqwest client");
         let config = state::AppConfig::default();
-        let state = state::AppState {
-            config: std::sync::Arc::new(tokio::sync::RwLock::new(config)),
-            client,
-            hooks: None,
-        };
-        if let Err(err) = commands::run_check_usage(&state).await {
+        let state = state::AppState::new(config, client, None);
+        if let Err(err) = commands::run_check_usage(&state, *json).await {
             eprintln!("Failed to fetch usage: {}", err);
         }
         return;
     }
 
+    if let Some(Command::Models(ModelsArgs { json })) = &cli.command {
+        let client = config::http_client_builder()
+            .build()
+            .expect("reqwest client");
+        let config = state::AppConfig {
+            vscode_version: services::vscode::fetch_vscode_version().await,
+            ..Default::default()
+        };
+        let state = state::AppState::new(config, client, None);
+        if let Err(err) = commands::run_models(&state, *json).await {
+            eprintln!("Failed to list models: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Export(args)) = &cli.command {
+        let client = config::http_client_builder()
+            .build()
+            .expect("reqwest client");
+        let state = state::AppState::new(state::AppConfig::default(), client, None);
+        let result = commands::run_export(
+            &state,
+            &args.server_url,
+            args.model.as_deref(),
+            args.small_model.as_deref(),
+            &args.format,
+            args.output.as_deref(),
+        )
+        .await;
+        if let Err(err) = result {
+            eprintln!("Failed to export Claude Code settings: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     if let Some(Command::Debug(DebugArgs { json })) = &cli.command {
         if let Err(err) = commands::run_debug(*json).await {
             eprintln!("Failed to print debug info: {}", err);
@@ -58,6 +103,38 @@ async fn main() {
         return;
     }
 
+    if let Some(Command::Paths(PathsArgs { json })) = &cli.command {
+        if let Err(err) = commands::run_paths(*json).await {
+            eprintln!("Failed to resolve paths: {}", err);
+        }
+        return;
+    }
+
+    if let Some(Command::Stats(StatsArgs { json })) = &cli.command {
+        if let Err(err) = commands::run_stats(*json) {
+            eprintln!("Failed to read usage stats: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Sessions(SessionsArgs { action })) = &cli.command {
+        let result = match action {
+            SessionsAction::List => commands::run_sessions_list(),
+            SessionsAction::Clean(args) => commands::run_sessions_clean(args.older_than),
+        };
+        if let Err(err) = result {
+            eprintln!("Failed to manage sessions: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Version(VersionArgs { check })) = &cli.command {
+        let _ = commands::run_version(*check).await;
+        return;
+    }
+
     if let Some(Command::SyncSkills) = &cli.command {
         if let Err(err) = skills_sync::sync_skills().await {
             eprintln!("Failed to sync skills: {}", err);
@@ -67,28 +144,46 @@ async fn main() {
         return;
     }
 
+    if let Some(Command::Hook(HookArgs { action: Some(action), .. })) = &cli.command {
+        match action {
+            HookAction::Validate(args) => match commands::run_hook_validate(args.path.as_deref()) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(err) => {
+                    eprintln!("Failed to validate hooks.json: {}", err);
+                    std::process::exit(1);
+                }
+            },
+            HookAction::Schema => print!("{}", hooks::schema::HOOKS_SCHEMA),
+        }
+        return;
+    }
+
     if let Some(Command::Hook(args)) = &cli.command {
-        let input = read_hook_input();
+        let input = read_hook_input(args.input.as_deref());
         let event = args.event.clone().or_else(|| input.hook_type.clone()).unwrap_or_else(|| "PreToolUse".to_string());
         let observer = hooks::observe::start_observer().await.ok();
         let config_path = args.config.as_ref().map(std::path::PathBuf::from);
         let executor = HookExecutor::load(config_path, observer).unwrap();
         let results = executor.execute_event(&event, &input).await.unwrap_or_default();
         let blocked = results.iter().any(|r| r.exit_code != 0);
-        for r in &results {
-            if !r.stderr.is_empty() {
-                eprintln!("{}", r.stderr.trim_end());
+        if args.print_results {
+            println!("{}", serde_json::to_string_pretty(&results).unwrap_or_default());
+        } else {
+            for r in &results {
+                if !r.stderr.is_empty() {
+                    eprintln!("{}", r.stderr.trim_end());
+                }
             }
+            println!("{}", serde_json::to_string(&input).unwrap_or_default());
         }
-        println!("{}", serde_json::to_string(&input).unwrap_or_default());
         if blocked {
             std::process::exit(1);
         }
         return;
     }
 
-    let mut client_builder = reqwest::Client::builder()
-        .user_agent("copilot-api-rs")
+    let mut client_builder = config::http_client_builder()
         .timeout(std::time::Duration::from_secs(60))
         .connect_timeout(std::time::Duration::from_secs(10))
         .pool_idle_timeout(std::time::Duration::from_secs(90))
@@ -142,30 +237,42 @@ async fn main() {
     }
     config.vscode_version = services::vscode::fetch_vscode_version().await;
 
+    let models_file = match &cli.command {
+        Some(Command::Start(args)) => args.models_file.clone(),
+        _ => cli.models_file.clone(),
+    }
+    .or_else(|| std::env::var("COPILOT_MODELS_FILE").ok().filter(|p| !p.trim().is_empty()));
+    if let Some(path) = models_file {
+        match routes::models::load_models_file(&path) {
+            Ok(models) => {
+                tracing::info!("Serving {} models from {}", models.data.len(), path);
+                config.static_models = Some(models);
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let hooks_enabled = std::env::var("COPILOT_HOOKS_ENABLED")
         .map(|v| !(v == "0" || v.eq_ignore_ascii_case("false")))
         .unwrap_or(true);
     let observer = if hooks_enabled { hooks::observe::start_observer().await.ok() } else { None };
+    let disabled_builtins = hooks::builtins::disabled_builtins();
+    if hooks_enabled && !disabled_builtins.is_empty() {
+        tracing::info!("Disabled hook builtins: {}", disabled_builtins.join(", "));
+    }
     let hook_executor = if hooks_enabled {
         HookExecutor::load(None, observer).ok().map(std::sync::Arc::new)
     } else {
         None
     };
-    let state = state::AppState {
-        config: std::sync::Arc::new(tokio::sync::RwLock::new(config)),
-        client,
-        hooks: hook_executor.clone(),
-    };
+    let state = state::AppState::new(config, client, hook_executor.clone());
 
     if let Some(hooks) = hook_executor.clone() {
         let input = HookInput { hook_type: Some("SessionStart".to_string()), ..Default::default() };
         let _ = hooks.execute_event("SessionStart", &input).await;
-        let stop_hooks = hooks.clone();
-        tokio::spawn(async move {
-            let _ = tokio::signal::ctrl_c().await;
-            let input = HookInput { hook_type: Some("SessionEnd".to_string()), ..Default::default() };
-            let _ = stop_hooks.execute_event("SessionEnd", &input).await;
-        });
     }
 
     // Prewarm tokens/models in background for stability and faster first request.
@@ -191,73 +298,208 @@ async fn main() {
         });
     }
 
-    if let Some(Command::Start(StartArgs { host, port, claude_code, .. })) = &cli.command {
+    warmup::spawn(state.clone(), warmup::warmup_interval());
+    let stats_flusher = stats::spawn_flusher(state.shutdown.clone());
+
+    if let Some(Command::Start(StartArgs { host, port, claude_code, model, small_model, non_interactive, .. })) =
+        &cli.command
+    {
         if *claude_code {
             let server_url = format!("http://{}:{}", host, port);
-            if let Err(err) = commands::run_claude_code_helper(&state, &server_url).await {
+            if let Err(err) = commands::run_claude_code_helper(
+                &state,
+                &server_url,
+                model.as_deref(),
+                small_model.as_deref(),
+                *non_interactive,
+            )
+            .await
+            {
                 eprintln!("Failed to prepare Claude Code helper: {}", err);
             }
         }
     } else if cli.claude_code {
         if let Some((host, port)) = cli.addr.split_once(':') {
             let server_url = format!("http://{}:{}", host, port);
-            if let Err(err) = commands::run_claude_code_helper(&state, &server_url).await {
+            if let Err(err) = commands::run_claude_code_helper(
+                &state,
+                &server_url,
+                cli.model.as_deref(),
+                cli.small_model.as_deref(),
+                cli.non_interactive,
+            )
+            .await
+            {
                 eprintln!("Failed to prepare Claude Code helper: {}", err);
             }
         }
     }
 
-    let app = Router::new()
-        .route("/", get(routes::misc::root))
+    let preload_tokenizer = match &cli.command {
+        Some(Command::Start(args)) => args.preload_tokenizer.clone(),
+        _ => cli.preload_tokenizer.clone(),
+    };
+    if let Some(name) = preload_tokenizer.or_else(|| tokenizer::use_precise_tokenizer().then(|| "o200k_base".to_string())) {
+        tracing::info!("Preloading tokenizer {}", name);
+        tokenizer::preload(&name);
+    }
+
+    let activity = state.activity.clone();
+    let shutdown = state.shutdown.clone();
+    let ready_state = state.clone();
+    let api_key = std::env::var("COPILOT_API_KEY").ok().filter(|key| !key.is_empty());
+    let insecure = api_key.is_none();
+    let app = build_app(state, api_key, max_body_bytes());
+
+    let (addr, allow_insecure, port_file, wait_ready, bind_retries) = match &cli.command {
+        Some(Command::Start(StartArgs { host, port, allow_insecure, port_file, wait_ready, bind_retries, .. })) => {
+            (format!("{}:{}", host, port), *allow_insecure, port_file.clone(), *wait_ready, *bind_retries)
+        }
+        _ => (cli.addr, cli.allow_insecure, cli.port_file, cli.wait_ready, cli.bind_retries),
+    };
+
+    if !listener::is_loopback_addr(&addr) && insecure {
+        tracing::warn!(
+            "Binding to {} without COPILOT_API_KEY exposes your Copilot quota to anyone who can reach this address",
+            addr
+        );
+        if !allow_insecure {
+            eprintln!("Refusing to listen on non-loopback address {} without COPILOT_API_KEY; pass --allow-insecure to proceed", addr);
+            std::process::exit(1);
+        }
+    }
+
+    let listener = match listener::bind_with_retries(&addr, bind_retries).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!("Failed to bind {}: {}", addr, err);
+            eprintln!("Failed to bind {}: {}", addr, err);
+            std::process::exit(1);
+        }
+    };
+    let addr = listener.local_addr().map(|a| a.to_string()).unwrap_or(addr);
+    if wait_ready && !routes::misc::wait_ready(&ready_state, READY_TIMEOUT).await {
+        tracing::warn!("Token/models not ready after {}s; serving anyway", READY_TIMEOUT.as_secs());
+    }
+    if let Some(path) = &port_file {
+        let port = listener.local_addr().map(|a| a.port()).unwrap_or_default();
+        if let Err(err) = listener::write_port_file(std::path::Path::new(path), port) {
+            tracing::warn!("Failed to write port file {}: {}", path, err);
+        }
+    }
+
+    if let Ok(base) = std::env::var("COPILOT_USAGE_VIEWER_URL") {
+        let endpoint = format!("http://{}", addr);
+        tracing::info!("Usage viewer: {}?endpoint={}", base, endpoint);
+    }
+    tracing::info!("listening on {}", addr);
+    let signal = shutdown_signal(activity, idle::idle_timeout());
+    let result = axum::serve(listener, app)
+        .with_graceful_shutdown(session_end_on(signal, hook_executor))
+        .await;
+    shutdown.cancel();
+    let _ = stats_flusher.await;
+    result.expect("server failed");
+}
+
+/// Every route except `/` and the health probes requires COPILOT_API_KEY when it
+/// is set, and only those routes count as activity for COPILOT_IDLE_TIMEOUT.
+fn build_app(state: state::AppState, api_key: Option<String>, max_body: usize) -> Router {
+    let activity = state.activity.clone();
+    let idempotency = state.idempotency.clone();
+    let api = Router::new()
         .route("/chat/completions", post(routes::chat_completions::handle))
+        .route("/completions", post(routes::completions::handle))
         .route("/models", get(routes::models::list))
         .route("/embeddings", post(routes::misc::embeddings))
         .route("/usage", get(routes::misc::usage))
         .route("/token", get(routes::misc::token))
+        .route("/debug/stream", get(routes::misc::debug_stream))
+        .route("/hooks/reload", post(routes::misc::reload_hooks))
+        .route("/metrics", get(metrics::metrics))
+        .route("/stats", get(stats::stats))
         .route("/auth/device-code", get(routes::auth::device_code))
         .route("/auth/poll", post(routes::auth::poll_token))
         .route("/auth/token", get(routes::auth::current_token))
+        .route("/auth/start", post(routes::auth::start))
+        .route("/auth/complete", post(routes::auth::complete))
         .route("/v1/chat/completions", post(routes::chat_completions::handle))
+        .route("/v1/completions", post(routes::completions::handle))
         .route("/v1/models", get(routes::models::list))
         .route("/v1/embeddings", post(routes::misc::embeddings))
+        .route("/v1/moderations", post(routes::moderations::handle))
+        .route(
+            "/v1/audio/transcriptions",
+            post(routes::audio::transcriptions).layer(DefaultBodyLimit::max(25 * 1024 * 1024)),
+        )
         .route("/v1/responses", post(routes::responses::handle))
         .route("/v1/messages", post(routes::messages::handle))
         .route("/v1/messages/count_tokens", post(routes::messages::count_tokens))
+        .route("/v1/messages/batch", post(routes::messages::batch))
+        .with_state(state.clone())
+        .layer(axum::middleware::from_fn_with_state(idempotency, idempotency::replay))
+        .layer(axum::middleware::from_fn_with_state(activity, idle::track_activity))
+        .layer(axum::middleware::from_fn_with_state(api_key, routes::misc::require_api_key));
+    Router::new()
+        .route("/", get(routes::misc::root))
+        .route("/health", get(routes::misc::health))
+        .route("/health/live", get(routes::misc::health_live))
         .with_state(state)
-        .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
-        .layer(TraceLayer::new_for_http());
+        .merge(api)
+        .layer(DefaultBodyLimit::max(max_body))
+        .layer(RequestDecompressionLayer::new())
+        .layer(compression_layer())
+        .layer(cors_layer(std::env::var("COPILOT_CORS_ORIGINS").ok().as_deref()))
+        .layer(TraceLayer::new_for_http())
+}
 
-    let addr = match &cli.command {
-        Some(Command::Start(StartArgs { host, port, .. })) => format!("{}:{}", host, port),
-        _ => cli.addr,
+/// Ctrl-C, or COPILOT_IDLE_TIMEOUT elapsing without requests.
+async fn shutdown_signal(activity: std::sync::Arc<idle::ActivityTracker>, idle_timeout: Option<std::time::Duration>) {
+    let idle = async {
+        match idle_timeout {
+            Some(timeout) => idle::wait_for_idle(activity, timeout).await,
+            None => std::future::pending().await,
+        }
     };
-
-    if let Ok(base) = std::env::var("COPILOT_USAGE_VIEWER_URL") {
-        let endpoint = format!("http://{}", addr);
-        tracing::info!("Usage viewer: {}?endpoint={}", base, endpoint);
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => tracing::info!("Shutting down"),
+        _ = idle => {}
     }
-    let listener = tokio::net::TcpListener::bind(&addr)
-        .await
-        .expect("bind failed");
+}
 
-    tracing::info!("listening on {}", addr);
-    axum::serve(listener, app).await.expect("server failed");
+/// Runs SessionEnd as soon as `signal` fires, before the server waits for open
+/// connections (long SSE streams) to drain.
+async fn session_end_on(signal: impl std::future::Future<Output = ()>, hooks: Option<std::sync::Arc<HookExecutor>>) {
+    signal.await;
+    if let Some(hooks) = hooks {
+        let input = HookInput { hook_type: Some("SessionEnd".to_string()), ..Default::default() };
+        let _ = hooks.execute_event("SessionEnd", &input).await;
+    }
 }
 
 async fn run_auth_flow(args: &AuthArgs) {
-    let client = reqwest::Client::builder()
-        .user_agent("copilot-api-rs")
+    let client = config::http_client_builder()
         .build()
         .expect("reqwest client");
+    let config = state::AppConfig::default();
 
-    match services::github::get_device_code(&client).await {
+    match services::github::get_device_code(&client, &config).await {
         Ok(device) => {
             println!(
                 "Please enter the code \"{}\" in {}",
                 device.user_code, device.verification_uri
             );
+            if args.open {
+                if let Err(err) = arboard::Clipboard::new().and_then(|mut c| c.set_text(device.user_code.clone())) {
+                    tracing::warn!("Failed to copy code to clipboard: {}", err);
+                }
+                if let Err(err) = utils::open_url(&device.verification_uri) {
+                    eprintln!("Failed to open browser: {}", err);
+                }
+            }
 
-            match services::github::poll_access_token(&client, &device).await {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(device.expires_in);
+            match services::github::poll_access_token(&client, &config, &device, Some(deadline)).await {
                 Ok(token) => {
                     if let Err(err) = token_store::write_github_token(&token).await {
                         eprintln!("Failed to write GitHub token: {}", err);
@@ -282,31 +524,414 @@ fn resolve_verbose(cli: &cli::Cli) -> bool {
         Some(Command::Start(args)) => args.verbose,
         Some(Command::Auth(args)) => args.verbose,
         Some(Command::Debug(_)) => cli.verbose,
-        Some(Command::CheckUsage) => cli.verbose,
+        Some(Command::CheckUsage(_)) => cli.verbose,
+        Some(Command::Models(_)) => cli.verbose,
         Some(Command::Hook(_)) => cli.verbose,
         Some(Command::SyncSkills) => cli.verbose,
+        Some(Command::Version(_)) => cli.verbose,
+        Some(Command::Paths(_)) => cli.verbose,
+        Some(Command::Sessions(_)) => cli.verbose,
+        Some(Command::Export(_)) => cli.verbose,
+        Some(Command::Stats(_)) => cli.verbose,
         None => cli.verbose,
     }
 }
 
-fn init_tracing(verbose: bool) {
-    let filter = if verbose {
+const READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+const DEFAULT_MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+fn max_body_bytes() -> usize {
+    parse_max_body_bytes(std::env::var("COPILOT_MAX_BODY_BYTES").ok().as_deref())
+}
+
+fn parse_max_body_bytes(raw: Option<&str>) -> usize {
+    raw.and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+// Compressing event streams makes many clients buffer until the stream ends.
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .no_br()
+        .no_zstd()
+        .compress_when(SizeAbove::default().and(NotForContentType::SSE))
+}
+
+fn cors_layer(origins: Option<&str>) -> CorsLayer {
+    let origins: Vec<axum::http::HeaderValue> = origins
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .filter_map(|origin| match origin.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!("Ignoring invalid CORS origin: {}", origin);
+                None
+            }
+        })
+        .collect();
+
+    if origins.is_empty() {
+        return CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any);
+    }
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(true)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+// --log-format > COPILOT_LOG_FORMAT > text
+fn log_format(flag: Option<String>, env: Option<String>) -> LogFormat {
+    match flag.or(env).as_deref().map(str::trim) {
+        Some(v) if v.eq_ignore_ascii_case("json") => LogFormat::Json,
+        Some(v) if !v.is_empty() && !v.eq_ignore_ascii_case("text") => {
+            eprintln!("Unknown log format {:?}, using text", v);
+            LogFormat::Text
+        }
+        _ => LogFormat::Text,
+    }
+}
+
+fn init_tracing(verbose: bool, quiet: bool, format: LogFormat) {
+    let filter = match log_directive(std::env::var("COPILOT_LOG").ok(), verbose, quiet) {
+        Some(directive) => tracing_subscriber::EnvFilter::try_new(&directive).unwrap_or_else(|err| {
+            eprintln!("Invalid COPILOT_LOG directive {:?}: {}", directive, err);
+            fallback_filter(verbose)
+        }),
+        None => fallback_filter(verbose),
+    };
+
+    build_subscriber(filter, format).init();
+}
+
+fn build_subscriber(
+    filter: tracing_subscriber::EnvFilter,
+    format: LogFormat,
+) -> Box<dyn tracing::Subscriber + Send + Sync> {
+    let registry = tracing_subscriber::registry().with(filter);
+    match format {
+        LogFormat::Text => Box::new(registry.with(tracing_subscriber::fmt::layer())),
+        LogFormat::Json => Box::new(registry.with(tracing_subscriber::fmt::layer().json())),
+    }
+}
+
+// COPILOT_LOG > --verbose > --quiet > RUST_LOG
+fn log_directive(copilot_log: Option<String>, verbose: bool, quiet: bool) -> Option<String> {
+    match copilot_log.filter(|v| !v.trim().is_empty()) {
+        Some(directive) => Some(directive),
+        None if verbose => Some("debug".to_string()),
+        None if quiet => Some("error".to_string()),
+        None => None,
+    }
+}
+
+fn fallback_filter(verbose: bool) -> tracing_subscriber::EnvFilter {
+    if verbose {
         tracing_subscriber::EnvFilter::new("debug")
     } else {
         tracing_subscriber::EnvFilter::from_default_env()
-    };
-
-    tracing_subscriber::registry()
-        .with(filter)
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    }
 }
 
-fn read_hook_input() -> HookInput {
+fn read_hook_input(path: Option<&str>) -> HookInput {
     let mut buffer = String::new();
-    let _ = std::io::stdin().read_to_string(&mut buffer);
+    match path {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(content) => buffer = content,
+            Err(err) => {
+                eprintln!("Failed to read hook input {}: {}", path, err);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let _ = std::io::stdin().read_to_string(&mut buffer);
+        }
+    }
     if buffer.trim().is_empty() {
         return HookInput::default();
     }
     serde_json::from_str::<HookInput>(&buffer).unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{build_app, build_subscriber, compression_layer, cors_layer, log_directive, log_format, parse_max_body_bytes, session_end_on, LogFormat, DEFAULT_MAX_BODY_BYTES};
+    use crate::state::test_state;
+    use axum::{routing::{get, post}, Json, Router};
+    use std::io::Write;
+    use tower_http::decompression::RequestDecompressionLayer;
+
+
+    #[tokio::test]
+    async fn session_end_runs_while_streams_are_still_open() {
+        let dir = std::env::temp_dir().join(format!("copilot-session-end-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("ended");
+        let hooks_path = dir.join("hooks.json");
+        let command = format!("echo done > \"{}\"", marker.display());
+        let config = serde_json::json!({
+            "hooks": { "SessionEnd": [{ "matcher": "*", "hooks": [{ "type": "command", "command": command }] }] }
+        });
+        std::fs::write(&hooks_path, config.to_string()).unwrap();
+        let hooks = crate::hooks::HookExecutor::load(Some(hooks_path), None).unwrap();
+
+        let app = Router::new().route(
+            "/stream",
+            get(|| async { axum::body::Body::from_stream(futures::stream::pending::<Result<bytes::Bytes, std::io::Error>>()) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let signal = async move {
+            let _ = stopped.await;
+        };
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(session_end_on(signal, Some(std::sync::Arc::new(hooks))))
+                .await
+        });
+
+        let open_stream = reqwest::get(format!("http://{addr}/stream")).await.unwrap();
+        stop.send(()).unwrap();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !marker.exists() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(marker.exists(), "SessionEnd did not run on the shutdown signal");
+        assert!(!server.is_finished(), "server should still be draining the open stream");
+
+        drop(open_stream);
+        server.abort();
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn health_probes_do_not_count_as_activity() {
+        let state = test_state();
+        let activity = state.activity.clone();
+        let app = build_app(state, None, DEFAULT_MAX_BODY_BYTES);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let before = activity.last_activity();
+        for path in ["/", "/health", "/health/live"] {
+            client.get(format!("http://{addr}{path}")).send().await.unwrap();
+        }
+        assert_eq!(activity.last_activity(), before);
+
+        let body = serde_json::json!({ "model": "gpt-4o", "max_tokens": 16, "messages": [{ "role": "user", "content": "hi" }] });
+        let resp = client.post(format!("http://{addr}/v1/messages/count_tokens")).json(&body).send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert!(activity.last_activity() > before);
+    }
+
+    #[tokio::test]
+    async fn api_routes_require_the_configured_key() {
+        let app = build_app(test_state(), Some("secret".to_string()), DEFAULT_MAX_BODY_BYTES);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let body = serde_json::json!({ "model": "gpt-4o", "messages": [{ "role": "user", "content": "hi" }] });
+        let url = format!("http://{}/v1/chat/completions", addr);
+        let missing = client.post(&url).json(&body).send().await.unwrap();
+        assert_eq!(missing.status(), 401);
+        let wrong = client.post(&url).bearer_auth("nope").json(&body).send().await.unwrap();
+        assert_eq!(wrong.status(), 401);
+        let token = client.get(format!("http://{}/token", addr)).send().await.unwrap();
+        assert_eq!(token.status(), 401);
+
+        let live = client.get(format!("http://{}/health/live", addr)).send().await.unwrap();
+        assert_eq!(live.status(), 200);
+    }
+
+    #[test]
+    fn max_body_bytes_falls_back_to_the_default() {
+        assert_eq!(parse_max_body_bytes(Some("1048576")), 1048576);
+        assert_eq!(parse_max_body_bytes(Some(" 2048 ")), 2048);
+        assert_eq!(parse_max_body_bytes(None), DEFAULT_MAX_BODY_BYTES);
+        assert_eq!(parse_max_body_bytes(Some("0")), DEFAULT_MAX_BODY_BYTES);
+        assert_eq!(parse_max_body_bytes(Some("32MB")), DEFAULT_MAX_BODY_BYTES);
+    }
+
+    #[tokio::test]
+    async fn oversized_body_is_rejected_with_413_even_when_gzipped() {
+        let app = build_app(test_state(), None, 1024);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/v1/messages/count_tokens", addr);
+        let large = serde_json::to_vec(&serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [{ "role": "user", "content": "x".repeat(4096) }],
+        }))
+        .unwrap();
+
+        let plain = client.post(&url).header("content-type", "application/json").body(large.clone()).send().await.unwrap();
+        assert_eq!(plain.status(), 413);
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&large).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        assert!(gzipped.len() < 1024);
+        let resp = client
+            .post(&url)
+            .header("content-type", "application/json")
+            .header("content-encoding", "gzip")
+            .body(gzipped)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 413);
+    }
+
+    #[tokio::test]
+    async fn compresses_json_but_not_event_streams() {
+        let models = serde_json::json!({
+            "object": "list",
+            "data": (0..50).map(|i| serde_json::json!({ "id": format!("model-{i}"), "object": "model" })).collect::<Vec<_>>(),
+        });
+        let app = Router::new()
+            .route("/v1/models", get(move || async move { Json(models) }))
+            .route(
+                "/v1/messages",
+                post(|| async {
+                    let body = "event: ping\ndata: {}\n\n".repeat(20);
+                    ([("content-type", "text/event-stream")], body)
+                }),
+            )
+            .route("/echo", post(|Json(body): Json<serde_json::Value>| async move { Json(body) }))
+            .layer(RequestDecompressionLayer::new())
+            .layer(compression_layer());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::builder().no_gzip().build().unwrap();
+        let models = client
+            .get(format!("http://{}/v1/models", addr))
+            .header("accept-encoding", "gzip")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(models.headers().get("content-encoding").unwrap(), "gzip");
+
+        let stream = client
+            .post(format!("http://{}/v1/messages", addr))
+            .header("accept-encoding", "gzip")
+            .send()
+            .await
+            .unwrap();
+        assert!(stream.headers().get("content-encoding").is_none());
+        assert!(stream.text().await.unwrap().starts_with("event: ping"));
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(br#"{"text":"hi"}"#).unwrap();
+        let echoed = client
+            .post(format!("http://{}/echo", addr))
+            .header("content-type", "application/json")
+            .header("content-encoding", "gzip")
+            .body(encoder.finish().unwrap())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(echoed.status(), 200);
+        assert_eq!(echoed.text().await.unwrap(), r#"{"text":"hi"}"#);
+    }
+
+    async fn cors_headers(origins: Option<&str>, origin: &str) -> reqwest::header::HeaderMap {
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(cors_layer(origins));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let resp = reqwest::Client::new()
+            .get(format!("http://{}/", addr))
+            .header("origin", origin)
+            .send()
+            .await
+            .unwrap();
+        resp.headers().clone()
+    }
+
+    #[tokio::test]
+    async fn cors_reflects_only_configured_origins() {
+        let origins = Some("https://app.example.com, https://admin.example.com");
+
+        let allowed = cors_headers(origins, "https://app.example.com").await;
+        assert_eq!(allowed.get("access-control-allow-origin").unwrap(), "https://app.example.com");
+        assert_eq!(allowed.get("access-control-allow-credentials").unwrap(), "true");
+
+        let denied = cors_headers(origins, "https://evil.example.com").await;
+        assert!(denied.get("access-control-allow-origin").is_none());
+    }
+
+    #[tokio::test]
+    async fn cors_defaults_to_any_origin() {
+        let headers = cors_headers(None, "https://anything.example.com").await;
+        assert_eq!(headers.get("access-control-allow-origin").unwrap(), "*");
+    }
+
+    #[test]
+    fn log_format_flag_overrides_env() {
+        assert_eq!(log_format(None, None), LogFormat::Text);
+        assert_eq!(log_format(None, Some("JSON".to_string())), LogFormat::Json);
+        assert_eq!(log_format(Some("text".to_string()), Some("json".to_string())), LogFormat::Text);
+        assert_eq!(log_format(None, Some("yaml".to_string())), LogFormat::Text);
+    }
+
+    #[test]
+    fn both_log_formats_keep_the_verbose_filter() {
+        for format in [LogFormat::Text, LogFormat::Json] {
+            let subscriber = build_subscriber(tracing_subscriber::EnvFilter::new("debug"), format);
+            tracing::subscriber::with_default(subscriber, || {
+                assert!(tracing::enabled!(tracing::Level::DEBUG));
+                assert!(!tracing::enabled!(tracing::Level::TRACE));
+                tracing::debug!(format = ?format, "log format smoke test");
+            });
+        }
+    }
+
+    #[test]
+    fn copilot_log_overrides_verbose() {
+        let directive = "copilot_api_server::routes=debug,reqwest=warn".to_string();
+        assert_eq!(log_directive(Some(directive.clone()), true, false), Some(directive.clone()));
+        assert_eq!(log_directive(Some(directive.clone()), false, false), Some(directive));
+        assert_eq!(log_directive(None, true, false).as_deref(), Some("debug"));
+        assert_eq!(log_directive(Some(" ".to_string()), false, false), None);
+        assert_eq!(log_directive(None, false, false), None);
+    }
+
+    #[test]
+    fn quiet_selects_error_filter_below_verbose_and_copilot_log() {
+        assert_eq!(log_directive(None, false, true).as_deref(), Some("error"));
+        assert_eq!(log_directive(None, true, true).as_deref(), Some("debug"));
+        assert_eq!(log_directive(Some("info".to_string()), false, true).as_deref(), Some("info"));
+    }
+}
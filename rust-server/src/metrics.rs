@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use axum::{http::header, response::IntoResponse};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use once_cell::sync::Lazy;
+
+use crate::routes::streaming::{drain_sse_blocks, extract_sse_data};
+
+// (model, kind) -> tokens, kind being "input" or "output".
+static TOKENS: Lazy<Mutex<BTreeMap<(String, &'static str), u64>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+pub fn record_tokens(model: &str, input: u64, output: u64) {
+    let mut tokens = TOKENS.lock().unwrap();
+    *tokens.entry((model.to_string(), "input")).or_default() += input;
+    *tokens.entry((model.to_string(), "output")).or_default() += output;
+}
+
+/// Records the usage object of a chat completion (`prompt_tokens`) or
+/// Responses API (`input_tokens`) payload, if it has one, and counts the
+/// request towards the persisted per-model stats either way.
+pub fn record_usage(model: &str, json: &serde_json::Value) {
+    let usage = usage_tokens(json);
+    if let Some((input, output)) = usage {
+        record_tokens(model, input, output);
+    }
+    let (input, output) = usage.unwrap_or_default();
+    crate::stats::record(model, input, output);
+}
+
+fn usage_tokens(json: &serde_json::Value) -> Option<(u64, u64)> {
+    let usage = json
+        .get("usage")
+        .or_else(|| json.get("response").and_then(|r| r.get("usage")))
+        .filter(|u| u.is_object())?;
+    let field = |a: &str, b: &str| usage.get(a).or_else(|| usage.get(b)).and_then(|v| v.as_u64()).unwrap_or(0);
+    Some((field("prompt_tokens", "input_tokens"), field("completion_tokens", "output_tokens")))
+}
+
+/// Passes an upstream SSE stream through untouched, recording the last usage
+/// object it carried once the stream ends.
+pub fn tap_usage<S, E>(stream: S, model: String) -> impl Stream<Item = Result<Bytes, E>>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    async_stream::stream! {
+        let mut buffer = Vec::<u8>::new();
+        let mut last_usage = None;
+        futures::pin_mut!(stream);
+        while let Some(chunk) = stream.next().await {
+            if let Ok(bytes) = &chunk {
+                buffer.extend_from_slice(bytes);
+                for block in drain_sse_blocks(&mut buffer) {
+                    let usage = extract_sse_data(&block)
+                        .and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+                        .and_then(|json| usage_tokens(&json));
+                    if usage.is_some() {
+                        last_usage = usage;
+                    }
+                }
+            }
+            yield chunk;
+        }
+        if let Some((input, output)) = last_usage {
+            record_tokens(&model, input, output);
+        }
+        let (input, output) = last_usage.unwrap_or_default();
+        crate::stats::record(&model, input, output);
+    }
+}
+
+fn render() -> String {
+    let tokens = TOKENS.lock().unwrap();
+    let mut out = String::from(
+        "# HELP copilot_tokens_total Tokens reported by upstream usage, by model and kind.\n# TYPE copilot_tokens_total counter\n",
+    );
+    for ((model, kind), count) in tokens.iter() {
+        out.push_str(&format!(
+            "copilot_tokens_total{{model=\"{}\",kind=\"{}\"}} {}\n",
+            model.replace('\\', "\\\\").replace('"', "\\\""),
+            kind,
+            count
+        ));
+    }
+    out
+}
+
+pub async fn metrics() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{record_usage, render, tap_usage, TOKENS};
+    use bytes::Bytes;
+    use futures::StreamExt;
+
+    fn counter(model: &str, kind: &'static str) -> u64 {
+        TOKENS.lock().unwrap().get(&(model.to_string(), kind)).copied().unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn completed_stream_increments_token_counters() {
+        let model = "test-stream-model";
+        let chunks = vec![
+            Ok::<Bytes, std::io::Error>(Bytes::from("data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\n")),
+            Ok(Bytes::from("data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,")),
+            Ok(Bytes::from("\"completion_tokens\":5,\"total_tokens\":17}}\n\ndata: [DONE]\n\n")),
+        ];
+
+        let relayed: Vec<Bytes> = tap_usage(futures::stream::iter(chunks), model.to_string())
+            .map(|c| c.expect("chunk"))
+            .collect()
+            .await;
+
+        assert_eq!(relayed.len(), 3);
+        assert_eq!(counter(model, "input"), 12);
+        assert_eq!(counter(model, "output"), 5);
+        assert!(render().contains("copilot_tokens_total{model=\"test-stream-model\",kind=\"input\"} 12\n"));
+    }
+
+    #[test]
+    fn records_responses_api_usage() {
+        let model = "test-responses-model";
+        record_usage(model, &serde_json::json!({"type": "response.completed", "response": {"usage": {"input_tokens": 7, "output_tokens": 3}}}));
+        record_usage(model, &serde_json::json!({"output": []}));
+        assert_eq!(counter(model, "input"), 7);
+        assert_eq!(counter(model, "output"), 3);
+    }
+}
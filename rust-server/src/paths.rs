@@ -1,10 +1,13 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::errors::{ApiError, ApiResult};
 
 pub struct AppPaths {
     pub app_dir: PathBuf,
     pub github_token_path: PathBuf,
+    pub vscode_version_path: PathBuf,
+    pub claude_code_models_path: PathBuf,
+    pub usage_stats_path: PathBuf,
 }
 
 pub fn get_paths() -> ApiResult<AppPaths> {
@@ -13,31 +16,69 @@ pub fn get_paths() -> ApiResult<AppPaths> {
     let base = base_dirs.data_local_dir();
 
     let app_dir = base.join("copilot-api");
-    let github_token_path = app_dir.join("github_token");
+    let github_token_path = github_token_path(std::env::var("COPILOT_TOKEN_PATH").ok().as_deref(), &app_dir);
+    let vscode_version_path = app_dir.join("vscode_version.json");
+    let claude_code_models_path = app_dir.join("claude_code_models.json");
+    let usage_stats_path = app_dir.join("usage_stats.json");
 
     Ok(AppPaths {
         app_dir,
         github_token_path,
+        vscode_version_path,
+        claude_code_models_path,
+        usage_stats_path,
     })
 }
 
+/// COPILOT_TOKEN_PATH when set, otherwise `github_token` in the app dir.
+fn github_token_path(override_path: Option<&str>, app_dir: &Path) -> PathBuf {
+    override_path
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| app_dir.join("github_token"))
+}
+
 pub async fn ensure_paths() -> ApiResult<AppPaths> {
     let paths = get_paths()?;
     tokio::fs::create_dir_all(&paths.app_dir)
         .await
         .map_err(|e| ApiError::Internal(format!("Failed to create app dir: {e}")))?;
+    ensure_token_file(&paths.github_token_path).await?;
+    Ok(paths)
+}
 
-    if !paths.github_token_path.exists() {
-        tokio::fs::write(&paths.github_token_path, "")
+/// Creates an empty, owner-only token file (and its directory) if missing.
+pub async fn ensure_token_file(path: &Path) -> ApiResult<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Failed to create token dir: {e}")))?;
+    }
+    if !path.exists() {
+        tokio::fs::write(path, "")
             .await
             .map_err(|e| ApiError::Internal(format!("Failed to create token file: {e}")))?;
 
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            let _ = tokio::fs::set_permissions(&paths.github_token_path, std::fs::Permissions::from_mode(0o600)).await;
+            let _ = tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await;
         }
     }
+    Ok(())
+}
 
-    Ok(paths)
+#[cfg(test)]
+mod tests {
+    use super::github_token_path;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn token_path_override_wins_over_app_dir() {
+        let app_dir = Path::new("/data/copilot-api");
+        assert_eq!(github_token_path(None, app_dir), app_dir.join("github_token"));
+        assert_eq!(github_token_path(Some("  "), app_dir), app_dir.join("github_token"));
+        assert_eq!(github_token_path(Some(" /secrets/gh_token "), app_dir), PathBuf::from("/secrets/gh_token"));
+    }
 }
@@ -1,16 +1,46 @@
-use crate::{errors::{ApiError, ApiResult}, state::AppState};
+use std::collections::HashMap;
+use std::time::Instant;
 
-pub async fn check_rate_limit(state: &AppState) -> ApiResult<()> {
+use crate::{errors::{ApiError, ApiResult}, state::{AppConfig, AppState}};
+
+/// Parses `COPILOT_RATE_LIMIT_PER_MODEL`, a JSON map of model id to interval seconds.
+pub fn parse_per_model_limits(raw: &str) -> HashMap<String, (u64, Option<Instant>)> {
+    match serde_json::from_str::<HashMap<String, u64>>(raw) {
+        Ok(map) => map.into_iter().map(|(model, secs)| (model, (secs, None))).collect(),
+        Err(err) => {
+            tracing::warn!("Ignoring invalid COPILOT_RATE_LIMIT_PER_MODEL: {}", err);
+            HashMap::new()
+        }
+    }
+}
+
+fn window_for(config: &AppConfig, model: &str) -> (Option<u64>, Option<Instant>) {
+    match config.rate_limit_per_model.get(model) {
+        Some((interval, last)) => (Some(*interval), *last),
+        None => (config.rate_limit_seconds, config.last_request_timestamp),
+    }
+}
+
+fn record_request(config: &mut AppConfig, model: &str, at: Instant) {
+    match config.rate_limit_per_model.get_mut(model) {
+        Some((_, last)) => *last = Some(at),
+        None => config.last_request_timestamp = Some(at),
+    }
+}
+
+/// `model` must already be alias-resolved, so an alias cannot sidestep the
+/// limit configured for the model it maps to.
+pub async fn check_rate_limit(state: &AppState, model: &str) -> ApiResult<()> {
     let mut config = state.config.write().await;
 
-    let limit = match config.rate_limit_seconds {
-        Some(v) => v,
-        None => return Ok(()),
+    let (limit, last) = match window_for(&config, model) {
+        (Some(limit), last) => (limit, last),
+        (None, _) => return Ok(()),
     };
 
-    let now = std::time::Instant::now();
+    let now = Instant::now();
 
-    if let Some(last) = config.last_request_timestamp {
+    if let Some(last) = last {
         let elapsed = now.duration_since(last).as_secs_f64();
         if elapsed < limit as f64 {
             let wait_secs = (limit as f64 - elapsed).ceil() as u64;
@@ -22,18 +52,18 @@ pub async fn check_rate_limit(state: &AppState) -> ApiResult<()> {
             drop(config);
             tokio::time::sleep(std::time::Duration::from_secs(wait_secs)).await;
             let mut config = state.config.write().await;
-            config.last_request_timestamp = Some(std::time::Instant::now());
+            record_request(&mut config, model, Instant::now());
             return Ok(());
         }
     }
 
-    config.last_request_timestamp = Some(now);
+    record_request(&mut config, model, now);
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::check_rate_limit;
+    use super::{check_rate_limit, parse_per_model_limits};
     use crate::state::{AppConfig, AppState};
 
     #[tokio::test]
@@ -45,13 +75,9 @@ mod tests {
             ..AppConfig::default()
         };
 
-        let state = AppState {
-            config: std::sync::Arc::new(tokio::sync::RwLock::new(config)),
-            client: reqwest::Client::new(),
-            hooks: None,
-        };
+        let state = AppState::new(config, reqwest::Client::new(), None);
 
-        let result = check_rate_limit(&state).await;
+        let result = check_rate_limit(&state, "gpt-4o").await;
         assert!(result.is_err());
     }
 
@@ -62,13 +88,9 @@ mod tests {
             ..AppConfig::default()
         };
 
-        let state = AppState {
-            config: std::sync::Arc::new(tokio::sync::RwLock::new(config)),
-            client: reqwest::Client::new(),
-            hooks: None,
-        };
+        let state = AppState::new(config, reqwest::Client::new(), None);
 
-        let result = check_rate_limit(&state).await;
+        let result = check_rate_limit(&state, "gpt-4o").await;
         assert!(result.is_ok());
     }
 
@@ -81,13 +103,49 @@ mod tests {
             ..AppConfig::default()
         };
 
-        let state = AppState {
-            config: std::sync::Arc::new(tokio::sync::RwLock::new(config)),
-            client: reqwest::Client::new(),
-            hooks: None,
-        };
+        let state = AppState::new(config, reqwest::Client::new(), None);
 
-        let result = check_rate_limit(&state).await;
+        let result = check_rate_limit(&state, "gpt-4o").await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn per_model_limits_are_isolated() {
+        let config = AppConfig {
+            rate_limit_seconds: None,
+            rate_limit_wait: false,
+            rate_limit_per_model: parse_per_model_limits(r#"{"gpt-5.2-codex": 30}"#),
+            ..AppConfig::default()
+        };
+
+        let state = AppState::new(config, reqwest::Client::new(), None);
+
+        assert!(check_rate_limit(&state, "gpt-5.2-codex").await.is_ok());
+        assert!(check_rate_limit(&state, "gpt-5.2-codex").await.is_err());
+        assert!(check_rate_limit(&state, "gpt-4o").await.is_ok());
+        assert!(check_rate_limit(&state, "gpt-4o").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn per_model_entry_does_not_consume_global_window() {
+        let config = AppConfig {
+            rate_limit_seconds: Some(10),
+            rate_limit_wait: false,
+            rate_limit_per_model: parse_per_model_limits(r#"{"gpt-5.2-codex": 1}"#),
+            ..AppConfig::default()
+        };
+
+        let state = AppState::new(config, reqwest::Client::new(), None);
+
+        assert!(check_rate_limit(&state, "gpt-5.2-codex").await.is_ok());
+        assert!(check_rate_limit(&state, "gpt-4o").await.is_ok());
+        assert!(check_rate_limit(&state, "gpt-4o").await.is_err());
+    }
+
+    #[test]
+    fn parse_per_model_limits_ignores_invalid_json() {
+        assert!(parse_per_model_limits("not json").is_empty());
+        let parsed = parse_per_model_limits(r#"{"o3": 5}"#);
+        assert_eq!(parsed.get("o3").map(|(secs, _)| *secs), Some(5));
+    }
 }
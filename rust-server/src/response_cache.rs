@@ -0,0 +1,190 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::errors::ApiResult;
+use crate::services::copilot::ChatCompletionsPayload;
+
+const DEFAULT_CAPACITY: usize = 128;
+
+#[derive(Debug)]
+pub struct ResponseCache {
+    capacity: usize,
+    entries: HashMap<u64, serde_json::Value>,
+    order: VecDeque<u64>,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        let capacity = std::env::var("COPILOT_RESPONSE_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_CAPACITY);
+        Self::with_capacity(capacity)
+    }
+}
+
+impl ResponseCache {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<serde_json::Value> {
+        let value = self.entries.get(&key)?.clone();
+        self.touch(key);
+        Some(value)
+    }
+
+    pub fn insert(&mut self, key: u64, value: serde_json::Value) {
+        if self.entries.insert(key, value).is_some() {
+            self.touch(key);
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn touch(&mut self, key: u64) {
+        self.order.retain(|k| *k != key);
+        self.order.push_back(key);
+    }
+}
+
+pub fn enabled() -> bool {
+    std::env::var("COPILOT_RESPONSE_CACHE")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Cache key for deterministic, non-streaming requests; `None` when the
+/// request must not be cached. Every field except `stream` and `user` is part
+/// of the key, so requests differing only in e.g. `max_tokens` never share.
+pub fn cache_key(payload: &ChatCompletionsPayload) -> Option<u64> {
+    if payload.stream.unwrap_or(false) || payload.temperature.is_some_and(|t| t != 0.0) {
+        return None;
+    }
+    let mut material = serde_json::to_value(payload).ok()?;
+    if let Some(fields) = material.as_object_mut() {
+        fields.remove("stream");
+        fields.remove("user");
+    }
+    let mut hasher = DefaultHasher::new();
+    material.to_string().hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+pub async fn fetch_cached<F, Fut>(cache: &Mutex<ResponseCache>, key: u64, fetch: F) -> ApiResult<serde_json::Value>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = ApiResult<serde_json::Value>>,
+{
+    if let Some(hit) = cache.lock().unwrap().get(key) {
+        tracing::debug!("Serving cached response for key {:016x}", key);
+        return Ok(hit);
+    }
+    let value = fetch().await?;
+    cache.lock().unwrap().insert(key, value.clone());
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cache_key, fetch_cached, ResponseCache};
+    use crate::services::copilot::{ChatCompletionsPayload, Message};
+    use std::sync::Mutex;
+
+    fn payload(content: &str, temperature: Option<f64>) -> ChatCompletionsPayload {
+        ChatCompletionsPayload {
+            model: "gpt-4o".to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: serde_json::json!(content),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
+            }],
+            temperature,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn second_identical_request_is_served_from_cache() {
+        let cache = Mutex::new(ResponseCache::with_capacity(4));
+        let key = cache_key(&payload("hi", Some(0.0))).expect("cacheable");
+        let mut upstream_calls = 0;
+
+        for _ in 0..2 {
+            let json = fetch_cached(&cache, key, || {
+                upstream_calls += 1;
+                async { Ok(serde_json::json!({ "id": "chatcmpl-1" })) }
+            })
+            .await
+            .unwrap();
+            assert_eq!(json["id"], "chatcmpl-1");
+        }
+
+        assert_eq!(upstream_calls, 1);
+    }
+
+    #[test]
+    fn only_deterministic_non_streaming_requests_are_cacheable() {
+        assert!(cache_key(&payload("hi", None)).is_some());
+        assert_eq!(cache_key(&payload("hi", Some(0.0))), cache_key(&payload("hi", Some(0.0))));
+        assert_ne!(cache_key(&payload("hi", Some(0.0))), cache_key(&payload("bye", Some(0.0))));
+        assert!(cache_key(&payload("hi", Some(0.7))).is_none());
+
+        let mut streaming = payload("hi", None);
+        streaming.stream = Some(true);
+        assert!(cache_key(&streaming).is_none());
+    }
+
+    #[test]
+    fn every_request_field_except_stream_and_user_is_keyed() {
+        let base = cache_key(&payload("hi", Some(0.0)));
+        let variants: Vec<fn(&mut ChatCompletionsPayload)> = vec![
+            |p| p.max_tokens = Some(16),
+            |p| p.max_completion_tokens = Some(16),
+            |p| p.tool_choice = Some(serde_json::json!("required")),
+            |p| p.top_p = Some(0.5),
+            |p| p.stop = Some(serde_json::json!(["\n"])),
+            |p| p.n = Some(2),
+            |p| p.response_format = Some(serde_json::json!({ "type": "json_object" })),
+        ];
+        for change in variants {
+            let mut changed = payload("hi", Some(0.0));
+            change(&mut changed);
+            assert_ne!(cache_key(&changed), base);
+        }
+
+        let mut same = payload("hi", Some(0.0));
+        same.user = Some("someone".to_string());
+        same.stream = Some(false);
+        assert_eq!(cache_key(&same), base);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entry() {
+        let mut cache = ResponseCache::with_capacity(2);
+        cache.insert(1, serde_json::json!(1));
+        cache.insert(2, serde_json::json!(2));
+        assert!(cache.get(1).is_some());
+        cache.insert(3, serde_json::json!(3));
+
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(3).is_some());
+    }
+}
@@ -0,0 +1,199 @@
+use axum::{
+    body::Body,
+    extract::{Multipart, State},
+    http::header,
+    response::Response,
+};
+use bytes::Bytes;
+
+use crate::{
+    approval::check_manual_approval,
+    errors::{ApiError, ApiResult},
+    rate_limit::check_rate_limit,
+    services::{azure, openai},
+    state::AppState,
+};
+
+#[derive(Debug, Default)]
+struct TranscriptionForm {
+    model: Option<String>,
+    file: Option<AudioFile>,
+    fields: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+struct AudioFile {
+    file_name: String,
+    content_type: Option<String>,
+    data: Bytes,
+}
+
+pub async fn transcriptions(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> ApiResult<Response> {
+    let provider = std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string());
+    check_provider(&provider)?;
+
+    let form = read_form(multipart).await?;
+    let model = form
+        .model
+        .clone()
+        .ok_or_else(|| ApiError::BadRequest("Missing required field: model".to_string()))?;
+    if form.file.is_none() {
+        return Err(ApiError::BadRequest("Missing required field: file".to_string()));
+    }
+
+    check_manual_approval(&state).await?;
+    check_rate_limit(&state, &model).await?;
+
+    let resp = if provider == "azure" {
+        let cfg = azure::load_azure_config(&model)
+            .ok_or_else(|| ApiError::BadRequest("Missing Azure OpenAI configuration".to_string()))?;
+        let deployment = cfg.deployment.clone();
+        azure::create_transcription(&state.client, &cfg, build_form(form, &deployment)?).await?
+    } else {
+        let model = model.trim_start_matches("openai:").to_string();
+        openai::create_transcription(&state.client, build_form(form, &model)?).await?
+    };
+
+    let status = resp.status();
+    let content_type = resp.headers().get(header::CONTENT_TYPE).cloned();
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|e| ApiError::Upstream(format!("Invalid transcription response: {e}")))?;
+
+    let mut builder = Response::builder().status(status);
+    if let Some(content_type) = content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    builder
+        .body(Body::from(body))
+        .map_err(|e| ApiError::Internal(format!("Failed to build response: {e}")))
+}
+
+fn check_provider(provider: &str) -> ApiResult<()> {
+    match provider {
+        "openai" | "azure" => Ok(()),
+        other => Err(ApiError::BadRequest(format!(
+            "Audio transcriptions are not supported by the {other} provider; set COPILOT_PROVIDER=openai or azure"
+        ))),
+    }
+}
+
+async fn read_form(mut multipart: Multipart) -> ApiResult<TranscriptionForm> {
+    let mut form = TranscriptionForm::default();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Invalid multipart body: {e}")))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "file" {
+            let file_name = field.file_name().unwrap_or("audio").to_string();
+            let content_type = field.content_type().map(str::to_string);
+            let data = field
+                .bytes()
+                .await
+                .map_err(|e| ApiError::BadRequest(format!("Invalid multipart body: {e}")))?;
+            form.file = Some(AudioFile { file_name, content_type, data });
+            continue;
+        }
+
+        let value = field
+            .text()
+            .await
+            .map_err(|e| ApiError::BadRequest(format!("Invalid multipart body: {e}")))?;
+        if name == "model" {
+            form.model = Some(value);
+        } else {
+            form.fields.push((name, value));
+        }
+    }
+    Ok(form)
+}
+
+fn build_form(form: TranscriptionForm, model: &str) -> ApiResult<reqwest::multipart::Form> {
+    let file = form
+        .file
+        .ok_or_else(|| ApiError::BadRequest("Missing required field: file".to_string()))?;
+    let mut part = reqwest::multipart::Part::stream(file.data).file_name(file.file_name);
+    if let Some(content_type) = file.content_type {
+        part = part
+            .mime_str(&content_type)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid file content type: {e}")))?;
+    }
+
+    let mut out = reqwest::multipart::Form::new()
+        .text("model", model.to_string())
+        .part("file", part);
+    for (name, value) in form.fields {
+        out = out.text(name, value);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_provider, read_form};
+    use crate::errors::ApiError;
+    use axum::{
+        body::Body,
+        extract::{FromRequest, Multipart},
+        http::Request,
+    };
+
+    fn multipart_request(body: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .header("content-type", "multipart/form-data; boundary=XBOUNDARY")
+            .body(Body::from(body.replace('\n', "\r\n")))
+            .unwrap()
+    }
+
+    #[test]
+    fn copilot_provider_is_rejected() {
+        assert!(matches!(check_provider("copilot"), Err(ApiError::BadRequest(_))));
+        assert!(check_provider("openai").is_ok());
+        assert!(check_provider("azure").is_ok());
+    }
+
+    #[tokio::test]
+    async fn read_form_collects_file_model_and_options() {
+        let body = "--XBOUNDARY
+Content-Disposition: form-data; name=\"model\"
+
+whisper-1
+--XBOUNDARY
+Content-Disposition: form-data; name=\"language\"
+
+en
+--XBOUNDARY
+Content-Disposition: form-data; name=\"response_format\"
+
+text
+--XBOUNDARY
+Content-Disposition: form-data; name=\"file\"; filename=\"clip.wav\"
+Content-Type: audio/wav
+
+RIFFDATA
+--XBOUNDARY--
+";
+        let multipart = Multipart::from_request(multipart_request(body), &()).await.unwrap();
+        let form = read_form(multipart).await.unwrap();
+
+        assert_eq!(form.model.as_deref(), Some("whisper-1"));
+        assert_eq!(
+            form.fields,
+            vec![
+                ("language".to_string(), "en".to_string()),
+                ("response_format".to_string(), "text".to_string()),
+            ]
+        );
+        let file = form.file.expect("file");
+        assert_eq!(file.file_name, "clip.wav");
+        assert_eq!(file.content_type.as_deref(), Some("audio/wav"));
+        assert_eq!(&file.data[..], b"RIFFDATA");
+    }
+}
@@ -1,14 +1,15 @@
 use axum::{extract::State, response::IntoResponse, Json};
 
 use crate::{
-    errors::ApiResult,
+    errors::{ApiError, ApiResult},
     services::github::{get_device_code, poll_access_token},
     state::AppState,
     token_store::{read_github_token, write_github_token},
 };
 
 pub async fn device_code(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
-    let device = get_device_code(&state.client).await?;
+    let config = state.config.read().await.clone();
+    let device = get_device_code(&state.client, &config).await?;
     Ok(Json(device))
 }
 
@@ -16,6 +17,10 @@ pub async fn device_code(State(state): State<AppState>) -> ApiResult<impl IntoRe
 pub struct PollRequest {
     pub device_code: String,
     pub interval: u64,
+    /// Seconds left on the device code, as returned by `/auth/device-code`;
+    /// without it polling continues until GitHub answers.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
 }
 
 pub async fn poll_token(
@@ -26,11 +31,15 @@ pub async fn poll_token(
         device_code: payload.device_code,
         user_code: "".to_string(),
         verification_uri: "".to_string(),
-        expires_in: 0,
+        expires_in: payload.expires_in.unwrap_or_default(),
         interval: payload.interval,
     };
+    let deadline = payload
+        .expires_in
+        .map(|secs| std::time::Instant::now() + std::time::Duration::from_secs(secs));
 
-    let token = poll_access_token(&state.client, &device).await?;
+    let config = state.config.read().await.clone();
+    let token = poll_access_token(&state.client, &config, &device, deadline).await?;
     write_github_token(&token).await?;
 
     {
@@ -41,7 +50,183 @@ pub async fn poll_token(
     Ok(Json(serde_json::json!({ "token": token })))
 }
 
+pub async fn start(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
+    let config = state.config.read().await.clone();
+    let device = get_device_code(&state.client, &config).await?;
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let expires_at = std::time::Instant::now() + std::time::Duration::from_secs(device.expires_in);
+
+    {
+        let mut config = state.config.write().await;
+        let now = std::time::Instant::now();
+        config.auth_sessions.retain(|_, (_, expires_at)| *expires_at > now);
+        config.auth_sessions.insert(session_id.clone(), (device.clone(), expires_at));
+    }
+
+    Ok(Json(serde_json::json!({
+        "session_id": session_id,
+        "user_code": device.user_code,
+        "verification_uri": device.verification_uri,
+        "expires_in": device.expires_in,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+pub struct CompleteRequest {
+    pub session_id: String,
+}
+
+pub async fn complete(
+    State(state): State<AppState>,
+    Json(payload): Json<CompleteRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let (device, expires_at) = {
+        let config = state.config.read().await;
+        config
+            .auth_sessions
+            .get(&payload.session_id)
+            .filter(|(_, expires_at)| *expires_at > std::time::Instant::now())
+            .cloned()
+    }
+    .ok_or_else(|| ApiError::NotFound(format!("Unknown or expired auth session: {}", payload.session_id)))?;
+
+    let config = state.config.read().await.clone();
+    let token = poll_access_token(&state.client, &config, &device, Some(expires_at)).await?;
+    write_github_token(&token).await?;
+
+    {
+        let mut config = state.config.write().await;
+        config.auth_sessions.remove(&payload.session_id);
+        config.github_token = Some(token.clone());
+    }
+
+    Ok(Json(serde_json::json!({ "token": token })))
+}
+
 pub async fn current_token(State(_state): State<AppState>) -> ApiResult<impl IntoResponse> {
     let token = read_github_token().await?;
     Ok(Json(serde_json::json!({ "token": token })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{complete, CompleteRequest};
+    use crate::state::{test_state, AppConfig, AppState};
+    use crate::services::github::DeviceCodeResponse;
+    use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A GitHub stand-in whose token endpoint answers `authorization_pending`
+    /// until `pending` polls have been made, then replies with a body that is
+    /// not JSON so the poll fails without a token ever being written.
+    async fn fake_github(pending: usize) -> (String, Arc<AtomicUsize>) {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+        let app = Router::new().route(
+            "/login/oauth/access_token",
+            post(move || {
+                let counter = counter.clone();
+                async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) < pending {
+                        r#"{"error":"authorization_pending"}"#
+                    } else {
+                        "not json"
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}"), polls)
+    }
+
+    async fn serve(state: AppState) -> String {
+        let app = crate::build_app(state, None, crate::DEFAULT_MAX_BODY_BYTES);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn poll_without_expires_in_keeps_polling() {
+        let (github, polls) = fake_github(1).await;
+        let config = AppConfig { github_base_url: github, ..Default::default() };
+        let server = serve(AppState::new(config, reqwest::Client::new(), None)).await;
+
+        let resp = reqwest::Client::new()
+            .post(format!("{server}/auth/poll"))
+            .json(&serde_json::json!({ "device_code": "dc", "interval": 0 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        assert!(resp.text().await.unwrap().contains("Invalid access token response"));
+        assert_eq!(polls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn complete_stops_when_the_stored_session_expires() {
+        let (github, polls) = fake_github(usize::MAX).await;
+        let config = AppConfig { github_base_url: github, ..Default::default() };
+        let state = AppState::new(config, reqwest::Client::new(), None);
+        let device = DeviceCodeResponse {
+            device_code: "dc".to_string(),
+            user_code: "ABCD-1234".to_string(),
+            verification_uri: "https://github.com/login/device".to_string(),
+            expires_in: 900,
+            interval: 0,
+        };
+        let expires_at = std::time::Instant::now() + std::time::Duration::from_millis(1500);
+        state.config.write().await.auth_sessions.insert("s1".to_string(), (device, expires_at));
+        let server = serve(state).await;
+
+        let started = std::time::Instant::now();
+        let resp = reqwest::Client::new()
+            .post(format!("{server}/auth/complete"))
+            .json(&serde_json::json!({ "session_id": "s1" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        assert!(resp.text().await.unwrap().contains("device code expired"));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(polls.load(Ordering::SeqCst) >= 2);
+    }
+
+
+    #[tokio::test]
+    async fn complete_rejects_unknown_session() {
+        let payload = CompleteRequest { session_id: "missing".to_string() };
+        let err = complete(State(test_state()), Json(payload)).await.err().expect("unknown session");
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn complete_rejects_expired_session() {
+        let state = test_state();
+        let device = DeviceCodeResponse {
+            device_code: "dc".to_string(),
+            user_code: "ABCD-1234".to_string(),
+            verification_uri: "https://github.com/login/device".to_string(),
+            expires_in: 900,
+            interval: 5,
+        };
+        state
+            .config
+            .write()
+            .await
+            .auth_sessions
+            .insert("old".to_string(), (device, std::time::Instant::now()));
+
+        let payload = CompleteRequest { session_id: "old".to_string() };
+        let err = complete(State(state), Json(payload)).await.err().expect("expired session");
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+}
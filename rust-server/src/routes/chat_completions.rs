@@ -1,4 +1,4 @@
-use axum::{extract::State, response::{IntoResponse, Response}, Json};
+use axum::{extract::State, http::HeaderMap, response::{IntoResponse, Response}, Json};
 use bytes::Bytes;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
@@ -6,17 +6,28 @@ use uuid::Uuid;
 
 use crate::{
     approval::check_manual_approval,
-    auth_flow::ensure_copilot_token,
+    auth_flow::{create_chat_completions_with_retry, create_responses_with_retry, ensure_copilot_token, send_with_token_retry},
     errors::{ApiError, ApiResult},
     hooks::types::HookInput,
     rate_limit::check_rate_limit,
-    routes::responses::{extract_instructions, messages_to_responses_input},
+    routes::{
+        fallback,
+        models::{cached_models, check_model_allowed},
+        responses::chat_to_responses_payload,
+        sampling,
+        streaming::{drain_sse_blocks, extract_sse_data, output_text_part},
+        tool_choice,
+    },
     services::{
         azure,
-        copilot::{create_chat_completions, create_responses, ChatCompletionsPayload, ResponsesPayload},
+        copilot::{
+            get_models, has_image_content, parse_initiator, parse_json, prepend_system_prefix, rate_limit_headers, request_timeout, system_prefix,
+            send_chat_completions, send_responses, ChatCompletionsPayload,
+        },
+        model_alias::resolve_model_alias,
         openai,
     },
-    state::AppState,
+    state::{AppState, ModelsResponse},
 };
 
 const RESPONSES_API_MODELS: &[&str] = &[
@@ -28,59 +39,64 @@ const RESPONSES_API_MODELS: &[&str] = &[
     "goldeneye",
 ];
 
-fn resolve_model_alias(model: &str) -> String {
-    let aliases = [
-        ("claude-opus-4.5", "gpt-5.2-codex"),
-        ("claude-opus-4", "gpt-5.2-codex"),
-        ("claude-4-opus", "gpt-5.2-codex"),
-        ("claude-3-opus", "gpt-5.2-codex"),
-        ("claude-3-opus-20240229", "gpt-5.2-codex"),
-        ("claude-sonnet-4", "gpt-5.1-codex"),
-        ("claude-4-sonnet", "gpt-5.1-codex"),
-        ("claude-3.5-sonnet", "gpt-5.1-codex"),
-        ("claude-3-5-sonnet-20241022", "gpt-5.1-codex"),
-        ("claude-3-sonnet", "gpt-5.1-codex"),
-        ("claude-3-sonnet-20240229", "gpt-5.1-codex"),
-        ("claude-haiku-3.5", "gpt-5-mini"),
-        ("claude-3.5-haiku", "gpt-5-mini"),
-        ("claude-3-haiku", "gpt-5-mini"),
-        ("claude-3-haiku-20240307", "gpt-5-mini"),
-        ("claude-2.1", "gpt-5.1"),
-        ("claude-2.0", "gpt-5.1"),
-        ("claude-instant-1.2", "gpt-5-mini"),
-        ("codex-5.2", "gpt-5.2-codex"),
-        ("codex-5.1", "gpt-5.1-codex"),
-        ("o3", "gpt-5.2-codex"),
-        ("o3-mini", "gpt-5-mini"),
-        ("o1", "gpt-5.1"),
-        ("o1-preview", "gpt-5.1"),
-        ("o1-mini", "gpt-5-mini"),
-    ];
-
-    if model.starts_with("claude-sonnet-4-") {
-        return "gpt-5.1-codex".to_string();
-    }
-    if model.starts_with("claude-opus-4-") || model.starts_with("claude-opus-4.5-") {
-        return "gpt-5.2-codex".to_string();
-    }
-    if model.starts_with("claude-haiku-") {
-        return "gpt-5-mini".to_string();
-    }
-
-    for (from, to) in aliases {
-        if model == from {
-            return to.to_string();
-        }
+fn validate_vision(model: &str, models: Option<&ModelsResponse>) -> ApiResult<()> {
+    let supports_vision = models
+        .and_then(|m| m.data.iter().find(|candidate| candidate.id == model))
+        .map(|candidate| candidate.capabilities.supports.vision == Some(true));
+    match supports_vision {
+        Some(false) => Err(ApiError::BadRequest("model does not support image input".to_string())),
+        _ => Ok(()),
     }
+}
 
-    model.to_string()
+fn validate_parallel_tool_calls(payload: &ChatCompletionsPayload, models: Option<&ModelsResponse>) -> ApiResult<()> {
+    if payload.parallel_tool_calls != Some(true) {
+        return Ok(());
+    }
+    let supported = models
+        .and_then(|m| m.data.iter().find(|candidate| candidate.id == payload.model))
+        .and_then(|candidate| candidate.capabilities.supports.parallel_tool_calls);
+    match supported {
+        Some(false) => Err(ApiError::BadRequest(format!(
+            "model {} does not support parallel_tool_calls",
+            payload.model
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Returns whether a streaming request for `model` has to be served from a
+/// non-streaming upstream call, or an error when the model cannot stream and
+/// COPILOT_AUTO_DOWNGRADE_STREAM is off.
+fn stream_downgrade(model: &str, models: Option<&ModelsResponse>, auto_downgrade: bool) -> ApiResult<bool> {
+    let supports_streaming = models
+        .and_then(|m| m.data.iter().find(|candidate| candidate.id == model))
+        .and_then(|candidate| candidate.capabilities.supports.streaming);
+    match supports_streaming {
+        Some(false) if auto_downgrade => Ok(true),
+        Some(false) => Err(ApiError::BadRequest(format!("model {model} does not support streaming"))),
+        _ => Ok(false),
+    }
+}
+
+fn auto_downgrade_stream() -> bool {
+    std::env::var("COPILOT_AUTO_DOWNGRADE_STREAM")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
 }
 
 fn requires_responses_api(model: &str) -> bool {
     RESPONSES_API_MODELS.contains(&model) || matches!(model, "codex-5.2" | "codex-5.1")
 }
 
-pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatCompletionsPayload>) -> ApiResult<Response> {
+pub async fn handle(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut payload): Json<ChatCompletionsPayload>,
+) -> ApiResult<Response> {
+    if payload.messages.is_empty() {
+        return Err(ApiError::BadRequest("messages must not be empty".to_string()));
+    }
     if let Some(hooks) = &state.hooks {
         let input = HookInput {
             hook_type: Some("PreToolUse".to_string()),
@@ -94,23 +110,29 @@ pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatC
             return Err(ApiError::BadRequest("Hook blocked request".to_string()));
         }
     }
+    let resolved_model = resolve_model_alias(&payload.model);
+    check_model_allowed(&resolved_model, state.config.read().await.allowed_models.as_deref())?;
     check_manual_approval(&state).await?;
-    check_rate_limit(&state).await?;
+    check_rate_limit(&state, &resolved_model).await?;
+    tool_choice::apply_none(&mut payload);
+    sampling::apply(&mut payload);
+    prepend_system_prefix(&mut payload.messages, system_prefix(&resolved_model));
     let provider = std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string());
 
     if provider == "azure" || payload.model.starts_with("azure:") {
-        if let Some(cfg) = azure::load_azure_config(&payload.model) {
+        let azure_model = azure::resolve_model(&payload.model);
+        if let Some(cfg) = azure::load_azure_config(&azure_model) {
             let mut azure_payload = payload.clone();
-            if azure_payload.model.starts_with("azure:") {
+            if azure_model.starts_with("azure:") {
                 azure_payload.model = cfg.deployment.clone();
             }
             let resp = azure::create_chat_completions(&state.client, &cfg, &serde_json::to_value(&azure_payload).unwrap())
                 .await?;
             if payload.stream.unwrap_or(false) {
-                let stream = crate::services::copilot::response_body_stream(resp);
+                let stream = crate::services::copilot::chat_completion_stream(resp);
                 return Ok(crate::routes::streaming::sse_response(stream));
             }
-            let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid Azure response: {e}")))?;
+            let json: serde_json::Value = parse_json(resp, "Invalid Azure response").await?;
             return Ok(Json(json).into_response());
         }
     }
@@ -119,6 +141,7 @@ pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatC
         if payload.model.starts_with("openai:") {
             payload.model = payload.model.trim_start_matches("openai:").to_string();
         }
+        payload.model = openai::resolve_model(&payload.model);
 
         if requires_responses_api(&payload.model) {
             return Err(ApiError::BadRequest("Model requires /v1/responses when using OpenAI provider".to_string()));
@@ -126,21 +149,25 @@ pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatC
 
         let resp = openai::create_chat_completions(&state.client, &serde_json::to_value(&payload).unwrap()).await?;
         if payload.stream.unwrap_or(false) {
-            let stream = crate::services::copilot::response_body_stream(resp);
+            let stream = crate::services::copilot::chat_completion_stream(resp);
             return Ok(crate::routes::streaming::sse_response(stream));
         }
-        let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid OpenAI response: {e}")))?;
+        let json: serde_json::Value = parse_json(resp, "Invalid OpenAI response").await?;
         return Ok(Json(json).into_response());
     }
 
     let token = ensure_copilot_token(&state).await?;
 
+    payload.normalize_token_limit();
+    payload.timeout = request_timeout(&headers);
+    payload.initiator = headers
+        .get("x-initiator")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_initiator)
+        .map(str::to_string);
     let original_model = payload.model.clone();
     payload.model = resolve_model_alias(&payload.model);
-
-    if requires_responses_api(&payload.model) {
-        return handle_responses_api(state, payload, original_model).await;
-    }
+    validate_parallel_tool_calls(&payload, state.config.read().await.models.as_ref())?;
 
     if state.config.read().await.show_token {
         if crate::tokenizer::use_precise_tokenizer() {
@@ -161,7 +188,51 @@ pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatC
         }
     }
 
-    let config = state.config.read().await.clone();
+    let aggregate = headers
+        .get("x-aggregate-stream")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    // Only plain non-streaming requests use the fallback chain; it runs after
+    // the same validation and defaults as a direct call.
+    let fallbacks = if payload.stream.unwrap_or(false) || aggregate {
+        Vec::new()
+    } else {
+        let config = state.config.read().await;
+        let fallbacks = config.fallback_models.get(&payload.model).cloned().unwrap_or_default();
+        fallback::allowed_fallbacks(fallbacks, config.allowed_models.as_deref())
+    };
+
+    if requires_responses_api(&payload.model) && fallbacks.is_empty() {
+        return handle_responses_api(state, payload, original_model).await;
+    }
+
+    let mut config = state.config.read().await.clone();
+
+    if has_image_content(&payload.messages) {
+        if config.models.is_none() {
+            let models = cached_models(&state, |config| {
+                let (client, token) = (&state.client, &token);
+                async move { get_models(client, &config, token).await }
+            })
+            .await?;
+            config.models = Some(models);
+        }
+        validate_vision(&payload.model, config.models.as_ref())?;
+    }
+
+    let downgrade_stream = payload.stream.unwrap_or(false)
+        && stream_downgrade(&payload.model, config.models.as_ref(), auto_downgrade_stream())?;
+    if downgrade_stream {
+        tracing::debug!("{} does not support streaming; replaying a non-streaming response", payload.model);
+        payload.stream = Some(false);
+    }
+
+    // A model that cannot stream is called without it; its reply is already whole.
+    let aggregate = aggregate && !downgrade_stream && !stream_downgrade(&payload.model, config.models.as_ref(), true)?;
+    if aggregate {
+        payload.stream = Some(true);
+    }
 
     if payload.max_tokens.is_none() {
         if let Some(models) = &config.models {
@@ -170,10 +241,59 @@ pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatC
             }
         }
     }
-    let resp = create_chat_completions(&state.client, &config, &token, &payload).await?;
+    if !fallbacks.is_empty() {
+        return handle_with_fallbacks(state, payload, fallbacks).await;
+    }
+    if let Some(key) = (crate::response_cache::enabled() && !downgrade_stream)
+        .then(|| crate::response_cache::cache_key(&payload))
+        .flatten()
+    {
+        let mut upstream_headers = HeaderMap::new();
+        let json = crate::response_cache::fetch_cached(&state.response_cache, key, || async {
+            let resp = create_chat_completions_with_retry(&state, &config, &token, &payload).await?;
+            upstream_headers = rate_limit_headers(resp.headers());
+            let json = parse_json::<serde_json::Value>(resp, "Invalid response").await?;
+            crate::metrics::record_usage(&payload.model, &json);
+            Ok(json)
+        })
+        .await?;
+        if let Some(hooks) = &state.hooks {
+            let input = HookInput {
+                hook_type: Some("PostToolUse".to_string()),
+                tool: Some("ChatCompletions".to_string()),
+                tool_input: Some(serde_json::to_value(&payload).unwrap_or_default()),
+                tool_output: Some(json.clone()),
+                session_id: None,
+            };
+            let _ = hooks.execute_event("PostToolUse", &input).await;
+        }
+        return Ok((upstream_headers, Json(json)).into_response());
+    }
 
-    if payload.stream.unwrap_or(false) {
-        let stream = crate::services::copilot::response_body_stream(resp);
+    let resp = create_chat_completions_with_retry(&state, &config, &token, &payload).await?;
+    let upstream_headers = rate_limit_headers(resp.headers());
+
+    if aggregate {
+        let json = aggregate_chat_stream(crate::services::copilot::chat_completion_stream(resp)).await?;
+        crate::metrics::record_usage(&payload.model, &json);
+        if let Some(hooks) = &state.hooks {
+            let input = HookInput {
+                hook_type: Some("PostToolUse".to_string()),
+                tool: Some("ChatCompletions".to_string()),
+                tool_input: Some(serde_json::to_value(&payload).unwrap_or_default()),
+                tool_output: Some(json.clone()),
+                session_id: None,
+            };
+            let _ = hooks.execute_event("PostToolUse", &input).await;
+        }
+        return Ok((upstream_headers, Json(json)).into_response());
+    }
+
+    if payload.stream.unwrap_or(false) || downgrade_stream {
+        let stream = crate::metrics::tap_usage(
+            crate::services::copilot::chat_completion_stream(resp),
+            payload.model.clone(),
+        );
         if let Some(hooks) = &state.hooks {
             let input = HookInput {
                 hook_type: Some("PostToolUse".to_string()),
@@ -184,10 +304,11 @@ pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatC
             };
             let _ = hooks.execute_event("PostToolUse", &input).await;
         }
-        return Ok(crate::routes::streaming::sse_response(stream));
+        return Ok((upstream_headers, crate::routes::streaming::sse_response(stream)).into_response());
     }
 
-    let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid response: {e}")))?;
+    let json: serde_json::Value = parse_json(resp, "Invalid response").await?;
+    crate::metrics::record_usage(&payload.model, &json);
     if let Some(hooks) = &state.hooks {
         let input = HookInput {
             hook_type: Some("PostToolUse".to_string()),
@@ -198,100 +319,175 @@ pub async fn handle(State(state): State<AppState>, Json(mut payload): Json<ChatC
         };
         let _ = hooks.execute_event("PostToolUse", &input).await;
     }
-    Ok(Json(json).into_response())
+    Ok((upstream_headers, Json(json)).into_response())
 }
 
-async fn handle_responses_api(
+async fn handle_with_fallbacks(
     state: AppState,
     payload: ChatCompletionsPayload,
-    _original_model: String,
+    fallbacks: Vec<String>,
 ) -> ApiResult<Response> {
     let token = ensure_copilot_token(&state).await?;
     let config = state.config.read().await.clone();
 
-    let instructions = extract_instructions(&payload.messages);
-    let input = messages_to_responses_input(&payload.messages);
-
-    if input.is_empty() {
-        return Err(ApiError::BadRequest("No valid input messages".to_string()));
-    }
-
-    let responses_payload = ResponsesPayload {
-        model: payload.model.clone(),
-        input: serde_json::to_value(input).unwrap_or(serde_json::json!([])),
-        instructions,
-        max_output_tokens: payload.max_tokens,
-        temperature: payload.temperature,
-        top_p: payload.top_p,
-        stream: payload.stream,
-        tools: payload.tools.as_ref().map(|tools| {
-            serde_json::Value::Array(
-                tools
-                    .iter()
-                    .map(|t| {
-                        serde_json::json!({
-                            "type": "function",
-                            "name": t.function.name,
-                            "description": t.function.description,
-                            "parameters": t.function.parameters,
-                        })
+    let chain: Vec<String> = std::iter::once(payload.model.clone()).chain(fallbacks).collect();
+    let fetch = || async {
+        let (model, resp) = fallback::send_with_fallbacks(&chain, |model| {
+            let attempt = ChatCompletionsPayload { model, ..payload.clone() };
+            let (state, config, token) = (&state, &config, &token);
+            async move {
+                if requires_responses_api(&attempt.model) {
+                    let responses_payload = chat_to_responses_payload(&attempt)?;
+                    send_with_token_retry(state, token, |token| {
+                        let responses_payload = &responses_payload;
+                        async move { send_responses(&state.client, config, &token, responses_payload).await }
+                    })
+                    .await
+                } else {
+                    send_with_token_retry(state, token, |token| {
+                        let attempt = &attempt;
+                        async move { send_chat_completions(&state.client, config, &token, attempt).await }
                     })
-                    .collect(),
-            )
-        }),
-        tool_choice: payload.tool_choice,
-        previous_response_id: None,
+                    .await
+                }
+            }
+        })
+        .await?;
+
+        let upstream_headers = rate_limit_headers(resp.headers());
+        let json: serde_json::Value = parse_json(resp, "Invalid response").await?;
+        crate::metrics::record_usage(&model, &json);
+        let json = if requires_responses_api(&model) { convert_responses_to_chat(json, model) } else { json };
+        ApiResult::Ok((upstream_headers, json))
     };
+    let mut upstream_headers = HeaderMap::new();
+    let json = match crate::response_cache::enabled().then(|| crate::response_cache::cache_key(&payload)).flatten() {
+        Some(key) => {
+            crate::response_cache::fetch_cached(&state.response_cache, key, || async {
+                let (headers, json) = fetch().await?;
+                upstream_headers = headers;
+                Ok(json)
+            })
+            .await?
+        }
+        None => {
+            let (headers, json) = fetch().await?;
+            upstream_headers = headers;
+            json
+        }
+    };
+    if let Some(hooks) = &state.hooks {
+        let input = HookInput {
+            hook_type: Some("PostToolUse".to_string()),
+            tool: Some("ChatCompletions".to_string()),
+            tool_input: Some(serde_json::to_value(&payload).unwrap_or_default()),
+            tool_output: Some(json.clone()),
+            session_id: None,
+        };
+        let _ = hooks.execute_event("PostToolUse", &input).await;
+    }
+    Ok((upstream_headers, Json(json)).into_response())
+}
 
-    let resp = create_responses(&state.client, &config, &token, &responses_payload).await?;
+async fn handle_responses_api(
+    state: AppState,
+    payload: ChatCompletionsPayload,
+    _original_model: String,
+) -> ApiResult<Response> {
+    let token = ensure_copilot_token(&state).await?;
+    let config = state.config.read().await.clone();
+
+    let responses_payload = chat_to_responses_payload(&payload)?;
+
+    let resp = create_responses_with_retry(&state, &config, &token, &responses_payload).await?;
+    let upstream_headers = rate_limit_headers(resp.headers());
 
     if payload.stream.unwrap_or(false) {
-        return Ok(stream_responses_as_chat_completion(resp, payload.model.clone()));
+        return Ok((upstream_headers, stream_responses_as_chat_completion(resp, payload.model.clone())).into_response());
     }
 
-    let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid responses payload: {e}")))?;
+    let json: serde_json::Value = parse_json(resp, "Invalid responses payload").await?;
+    crate::metrics::record_usage(&payload.model, &json);
     let converted = convert_responses_to_chat(json, payload.model);
-    Ok(Json(converted).into_response())
+    Ok((upstream_headers, Json(converted)).into_response())
 }
 
 fn stream_responses_as_chat_completion(resp: reqwest::Response, model: String) -> axum::response::Response {
-    let stream = resp.bytes_stream();
-    let out_stream = async_stream::stream! {
+    let upstream = crate::metrics::tap_usage(resp.bytes_stream(), model.clone());
+    let stream_reasoning = crate::routes::streaming::stream_reasoning();
+    crate::routes::streaming::sse_response(responses_to_chat_stream(upstream, model, stream_reasoning))
+}
+
+fn responses_to_chat_stream<S, E>(
+    stream: S,
+    model: String,
+    stream_reasoning: bool,
+) -> impl futures::Stream<Item = Result<Bytes, std::io::Error>>
+where
+    S: futures::Stream<Item = Result<Bytes, E>>,
+{
+    async_stream::stream! {
         let mut buffer = Vec::<u8>::new();
         let mut input_tokens: u64 = 0;
         let mut output_tokens: u64 = 0;
         let mut saw_completed = false;
+        let mut streamed_text = std::collections::HashSet::new();
         let chat_id = format!("chatcmpl-{}", Uuid::new_v4());
         futures::pin_mut!(stream);
 
         while let Some(chunk) = stream.next().await {
-            if let Ok(bytes) = chunk {
-                buffer.extend_from_slice(&bytes);
-                while let Some(pos) = find_double_newline(&buffer) {
-                    let line = buffer.drain(..pos + 2).collect::<Vec<u8>>();
-                    let text = String::from_utf8_lossy(&line);
-                    for raw in text.split("\n") {
-                        if let Some(data) = raw.strip_prefix("data: ") {
-                            if data.trim() == "[DONE]" {
-                                continue;
-                            }
-                            if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                                if let Some(delta) = json.get("delta") {
-                                    let chunk = build_chat_chunk(&chat_id, delta, json.get("response"));
-                                    let payload = format!("data: {}\n\n", serde_json::to_string(&chunk).unwrap());
-                                    yield Ok(Bytes::from(payload));
-                                }
-
-                                if json.get("type") == Some(&serde_json::Value::String("response.completed".to_string())) {
-                                    if let Some(usage) = json.get("response").and_then(|r| r.get("usage")) {
-                                        input_tokens = usage.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
-                                        output_tokens = usage.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
-                                    }
-                                    saw_completed = true;
-                                }
-                            }
-                        }
+            let Ok(bytes) = chunk else {
+                continue;
+            };
+            buffer.extend_from_slice(&bytes);
+            for block in drain_sse_blocks(&mut buffer) {
+                let Some(data) = extract_sse_data(&block) else {
+                    continue;
+                };
+                if data.trim() == "[DONE]" {
+                    continue;
+                }
+                let Ok(json) = serde_json::from_str::<serde_json::Value>(&data) else {
+                    continue;
+                };
+
+                let event_type = json.get("type").and_then(|t| t.as_str());
+                if event_type == Some("response.output_text.delta") {
+                    streamed_text.insert(output_text_part(&json));
+                }
+
+                if event_type == Some("response.output_text.done") {
+                    // Upstreams that skip deltas for short replies only send the full text here.
+                    let text = json
+                        .get("text")
+                        .and_then(|t| t.as_str())
+                        .filter(|t| !t.is_empty() && !streamed_text.contains(&output_text_part(&json)));
+                    if let Some(text) = text {
+                        let delta = serde_json::json!({ "content": text });
+                        let chunk = build_chat_chunk(&chat_id, &delta, json.get("response"));
+                        let payload = format!("data: {}\n\n", serde_json::to_string(&chunk).unwrap());
+                        yield Ok(Bytes::from(payload));
+                    }
+                } else if event_type == Some("response.reasoning_summary_text.delta") {
+                    let text = json.get("delta").and_then(|d| d.as_str()).filter(|_| stream_reasoning);
+                    if let Some(text) = text {
+                        let delta = serde_json::json!({ "reasoning_content": text });
+                        let chunk = build_chat_chunk(&chat_id, &delta, json.get("response"));
+                        let payload = format!("data: {}\n\n", serde_json::to_string(&chunk).unwrap());
+                        yield Ok(Bytes::from(payload));
                     }
+                } else if let Some(delta) = json.get("delta") {
+                    let chunk = build_chat_chunk(&chat_id, delta, json.get("response"));
+                    let payload = format!("data: {}\n\n", serde_json::to_string(&chunk).unwrap());
+                    yield Ok(Bytes::from(payload));
+                }
+
+                if event_type == Some("response.completed") {
+                    if let Some(usage) = json.get("response").and_then(|r| r.get("usage")) {
+                        input_tokens = usage.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+                        output_tokens = usage.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+                    }
+                    saw_completed = true;
                 }
             }
         }
@@ -316,17 +512,130 @@ fn stream_responses_as_chat_completion(resp: reqwest::Response, model: String) -
                     "total_tokens": input_tokens + output_tokens,
                 }
             });
-            let payload = format!("data: {}\n\n", final_chunk.to_string());
+            let payload = format!("data: {}\n\n", final_chunk);
             yield Ok(Bytes::from(payload));
             yield Ok::<Bytes, std::io::Error>(Bytes::from("data: [DONE]\n\n"));
         }
-    };
+    }
+}
 
-    crate::routes::streaming::sse_response(out_stream)
+/// Collects a chat completion stream into one `chat.completion`. A stream that
+/// breaks off or carries an error event fails instead of returning a partial
+/// completion.
+async fn aggregate_chat_stream<S, E>(stream: S) -> ApiResult<serde_json::Value>
+where
+    S: futures::Stream<Item = Result<Bytes, E>>,
+    E: std::fmt::Display,
+{
+    futures::pin_mut!(stream);
+    let mut buffer = Vec::<u8>::new();
+    let mut chunks = Vec::new();
+    while let Some(bytes) = stream.next().await {
+        let bytes = bytes.map_err(|e| ApiError::Upstream(format!("Upstream stream failed: {e}")))?;
+        buffer.extend_from_slice(&bytes);
+        for chunk in drain_sse_blocks(&mut buffer)
+            .iter()
+            .filter_map(|block| extract_sse_data(block))
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+        {
+            if let Some(error) = chunk.get("error") {
+                let message = error.get("message").and_then(|m| m.as_str()).map(str::to_string);
+                return Err(ApiError::Upstream(message.unwrap_or_else(|| error.to_string())));
+            }
+            chunks.push(chunk);
+        }
+    }
+    Ok(assemble_chat_completion(&chunks))
 }
 
-fn find_double_newline(buf: &[u8]) -> Option<usize> {
-    buf.windows(2).position(|w| w == b"\n\n")
+#[derive(Default)]
+struct AggregatedChoice {
+    role: Option<String>,
+    content: String,
+    tool_calls: std::collections::BTreeMap<u64, serde_json::Value>,
+    finish_reason: Option<serde_json::Value>,
+}
+
+fn assemble_chat_completion(chunks: &[serde_json::Value]) -> serde_json::Value {
+    let mut choices = std::collections::BTreeMap::<u64, AggregatedChoice>::new();
+    let mut usage = serde_json::Value::Null;
+
+    for chunk in chunks {
+        if let Some(u) = chunk.get("usage").filter(|u| !u.is_null()) {
+            usage = u.clone();
+        }
+        for choice in chunk.get("choices").and_then(|c| c.as_array()).into_iter().flatten() {
+            let index = choice.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+            let entry = choices.entry(index).or_default();
+            if let Some(reason) = choice.get("finish_reason").filter(|r| !r.is_null()) {
+                entry.finish_reason = Some(reason.clone());
+            }
+            let Some(delta) = choice.get("delta") else {
+                continue;
+            };
+            if let Some(role) = delta.get("role").and_then(|v| v.as_str()) {
+                entry.role = Some(role.to_string());
+            }
+            if let Some(content) = delta.get("content").and_then(|v| v.as_str()) {
+                entry.content.push_str(content);
+            }
+            for call in delta.get("tool_calls").and_then(|v| v.as_array()).into_iter().flatten() {
+                let call_index = call.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                let slot = entry.tool_calls.entry(call_index).or_insert_with(|| {
+                    serde_json::json!({
+                        "id": "",
+                        "type": "function",
+                        "function": { "name": "", "arguments": "" }
+                    })
+                });
+                if let Some(id) = call.get("id").and_then(|v| v.as_str()) {
+                    slot["id"] = serde_json::Value::String(id.to_string());
+                }
+                if let Some(name) = call.pointer("/function/name").and_then(|v| v.as_str()) {
+                    slot["function"]["name"] = serde_json::Value::String(name.to_string());
+                }
+                if let Some(args) = call.pointer("/function/arguments").and_then(|v| v.as_str()) {
+                    let joined = format!("{}{}", slot["function"]["arguments"].as_str().unwrap_or(""), args);
+                    slot["function"]["arguments"] = serde_json::Value::String(joined);
+                }
+            }
+        }
+    }
+
+    let choices: Vec<serde_json::Value> = choices
+        .into_iter()
+        .map(|(index, choice)| {
+            let mut message = serde_json::json!({
+                "role": choice.role.unwrap_or_else(|| "assistant".to_string()),
+                "content": if choice.content.is_empty() && !choice.tool_calls.is_empty() {
+                    serde_json::Value::Null
+                } else {
+                    serde_json::Value::String(choice.content)
+                },
+            });
+            if !choice.tool_calls.is_empty() {
+                message["tool_calls"] = serde_json::Value::Array(choice.tool_calls.into_values().collect());
+            }
+            serde_json::json!({
+                "index": index,
+                "message": message,
+                "finish_reason": choice.finish_reason.unwrap_or(serde_json::Value::Null),
+            })
+        })
+        .collect();
+
+    let first = chunks.first();
+    let mut out = serde_json::json!({
+        "id": first.and_then(|c| c.get("id")).cloned().unwrap_or_default(),
+        "object": "chat.completion",
+        "created": first.and_then(|c| c.get("created")).cloned().unwrap_or(serde_json::json!(0)),
+        "model": first.and_then(|c| c.get("model")).cloned().unwrap_or_default(),
+        "choices": choices,
+    });
+    if !usage.is_null() {
+        out["usage"] = usage;
+    }
+    out
 }
 
 #[derive(Serialize, Deserialize)]
@@ -373,19 +682,46 @@ fn build_chat_chunk(id: &str, delta: &serde_json::Value, response: Option<&serde
 }
 
 fn convert_responses_to_chat(response: serde_json::Value, model: String) -> serde_json::Value {
-    let output_text = response
-        .get("output")
-        .and_then(|o| o.as_array())
-        .and_then(|arr| {
-            arr.iter()
-                .find(|x| x.get("type") == Some(&serde_json::Value::String("message".to_string())))
-        })
-        .and_then(|msg| msg.get("content"))
-        .and_then(|c| c.as_array())
-        .and_then(|arr| arr.iter().find(|x| x.get("type") == Some(&serde_json::Value::String("output_text".to_string()))))
-        .and_then(|t| t.get("text"))
-        .and_then(|t| t.as_str())
-        .unwrap_or("");
+    let mut output_text = String::new();
+    let mut tool_calls = Vec::new();
+    for item in response.get("output").and_then(|o| o.as_array()).into_iter().flatten() {
+        match item.get("type").and_then(|t| t.as_str()) {
+            Some("message") => {
+                let texts = item
+                    .get("content")
+                    .and_then(|c| c.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some("output_text"))
+                    .filter_map(|part| part.get("text").and_then(|t| t.as_str()));
+                for text in texts {
+                    output_text.push_str(text);
+                }
+            }
+            Some("function_call") => tool_calls.push(serde_json::json!({
+                "id": item.get("call_id").or_else(|| item.get("id")).cloned().unwrap_or(serde_json::Value::Null),
+                "type": "function",
+                "function": {
+                    "name": item.get("name").cloned().unwrap_or(serde_json::Value::Null),
+                    "arguments": item.get("arguments").and_then(|a| a.as_str()).unwrap_or("{}"),
+                },
+            })),
+            _ => {}
+        }
+    }
+
+    let mut message = serde_json::json!({
+        "role": "assistant",
+        "content": if output_text.is_empty() && !tool_calls.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::Value::String(output_text)
+        },
+    });
+    let finish_reason = if tool_calls.is_empty() { "stop" } else { "tool_calls" };
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = serde_json::Value::Array(tool_calls);
+    }
 
     serde_json::json!({
         "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
@@ -398,12 +734,9 @@ fn convert_responses_to_chat(response: serde_json::Value, model: String) -> serd
         "choices": [
             {
                 "index": 0,
-                "message": {
-                    "role": "assistant",
-                    "content": output_text,
-                },
+                "message": message,
                 "logprobs": null,
-                "finish_reason": "stop",
+                "finish_reason": finish_reason,
             }
         ],
         "usage": response.get("usage").cloned().unwrap_or(serde_json::json!({})),
@@ -412,14 +745,11 @@ fn convert_responses_to_chat(response: serde_json::Value, model: String) -> serd
 
 #[cfg(test)]
 mod tests {
-    use super::{build_chat_chunk, convert_responses_to_chat, find_double_newline, resolve_model_alias, requires_responses_api};
-
-    #[test]
-    fn resolves_claude_aliases() {
-        assert_eq!(resolve_model_alias("claude-opus-4.5"), "gpt-5.2-codex");
-        assert_eq!(resolve_model_alias("claude-3.5-haiku"), "gpt-5-mini");
-        assert_eq!(resolve_model_alias("claude-2.1"), "gpt-5.1");
-    }
+    use super::{aggregate_chat_stream, assemble_chat_completion, build_chat_chunk, convert_responses_to_chat, handle, stream_downgrade, validate_parallel_tool_calls, validate_vision, requires_responses_api, responses_to_chat_stream};
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use crate::services::copilot::ChatCompletionsPayload;
+    use axum::{extract::State, http::{HeaderMap, StatusCode}, Json};
 
     #[test]
     fn responses_api_required_models() {
@@ -453,9 +783,33 @@ mod tests {
     }
 
     #[test]
-    fn finds_double_newline_in_buffer() {
-        let buf = b"data: {\"a\":1}\n\nrest";
-        assert_eq!(find_double_newline(buf), Some(13));
+    fn converts_responses_function_calls_to_tool_calls() {
+        let response = serde_json::json!({
+            "output": [
+                { "type": "reasoning", "summary": [] },
+                {
+                    "type": "function_call",
+                    "id": "fc_1",
+                    "call_id": "call_abc",
+                    "name": "get_weather",
+                    "arguments": "{\"city\":\"Paris\"}"
+                }
+            ],
+            "usage": { "input_tokens": 10, "output_tokens": 5, "total_tokens": 15 }
+        });
+
+        let converted = convert_responses_to_chat(response, "gpt-5.2-codex".to_string());
+        let choice = &converted["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(choice["message"]["content"], serde_json::Value::Null);
+        assert_eq!(
+            choice["message"]["tool_calls"],
+            serde_json::json!([{
+                "id": "call_abc",
+                "type": "function",
+                "function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" }
+            }])
+        );
     }
 
     #[test]
@@ -466,4 +820,274 @@ mod tests {
         assert_eq!(chunk.model, "gpt-5.2-codex");
         assert_eq!(chunk.choices.len(), 1);
     }
+
+    #[tokio::test]
+    async fn rejects_empty_messages_without_upstream_call() {
+        let state = crate::state::test_state();
+        let payload = ChatCompletionsPayload {
+            model: "gpt-4o".to_string(),
+            ..Default::default()
+        };
+
+        let err = handle(State(state), HeaderMap::new(), Json(payload)).await.expect_err("empty messages rejected");
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "messages must not be empty");
+    }
+
+    #[tokio::test]
+    async fn aliases_share_the_resolved_model_rate_limit() {
+        let config = crate::state::AppConfig {
+            rate_limit_seconds: None,
+            rate_limit_wait: false,
+            rate_limit_per_model: [("gpt-5.2-codex".to_string(), (30, Some(std::time::Instant::now())))].into(),
+            ..Default::default()
+        };
+        let state = crate::state::AppState::new(config, reqwest::Client::new(), None);
+        let payload: ChatCompletionsPayload = serde_json::from_value(serde_json::json!({
+            "model": "claude-opus-4.5",
+            "messages": [{ "role": "user", "content": "hi" }]
+        }))
+        .unwrap();
+
+        let err = handle(State(state), HeaderMap::new(), Json(payload)).await.expect_err("rate limited");
+        assert!(err.to_string().starts_with("Rate limit exceeded"));
+    }
+
+    #[tokio::test]
+    async fn fallback_chain_still_validates_vision() {
+        let mut text_only = crate::routes::models::default_model();
+        text_only.id = "gpt-4o".to_string();
+        text_only.capabilities.supports.vision = Some(false);
+        let config = crate::state::AppConfig {
+            copilot_token: Some("token".to_string()),
+            models: Some(crate::state::ModelsResponse { data: vec![text_only], object: "list".to_string() }),
+            fallback_models: [("gpt-4o".to_string(), vec!["gpt-4.1".to_string()])].into(),
+            ..Default::default()
+        };
+        let state = crate::state::AppState::new(config, reqwest::Client::new(), None);
+        let payload: ChatCompletionsPayload = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{
+                "role": "user",
+                "content": [{ "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } }]
+            }]
+        }))
+        .unwrap();
+
+        let err = handle(State(state), HeaderMap::new(), Json(payload)).await.expect_err("image rejected");
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "model does not support image input");
+    }
+
+    #[tokio::test]
+    async fn responses_stream_handles_event_split_across_chunks() {
+        let chunks = vec![
+            Ok::<Bytes, std::io::Error>(Bytes::from("data: {\"type\":\"response.output_text.delta\",\"del")),
+            Ok(Bytes::from("ta\":{\"content\":\"hi\"}}\n")),
+            Ok(Bytes::from("\ndata: {\"type\":\"response.completed\",\"response\":{\"usage\":{\"input_tokens\":3,\"output_tokens\":1}}}\n\n")),
+        ];
+
+        let out: Vec<String> = responses_to_chat_stream(futures::stream::iter(chunks), "gpt-5.2-codex".to_string(), false)
+            .map(|c| String::from_utf8_lossy(&c.expect("chunk")).to_string())
+            .collect()
+            .await;
+
+        assert_eq!(out.len(), 3);
+        let first: serde_json::Value = serde_json::from_str(out[0].trim().trim_start_matches("data: ")).unwrap();
+        assert_eq!(first["choices"][0]["delta"]["content"], "hi");
+        let last: serde_json::Value = serde_json::from_str(out[1].trim().trim_start_matches("data: ")).unwrap();
+        assert_eq!(last["usage"]["total_tokens"], 4);
+        assert_eq!(out[2], "data: [DONE]\n\n");
+    }
+
+    #[tokio::test]
+    async fn responses_stream_forwards_reasoning_summary_when_enabled() {
+        let events = "data: {\"type\":\"response.reasoning_summary_text.delta\",\"delta\":\"thinking\"}\n\n\
+            data: {\"type\":\"response.output_text.delta\",\"delta\":{\"content\":\"hi\"}}\n\n";
+        let collect = |stream_reasoning| {
+            let chunks = vec![Ok::<Bytes, std::io::Error>(Bytes::from(events))];
+            responses_to_chat_stream(futures::stream::iter(chunks), "gpt-5.2-codex".to_string(), stream_reasoning)
+                .map(|c| serde_json::from_str::<serde_json::Value>(String::from_utf8_lossy(&c.unwrap()).trim().trim_start_matches("data: ")).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let out = collect(true).await;
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0]["choices"][0]["delta"], serde_json::json!({ "reasoning_content": "thinking" }));
+        assert_eq!(out[1]["choices"][0]["delta"]["content"], "hi");
+
+        let out = collect(false).await;
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0]["choices"][0]["delta"]["content"], "hi");
+    }
+
+    #[tokio::test]
+    async fn responses_stream_emits_output_text_done_when_no_deltas_arrived() {
+        let collect = |events: &'static str| {
+            let chunks = vec![Ok::<Bytes, std::io::Error>(Bytes::from(events))];
+            responses_to_chat_stream(futures::stream::iter(chunks), "gpt-5.2-codex".to_string(), false)
+                .map(|c| String::from_utf8_lossy(&c.unwrap()).to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let out = collect(
+            "data: {\"type\":\"response.output_text.done\",\"item_id\":\"msg_1\",\"content_index\":0,\"text\":\"Hi there\"}\n\n\
+            data: {\"type\":\"response.completed\",\"response\":{\"usage\":{\"input_tokens\":3,\"output_tokens\":2}}}\n\n",
+        )
+        .await;
+        assert_eq!(out.len(), 3);
+        let first: serde_json::Value = serde_json::from_str(out[0].trim().trim_start_matches("data: ")).unwrap();
+        assert_eq!(first["choices"][0]["delta"], serde_json::json!({ "content": "Hi there" }));
+
+        let out = collect(
+            "data: {\"type\":\"response.output_text.delta\",\"item_id\":\"msg_1\",\"content_index\":0,\"delta\":{\"content\":\"Hi there\"}}\n\n\
+            data: {\"type\":\"response.output_text.done\",\"item_id\":\"msg_1\",\"content_index\":0,\"text\":\"Hi there\"}\n\n",
+        )
+        .await;
+        assert_eq!(out.len(), 1);
+    }
+
+    #[test]
+    fn assembles_chat_completion_from_chunks() {
+        let chunks = vec![
+            serde_json::json!({"id": "chatcmpl-9", "created": 7, "model": "gpt-4o", "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Hel"}}]}),
+            serde_json::json!({"id": "chatcmpl-9", "choices": [{"index": 0, "delta": {"content": "lo"}}]}),
+            serde_json::json!({"id": "chatcmpl-9", "choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "lookup", "arguments": "{\"q\":"}}]}}]}),
+            serde_json::json!({"id": "chatcmpl-9", "choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "\"rust\"}"}}]}}]}),
+            serde_json::json!({"id": "chatcmpl-9", "choices": [{"index": 0, "delta": {}, "finish_reason": "tool_calls"}], "usage": {"prompt_tokens": 5, "completion_tokens": 3, "total_tokens": 8}}),
+        ];
+
+        let out = assemble_chat_completion(&chunks);
+        assert_eq!(out["object"], "chat.completion");
+        assert_eq!(out["id"], "chatcmpl-9");
+        assert_eq!(out["model"], "gpt-4o");
+        let choice = &out["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(choice["message"]["role"], "assistant");
+        assert_eq!(choice["message"]["content"], "Hello");
+        assert_eq!(choice["message"]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(choice["message"]["tool_calls"][0]["function"]["name"], "lookup");
+        assert_eq!(choice["message"]["tool_calls"][0]["function"]["arguments"], "{\"q\":\"rust\"}");
+        assert_eq!(out["usage"]["total_tokens"], 8);
+    }
+
+    #[tokio::test]
+    async fn aggregates_a_json_reply_from_upstream() {
+        let upstream = reqwest::Response::from(
+            axum::http::Response::builder()
+                .header("content-type", "application/json")
+                .body(r#"{"id":"chatcmpl-1","model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"whole"},"finish_reason":"stop"}]}"#)
+                .unwrap(),
+        );
+        let out = aggregate_chat_stream(crate::services::copilot::chat_completion_stream(upstream)).await.unwrap();
+        assert_eq!(out["choices"][0]["message"]["content"], "whole");
+        assert_eq!(out["choices"][0]["finish_reason"], "stop");
+    }
+
+    #[tokio::test]
+    async fn aggregation_fails_on_a_broken_stream() {
+        let chunks = vec![
+            Ok(Bytes::from("data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"par\"}}]}\n\n")),
+            Err(std::io::Error::other("connection reset")),
+        ];
+        let err = aggregate_chat_stream(futures::stream::iter(chunks)).await.expect_err("broken stream");
+        assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(err.to_string(), "Upstream stream failed: connection reset");
+
+        let chunks = vec![Ok::<_, std::io::Error>(Bytes::from("data: {\"error\":{\"message\":\"overloaded\"}}\n\n"))];
+        let err = aggregate_chat_stream(futures::stream::iter(chunks)).await.expect_err("error event");
+        assert_eq!(err.to_string(), "overloaded");
+    }
+
+    #[test]
+    fn rejects_image_input_for_models_without_vision() {
+        let mut text_only = crate::routes::models::default_model();
+        text_only.id = "o3-mini".to_string();
+        let mut vision = crate::routes::models::default_model();
+        vision.id = "gpt-4o".to_string();
+        vision.capabilities.supports.vision = Some(true);
+        let models = crate::state::ModelsResponse {
+            data: vec![text_only, vision],
+            object: "list".to_string(),
+        };
+
+        let err = validate_vision("o3-mini", Some(&models)).expect_err("text-only model rejected");
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "model does not support image input");
+        assert!(validate_vision("gpt-4o", Some(&models)).is_ok());
+        assert!(validate_vision("unlisted-model", Some(&models)).is_ok());
+    }
+
+    #[test]
+    fn rejects_parallel_tool_calls_for_unsupported_models() {
+        let mut sequential = crate::routes::models::default_model();
+        sequential.id = "o1".to_string();
+        sequential.capabilities.supports.parallel_tool_calls = Some(false);
+        let mut parallel = crate::routes::models::default_model();
+        parallel.id = "gpt-4o".to_string();
+        parallel.capabilities.supports.parallel_tool_calls = Some(true);
+        let models = crate::state::ModelsResponse { data: vec![sequential, parallel], object: "list".to_string() };
+
+        let payload: ChatCompletionsPayload = serde_json::from_value(serde_json::json!({
+            "model": "o1",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "parallel_tool_calls": true
+        }))
+        .unwrap();
+        let err = validate_parallel_tool_calls(&payload, Some(&models)).expect_err("parallel calls rejected");
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "model o1 does not support parallel_tool_calls");
+
+        let disabled = ChatCompletionsPayload { parallel_tool_calls: Some(false), ..payload.clone() };
+        assert!(validate_parallel_tool_calls(&disabled, Some(&models)).is_ok());
+        let supported = ChatCompletionsPayload { model: "gpt-4o".to_string(), ..payload.clone() };
+        assert!(validate_parallel_tool_calls(&supported, Some(&models)).is_ok());
+        assert!(validate_parallel_tool_calls(&payload, None).is_ok());
+    }
+
+    fn models_without_streaming() -> crate::state::ModelsResponse {
+        let mut no_stream = crate::routes::models::default_model();
+        no_stream.id = "o1-pro".to_string();
+        no_stream.capabilities.supports.streaming = Some(false);
+        let mut streaming = crate::routes::models::default_model();
+        streaming.id = "gpt-4o".to_string();
+        crate::state::ModelsResponse { data: vec![no_stream, streaming], object: "list".to_string() }
+    }
+
+    #[test]
+    fn rejects_stream_for_models_without_streaming() {
+        let models = models_without_streaming();
+        let err = stream_downgrade("o1-pro", Some(&models), false).expect_err("streaming rejected");
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "model o1-pro does not support streaming");
+        assert!(!stream_downgrade("gpt-4o", Some(&models), false).unwrap());
+        assert!(!stream_downgrade("unlisted-model", None, false).unwrap());
+    }
+
+    #[tokio::test]
+    async fn downgraded_stream_replays_single_chunk() {
+        let models = models_without_streaming();
+        assert!(stream_downgrade("o1-pro", Some(&models), true).unwrap());
+        assert!(!stream_downgrade("gpt-4o", Some(&models), true).unwrap());
+
+        let body = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "o1-pro",
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": "done" }, "finish_reason": "stop" }]
+        });
+        let resp = reqwest::Response::from(
+            axum::http::Response::builder()
+                .header("content-type", "application/json")
+                .body(body.to_string())
+                .unwrap(),
+        );
+        let chunks: Vec<Bytes> = crate::services::copilot::chat_completion_stream(resp)
+            .map(|c| c.expect("chunk"))
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 2);
+        let first = String::from_utf8(chunks[0].to_vec()).unwrap();
+        assert!(first.contains("\"content\":\"done\""));
+        assert_eq!(chunks[1], Bytes::from("data: [DONE]\n\n"));
+    }
 }
@@ -0,0 +1,209 @@
+use axum::{extract::State, response::{IntoResponse, Response}, Json};
+use serde::Deserialize;
+
+use crate::{
+    approval::check_manual_approval,
+    auth_flow::{create_chat_completions_with_retry, ensure_copilot_token},
+    errors::{ApiError, ApiResult},
+    rate_limit::check_rate_limit,
+    routes::models::check_model_allowed,
+    services::{
+        copilot::{parse_json, rate_limit_headers, ChatCompletionsPayload, Message},
+        model_alias::resolve_model_alias,
+    },
+    state::AppState,
+};
+
+/// Legacy `/v1/completions` request. Only the fields that map onto a chat
+/// completion are honoured.
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompletionsPayload {
+    pub model: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub echo: bool,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    #[serde(default)]
+    pub stop: Option<serde_json::Value>,
+    #[serde(default)]
+    pub stream: Option<bool>,
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+struct FimTokens {
+    prefix: &'static str,
+    suffix: &'static str,
+    middle: &'static str,
+}
+
+/// Models trained with fill-in-the-middle sentinel tokens. Anything else gets
+/// an instruction-style prompt instead.
+fn fim_tokens(model: &str) -> Option<FimTokens> {
+    let model = model.to_ascii_lowercase();
+    if model.contains("starcoder") || model.contains("santacoder") {
+        Some(FimTokens { prefix: "<fim_prefix>", suffix: "<fim_suffix>", middle: "<fim_middle>" })
+    } else if model.contains("codellama") || model.contains("code-llama") {
+        Some(FimTokens { prefix: "<PRE> ", suffix: " <SUF>", middle: " <MID>" })
+    } else if model.contains("codestral") {
+        Some(FimTokens { prefix: "[PREFIX]", suffix: "[SUFFIX]", middle: "" })
+    } else {
+        None
+    }
+}
+
+fn fim_prompt(model: &str, prompt: &str, suffix: &str) -> String {
+    match fim_tokens(model) {
+        // Codestral puts the suffix first.
+        Some(tokens) if tokens.middle.is_empty() => format!("{}{}{}{}", tokens.suffix, suffix, tokens.prefix, prompt),
+        Some(tokens) => format!("{}{}{}{}{}", tokens.prefix, prompt, tokens.suffix, suffix, tokens.middle),
+        None => format!(
+            "Fill in the code that belongs between <prefix> and <suffix>. Reply with only the missing code, without fences or explanation.\n<prefix>{}</prefix>\n<suffix>{}</suffix>",
+            prompt, suffix
+        ),
+    }
+}
+
+fn fim_model(requested: &str) -> String {
+    std::env::var("COPILOT_FIM_MODEL").ok().filter(|m| !m.trim().is_empty()).unwrap_or_else(|| requested.to_string())
+}
+
+fn to_chat_payload(payload: &CompletionsPayload) -> ChatCompletionsPayload {
+    let (model, content) = match payload.suffix.as_deref().filter(|s| !s.is_empty()) {
+        Some(suffix) => {
+            let model = fim_model(&payload.model);
+            let content = fim_prompt(&model, &payload.prompt, suffix);
+            (model, content)
+        }
+        None => (payload.model.clone(), payload.prompt.clone()),
+    };
+    ChatCompletionsPayload {
+        model,
+        messages: vec![Message {
+            role: "user".to_string(),
+            content: serde_json::Value::String(content),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_text: None,
+            reasoning_opaque: None,
+        }],
+        max_tokens: payload.max_tokens,
+        temperature: payload.temperature,
+        top_p: payload.top_p,
+        stop: payload.stop.clone(),
+        user: payload.user.clone(),
+        ..Default::default()
+    }
+}
+
+fn to_text_completion(chat: &serde_json::Value, payload: &CompletionsPayload) -> serde_json::Value {
+    let choices: Vec<serde_json::Value> = chat
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .map(|choices| {
+            choices
+                .iter()
+                .map(|choice| {
+                    let text = choice.pointer("/message/content").and_then(|c| c.as_str()).unwrap_or_default();
+                    let text = if payload.echo { format!("{}{}", payload.prompt, text) } else { text.to_string() };
+                    serde_json::json!({
+                        "text": text,
+                        "index": choice.get("index").cloned().unwrap_or(serde_json::json!(0)),
+                        "logprobs": null,
+                        "finish_reason": choice.get("finish_reason").cloned().unwrap_or(serde_json::Value::Null),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut out = serde_json::json!({
+        "id": chat.get("id").cloned().unwrap_or(serde_json::Value::Null),
+        "object": "text_completion",
+        "created": chat.get("created").cloned().unwrap_or(serde_json::json!(0)),
+        "model": chat.get("model").cloned().unwrap_or_else(|| serde_json::json!(payload.model)),
+        "choices": choices,
+    });
+    if let Some(usage) = chat.get("usage") {
+        out["usage"] = usage.clone();
+    }
+    out
+}
+
+pub async fn handle(State(state): State<AppState>, Json(payload): Json<CompletionsPayload>) -> ApiResult<Response> {
+    if payload.stream.unwrap_or(false) {
+        return Err(ApiError::BadRequest("stream is not supported on /v1/completions".to_string()));
+    }
+    let mut chat_payload = to_chat_payload(&payload);
+    chat_payload.model = resolve_model_alias(&chat_payload.model);
+    check_model_allowed(&chat_payload.model, state.config.read().await.allowed_models.as_deref())?;
+    check_manual_approval(&state).await?;
+    check_rate_limit(&state, &chat_payload.model).await?;
+
+    let token = ensure_copilot_token(&state).await?;
+    let config = state.config.read().await.clone();
+    let resp = create_chat_completions_with_retry(&state, &config, &token, &chat_payload).await?;
+    let upstream_headers = rate_limit_headers(resp.headers());
+    let json: serde_json::Value = parse_json(resp, "Invalid response").await?;
+    crate::metrics::record_usage(&chat_payload.model, &json);
+    Ok((upstream_headers, Json(to_text_completion(&json, &payload))).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fim_prompt, to_chat_payload, to_text_completion, CompletionsPayload};
+
+    fn payload(model: &str, suffix: Option<&str>) -> CompletionsPayload {
+        serde_json::from_value(serde_json::json!({
+            "model": model,
+            "prompt": "def add(a, b):\n    ",
+            "suffix": suffix,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn suffix_builds_combined_prompt() {
+        let chat = to_chat_payload(&payload("gpt-4o", Some("\n\nprint(add(1, 2))")));
+        assert_eq!(chat.model, "gpt-4o");
+        assert_eq!(
+            chat.messages[0].content,
+            "Fill in the code that belongs between <prefix> and <suffix>. Reply with only the missing code, without fences or explanation.\n<prefix>def add(a, b):\n    </prefix>\n<suffix>\n\nprint(add(1, 2))</suffix>"
+        );
+
+        let plain = to_chat_payload(&payload("gpt-4o", None));
+        assert_eq!(plain.messages[0].content, "def add(a, b):\n    ");
+    }
+
+    #[test]
+    fn fim_models_use_sentinel_tokens() {
+        assert_eq!(fim_prompt("starcoder2-15b", "a", "c"), "<fim_prefix>a<fim_suffix>c<fim_middle>");
+        assert_eq!(fim_prompt("codellama-13b", "a", "c"), "<PRE> a <SUF>c <MID>");
+        assert_eq!(fim_prompt("codestral-latest", "a", "c"), "[SUFFIX]c[PREFIX]a");
+    }
+
+    #[test]
+    fn echo_prepends_prompt() {
+        let mut request = payload("gpt-4o", None);
+        request.echo = true;
+        let chat = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o",
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": "return a + b" }, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 5, "completion_tokens": 4, "total_tokens": 9 }
+        });
+        let out = to_text_completion(&chat, &request);
+        assert_eq!(out["object"], "text_completion");
+        assert_eq!(out["choices"][0]["text"], "def add(a, b):\n    return a + b");
+        assert_eq!(out["choices"][0]["finish_reason"], "stop");
+        assert_eq!(out["usage"]["total_tokens"], 9);
+    }
+}
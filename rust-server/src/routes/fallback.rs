@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use axum::http::StatusCode;
+
+use crate::errors::{ApiError, ApiResult};
+use crate::routes::models::check_model_allowed;
+
+/// COPILOT_FALLBACK_MODELS: a JSON object mapping a model to the models tried
+/// after it, in order.
+pub(crate) fn fallback_models() -> HashMap<String, Vec<String>> {
+    std::env::var("COPILOT_FALLBACK_MODELS")
+        .ok()
+        .map(|raw| parse_fallback_models(&raw))
+        .unwrap_or_default()
+}
+
+/// Fallbacks are held to the same COPILOT_ALLOWED_MODELS as the requested model.
+pub fn allowed_fallbacks(fallbacks: Vec<String>, allowed: Option<&[String]>) -> Vec<String> {
+    fallbacks
+        .into_iter()
+        .filter(|model| {
+            let permitted = check_model_allowed(model, allowed).is_ok();
+            if !permitted {
+                tracing::debug!("Skipping fallback {} outside COPILOT_ALLOWED_MODELS", model);
+            }
+            permitted
+        })
+        .collect()
+}
+
+fn parse_fallback_models(raw: &str) -> HashMap<String, Vec<String>> {
+    match serde_json::from_str::<HashMap<String, Vec<String>>>(raw) {
+        Ok(map) => map,
+        Err(err) => {
+            tracing::warn!("Ignoring invalid COPILOT_FALLBACK_MODELS: {}", err);
+            HashMap::new()
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::NOT_FOUND || status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Sends to each model in `chain` until one succeeds, moving on only for
+/// retryable statuses. Returns the model that answered with its response, or
+/// the last upstream error with its status.
+pub async fn send_with_fallbacks<F, Fut>(chain: &[String], mut send: F) -> ApiResult<(String, reqwest::Response)>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ApiResult<reqwest::Response>>,
+{
+    for (i, model) in chain.iter().enumerate() {
+        let resp = send(model.clone()).await?;
+        let status = resp.status();
+        if status.is_success() {
+            if i > 0 {
+                tracing::info!("Model {} unavailable, served by fallback {}", chain[0], model);
+            }
+            return Ok((model.clone(), resp));
+        }
+
+        let text = resp.text().await.unwrap_or_default();
+        if is_retryable(status) && i + 1 < chain.len() {
+            tracing::warn!("Model {} failed with {}, trying fallback {}", model, status, chain[i + 1]);
+            continue;
+        }
+        return Err(ApiError::UpstreamStatus(status, format!("Failed to create chat completions: {text}")));
+    }
+
+    Err(ApiError::Internal("No models to try".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{allowed_fallbacks, parse_fallback_models, send_with_fallbacks};
+    use crate::errors::ApiError;
+
+    fn response(status: u16, body: &'static str) -> reqwest::Response {
+        reqwest::Response::from(
+            axum::http::Response::builder()
+                .status(status)
+                .body(body)
+                .unwrap(),
+        )
+    }
+
+    fn chain() -> Vec<String> {
+        vec!["gpt-5.2-codex".to_string(), "gpt-5.1-codex".to_string(), "gpt-4o".to_string()]
+    }
+
+    #[test]
+    fn parses_ordered_fallback_map() {
+        let map = parse_fallback_models(r#"{"gpt-5.2-codex":["gpt-5.1-codex","gpt-4o"]}"#);
+        assert_eq!(map["gpt-5.2-codex"], vec!["gpt-5.1-codex", "gpt-4o"]);
+        assert!(parse_fallback_models("not json").is_empty());
+    }
+
+    #[test]
+    fn fallbacks_outside_the_allowlist_are_dropped() {
+        let fallbacks = vec!["gpt-5.1-codex".to_string(), "gpt-4o".to_string()];
+        let allowed = vec!["gpt-4o-mini".to_string(), "gpt-4o".to_string()];
+        assert_eq!(allowed_fallbacks(fallbacks.clone(), Some(&allowed)), vec!["gpt-4o"]);
+        assert_eq!(allowed_fallbacks(fallbacks.clone(), None), fallbacks);
+        assert!(allowed_fallbacks(fallbacks, Some(&["gpt-4o-mini".to_string()])).is_empty());
+    }
+
+    #[tokio::test]
+    async fn falls_back_after_primary_failure() {
+        let mut attempts = Vec::new();
+        let (model, resp) = send_with_fallbacks(&chain(), |model| {
+            attempts.push(model.clone());
+            async move {
+                if model == "gpt-5.2-codex" {
+                    Ok(response(503, "capacity"))
+                } else {
+                    Ok(response(200, "{\"ok\":true}"))
+                }
+            }
+        })
+        .await
+        .expect("fallback succeeds");
+
+        assert_eq!(model, "gpt-5.1-codex");
+        assert_eq!(attempts, vec!["gpt-5.2-codex", "gpt-5.1-codex"]);
+        assert_eq!(resp.text().await.unwrap(), "{\"ok\":true}");
+    }
+
+    #[tokio::test]
+    async fn non_retryable_error_stops_the_chain() {
+        let mut attempts = 0;
+        let err = send_with_fallbacks(&chain(), |_| {
+            attempts += 1;
+            async { Ok(response(400, "bad request")) }
+        })
+        .await
+        .expect_err("400 is not retried");
+
+        assert_eq!(attempts, 1);
+        assert_eq!(err.status_code(), 400);
+        assert!(matches!(err, ApiError::UpstreamStatus(_, msg) if msg.contains("bad request")));
+    }
+
+    #[tokio::test]
+    async fn exhausted_chain_keeps_the_last_status() {
+        let mut attempts = 0;
+        let err = send_with_fallbacks(&chain(), |model| {
+            attempts += 1;
+            async move {
+                match model.as_str() {
+                    "gpt-4o" => Ok(response(429, "slow down")),
+                    _ => Ok(response(503, "capacity")),
+                }
+            }
+        })
+        .await
+        .expect_err("every model failed");
+
+        assert_eq!(attempts, 3);
+        assert_eq!(err.status_code(), 429);
+        assert_eq!(err.to_string(), "Failed to create chat completions: slow down");
+    }
+}
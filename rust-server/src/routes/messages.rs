@@ -1,4 +1,4 @@
-use axum::{extract::State, response::{IntoResponse, Response}, Json};
+use axum::{extract::State, http::HeaderMap, response::{IntoResponse, Response}, Json};
 use bytes::Bytes;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
@@ -6,16 +6,22 @@ use uuid::Uuid;
 
 use crate::{
     approval::check_manual_approval,
-    auth_flow::ensure_copilot_token,
+    auth_flow::{create_chat_completions_with_retry, create_responses_with_retry, ensure_copilot_token},
     errors::{ApiError, ApiResult},
     hooks::types::HookInput,
     rate_limit::check_rate_limit,
-    routes::responses::{extract_instructions, messages_to_responses_input},
+    routes::{
+        models::check_model_allowed,
+        responses::chat_to_responses_payload,
+        sampling,
+        streaming::{drain_sse_blocks, extract_sse_data, extract_sse_event, output_text_part},
+        tool_choice,
+    },
     services::{
         anthropic,
-        copilot::{create_chat_completions, create_responses, ChatCompletionsPayload, Message, Tool},
+        copilot::{parse_json, prepend_system_prefix, rate_limit_headers, parse_initiator, request_timeout, system_prefix, ChatCompletionsPayload, Message, Tool},
     },
-    state::AppState,
+    state::{AppState, ModelsResponse},
 };
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -43,6 +49,8 @@ pub struct AnthropicMessagesPayload {
     pub tool_choice: Option<serde_json::Value>,
 }
 
+// `role` is the enum tag; serde strips it before deserializing the variant,
+// so the message structs must not declare it again.
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "role")]
 pub enum AnthropicMessage {
@@ -50,17 +58,24 @@ pub enum AnthropicMessage {
     User(AnthropicUserMessage),
     #[serde(rename = "assistant")]
     Assistant(AnthropicAssistantMessage),
+    /// Not part of the Anthropic API, but some clients interleave system
+    /// turns between user and assistant messages.
+    #[serde(rename = "system")]
+    System(AnthropicSystemMessage),
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AnthropicUserMessage {
-    pub role: String,
     pub content: serde_json::Value,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AnthropicAssistantMessage {
-    pub role: String,
+    pub content: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AnthropicSystemMessage {
     pub content: serde_json::Value,
 }
 
@@ -85,7 +100,14 @@ pub struct AnthropicResponse {
     pub usage: serde_json::Value,
 }
 
-pub async fn handle(State(state): State<AppState>, Json(payload): Json<AnthropicMessagesPayload>) -> ApiResult<Response> {
+pub async fn handle(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<AnthropicMessagesPayload>,
+) -> ApiResult<Response> {
+    if payload.messages.is_empty() {
+        return Err(ApiError::BadRequest("messages must not be empty".to_string()));
+    }
     if let Some(hooks) = &state.hooks {
         let input = HookInput {
             hook_type: Some("PreToolUse".to_string()),
@@ -99,8 +121,36 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Anthropic
             return Err(ApiError::BadRequest("Hook blocked request".to_string()));
         }
     }
+    let resolved_model = resolve_model_alias(&payload.model);
+    check_model_allowed(&resolved_model, state.config.read().await.allowed_models.as_deref())?;
     check_manual_approval(&state).await?;
-    check_rate_limit(&state).await?;
+    check_rate_limit(&state, &resolved_model).await?;
+    let streaming = payload.stream.unwrap_or(false);
+    stream_errors(streaming, forward(state, payload, &headers).await)
+}
+
+/// A streaming client has already committed to SSE parsing, so failures that
+/// happen before the first chunk (credentials, upstream rejection) are sent as
+/// a single Anthropic `error` event. Invalid requests keep their JSON 400.
+fn stream_errors(streaming: bool, result: ApiResult<Response>) -> ApiResult<Response> {
+    match result {
+        Err(err) if streaming && !matches!(err, ApiError::BadRequest(_)) => {
+            tracing::warn!("Streaming /v1/messages failed before the first chunk: {}", err);
+            let error_type = match err {
+                ApiError::Unauthorized(_) => "authentication_error",
+                ApiError::NotFound(_) => "not_found_error",
+                _ => "api_error",
+            };
+            let ev = anthropic_error_event(error_type, &err.to_string());
+            let frame = format!("event: error\ndata: {}\n\n", ev);
+            let body = futures::stream::once(async move { Ok::<_, std::io::Error>(Bytes::from(frame)) });
+            Ok(crate::routes::streaming::sse_response(body))
+        }
+        result => result,
+    }
+}
+
+async fn forward(state: AppState, payload: AnthropicMessagesPayload, headers: &HeaderMap) -> ApiResult<Response> {
     let provider = std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string());
 
     if provider == "anthropic" || (payload.model.to_lowercase().starts_with("claude") && std::env::var("ANTHROPIC_API_KEY").is_ok()) {
@@ -119,7 +169,7 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Anthropic
             }
             return Ok(crate::routes::streaming::sse_response(stream));
         }
-        let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid Anthropic response: {e}")))?;
+        let json: serde_json::Value = parse_json(resp, "Invalid Anthropic response").await?;
         if let Some(hooks) = &state.hooks {
             let input = HookInput {
                 hook_type: Some("PostToolUse".to_string()),
@@ -133,15 +183,19 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Anthropic
         return Ok(Json(json).into_response());
     }
     let resolved_model = resolve_model_alias(&payload.model);
+    validate_documents(&payload, requires_responses_api(&resolved_model))?;
     let token = ensure_copilot_token(&state).await?;
+    let mut openai_payload = translate_to_openai(&payload);
+    apply_request_headers(&mut openai_payload, headers);
+    check_context_window(&openai_payload, state.config.read().await.models.as_ref())?;
 
     if requires_responses_api(&resolved_model) {
-        return handle_responses_api(state, payload, resolved_model).await;
+        return handle_responses_api(state, payload, &openai_payload, resolved_model).await;
     }
 
-    let openai_payload = translate_to_openai(&payload);
     let config = state.config.read().await.clone();
-    let resp = create_chat_completions(&state.client, &config, &token, &openai_payload).await?;
+    let resp = create_chat_completions_with_retry(&state, &config, &token, &openai_payload).await?;
+    let upstream_headers = rate_limit_headers(resp.headers());
 
     if payload.stream.unwrap_or(false) {
         if let Some(hooks) = &state.hooks {
@@ -154,10 +208,11 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Anthropic
             };
             let _ = hooks.execute_event("PostToolUse", &input).await;
         }
-        return Ok(stream_anthropic(resp));
+        return Ok((upstream_headers, stream_anthropic(resp, &openai_payload.model)).into_response());
     }
 
-    let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid response: {e}")))?;
+    let json: serde_json::Value = parse_json(resp, "Invalid response").await?;
+    crate::metrics::record_usage(&openai_payload.model, &json);
     let anthropic = translate_to_anthropic(&json, &payload.model);
     if let Some(hooks) = &state.hooks {
         let input = HookInput {
@@ -169,7 +224,109 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Anthropic
         };
         let _ = hooks.execute_event("PostToolUse", &input).await;
     }
-    Ok(Json(anthropic).into_response())
+    Ok((upstream_headers, Json(anthropic)).into_response())
+}
+
+/// Rejects prompts that can't fit the model's context window instead of
+/// letting upstream fail with an opaque error. Skipped when the model list
+/// hasn't been loaded or the model has no advertised limit.
+fn check_context_window(payload: &ChatCompletionsPayload, models: Option<&ModelsResponse>) -> ApiResult<()> {
+    let Some(model) = models.and_then(|models| models.data.iter().find(|m| m.id == payload.model)) else {
+        return Ok(());
+    };
+    let Some(limit) = model.capabilities.limits.max_context_window_tokens else {
+        return Ok(());
+    };
+
+    let estimate = if crate::tokenizer::use_precise_tokenizer() {
+        let tokenizer = Some(model.capabilities.tokenizer.as_str()).filter(|t| !t.is_empty()).unwrap_or("o200k_base");
+        crate::tokenizer::estimate_chat_tokens(payload, tokenizer)
+    } else {
+        crate::utils::estimate_tokens_from_json(&serde_json::to_value(payload).unwrap_or_default())
+    };
+    if estimate > u64::from(limit) {
+        return Err(ApiError::BadRequest(format!(
+            "prompt exceeds model context window: ~{estimate} tokens, {} allows {limit}",
+            payload.model
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessagesBatchRequest {
+    pub requests: Vec<AnthropicMessagesPayload>,
+}
+
+/// Larger batches are rejected outright rather than queued behind the
+/// concurrency limit.
+const MAX_BATCH_REQUESTS: usize = 100;
+
+/// Runs each payload through [`handle`], at most COPILOT_BATCH_CONCURRENCY at a
+/// time. Results keep request order and a failed item does not fail the batch.
+pub async fn batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(batch): Json<MessagesBatchRequest>,
+) -> ApiResult<Response> {
+    if batch.requests.is_empty() {
+        return Err(ApiError::BadRequest("requests must not be empty".to_string()));
+    }
+    if batch.requests.len() > MAX_BATCH_REQUESTS {
+        return Err(ApiError::BadRequest(format!(
+            "a batch may hold at most {MAX_BATCH_REQUESTS} requests, got {}",
+            batch.requests.len()
+        )));
+    }
+    if batch.requests.iter().any(|r| r.stream.unwrap_or(false)) {
+        return Err(ApiError::BadRequest("stream is not supported on /v1/messages/batch".to_string()));
+    }
+    let responses = run_batch(batch.requests, batch_concurrency(), |payload| {
+        handle(State(state.clone()), headers.clone(), Json(payload))
+    })
+    .await;
+    Ok(Json(serde_json::json!({ "responses": responses })).into_response())
+}
+
+fn batch_concurrency() -> usize {
+    std::env::var("COPILOT_BATCH_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(4)
+}
+
+async fn run_batch<F, Fut>(requests: Vec<AnthropicMessagesPayload>, concurrency: usize, run: F) -> Vec<serde_json::Value>
+where
+    F: Fn(AnthropicMessagesPayload) -> Fut,
+    Fut: std::future::Future<Output = ApiResult<Response>>,
+{
+    futures::stream::iter(requests)
+        .map(|payload| {
+            let item = run(payload);
+            async move {
+                let resp = match item.await {
+                    Ok(resp) => resp,
+                    Err(err) => return batch_error(err.status_code().as_u16(), err.to_string()),
+                };
+                let status = resp.status();
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap_or_default();
+                let json = serde_json::from_slice::<serde_json::Value>(&body).unwrap_or(serde_json::Value::Null);
+                if status.is_success() {
+                    serde_json::json!({ "type": "succeeded", "message": json })
+                } else {
+                    let message = json["error"]["message"].as_str().unwrap_or_default().to_string();
+                    batch_error(status.as_u16(), message)
+                }
+            }
+        })
+        .buffered(concurrency)
+        .collect()
+        .await
+}
+
+fn batch_error(status: u16, message: String) -> serde_json::Value {
+    serde_json::json!({ "type": "errored", "status": status, "error": { "message": message } })
 }
 
 pub async fn count_tokens(
@@ -212,60 +369,49 @@ pub async fn count_tokens(
 async fn handle_responses_api(
     state: AppState,
     payload: AnthropicMessagesPayload,
+    openai_payload: &ChatCompletionsPayload,
     resolved_model: String,
 ) -> ApiResult<Response> {
     let token = ensure_copilot_token(&state).await?;
-    let openai_payload = translate_to_openai(&payload);
-    let instructions = extract_instructions(&openai_payload.messages);
-    let input = messages_to_responses_input(&openai_payload.messages);
-
-    if input.is_empty() {
-        return Err(ApiError::BadRequest("No valid input messages".to_string()));
-    }
-
-    let responses_payload = crate::services::copilot::ResponsesPayload {
-        model: resolved_model,
-        input: serde_json::to_value(input).unwrap_or(serde_json::json!([])),
-        instructions,
-        max_output_tokens: openai_payload.max_tokens,
-        temperature: openai_payload.temperature,
-        top_p: openai_payload.top_p,
-        stream: payload.stream,
-        tools: openai_payload.tools.as_ref().map(|tools| {
-            serde_json::Value::Array(
-                tools
-                    .iter()
-                    .map(|t| serde_json::json!({
-                        "type": "function",
-                        "name": t.function.name,
-                        "description": t.function.description,
-                        "parameters": t.function.parameters,
-                    }))
-                    .collect(),
-            )
-        }),
-        tool_choice: openai_payload.tool_choice,
-        previous_response_id: None,
-    };
+    let mut responses_payload = chat_to_responses_payload(openai_payload)?;
+    responses_payload.model = resolved_model;
 
     let config = state.config.read().await.clone();
-    let resp = create_responses(&state.client, &config, &token, &responses_payload).await?;
+    let resp = create_responses_with_retry(&state, &config, &token, &responses_payload).await?;
+    let upstream_headers = rate_limit_headers(resp.headers());
 
     if payload.stream.unwrap_or(false) {
-        return Ok(stream_anthropic_from_responses(resp, &payload.model));
+        return Ok((
+            upstream_headers,
+            stream_anthropic_from_responses(resp, &payload.model, &responses_payload.model),
+        )
+            .into_response());
     }
 
-    let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid responses payload: {e}")))?;
+    let json: serde_json::Value = parse_json(resp, "Invalid responses payload").await?;
+    crate::metrics::record_usage(&responses_payload.model, &json);
     let anthropic = translate_responses_to_anthropic(&json, &payload.model);
-    Ok(Json(anthropic).into_response())
+    Ok((upstream_headers, Json(anthropic)).into_response())
+}
+
+/// Carries the per-request upstream options that chat completions reads from
+/// headers over to the translated payload.
+fn apply_request_headers(payload: &mut ChatCompletionsPayload, headers: &HeaderMap) {
+    payload.timeout = request_timeout(headers);
+    payload.initiator = headers
+        .get("x-initiator")
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_initiator)
+        .map(str::to_string);
 }
 
 fn translate_to_openai(payload: &AnthropicMessagesPayload) -> ChatCompletionsPayload {
     let messages = translate_messages(&payload.messages, payload.system.clone());
-    ChatCompletionsPayload {
+    let mut out = ChatCompletionsPayload {
         model: resolve_model_alias(&payload.model),
         messages,
         max_tokens: Some(payload.max_tokens),
+        max_completion_tokens: None,
         stop: payload.stop_sequences.as_ref().map(|s| serde_json::to_value(s).unwrap()),
         stream: payload.stream,
         temperature: payload.temperature,
@@ -278,9 +424,16 @@ fn translate_to_openai(payload: &AnthropicMessagesPayload) -> ChatCompletionsPay
         response_format: None,
         seed: None,
         tools: payload.tools.as_ref().map(|t| translate_tools(t)),
-        tool_choice: payload.tool_choice.clone(),
+        tool_choice: tool_choice::from_anthropic(payload.tool_choice.as_ref()),
+        parallel_tool_calls: None,
         user: payload.metadata.as_ref().and_then(|m| m.get("user_id").and_then(|v| v.as_str()).map(|s| s.to_string())),
-    }
+        initiator: None,
+        timeout: None,
+    };
+    tool_choice::apply_none(&mut out);
+    sampling::apply(&mut out);
+    prepend_system_prefix(&mut out.messages, system_prefix(&out.model));
+    out
 }
 
 fn translate_tools(tools: &Vec<AnthropicTool>) -> Vec<Tool> {
@@ -297,44 +450,70 @@ fn translate_tools(tools: &Vec<AnthropicTool>) -> Vec<Tool> {
         .collect()
 }
 
+/// The top-level `system` field becomes one leading system message (array
+/// blocks joined with a blank line). Each `role: "system"` entry in `messages`
+/// becomes its own system message at the same position; it is never merged
+/// into the leading one or its neighbours. Empty system text is dropped.
 fn translate_messages(messages: &[AnthropicMessage], system: Option<serde_json::Value>) -> Vec<Message> {
     let mut out = Vec::new();
 
     if let Some(system) = system {
-        if system.is_string() {
-            out.push(Message {
-                role: "system".to_string(),
-                content: system,
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            });
-        } else if let Some(arr) = system.as_array() {
-            let text = arr
-                .iter()
-                .filter_map(|v| v.get("text").and_then(|t| t.as_str()))
-                .collect::<Vec<_>>()
-                .join("\n\n");
-            out.push(Message {
-                role: "system".to_string(),
-                content: serde_json::Value::String(text),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            });
-        }
+        out.extend(system_message(&system));
     }
 
     for msg in messages {
         match msg {
             AnthropicMessage::User(m) => out.extend(handle_user_message(m)),
             AnthropicMessage::Assistant(m) => out.extend(handle_assistant_message(m)),
+            AnthropicMessage::System(m) => out.extend(system_message(&m.content)),
         }
     }
 
     out
 }
 
+fn system_message(content: &serde_json::Value) -> Option<Message> {
+    let text = match content {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|v| v.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => return None,
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(Message {
+        role: "system".to_string(),
+        content: serde_json::Value::String(text),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+        reasoning_text: None,
+        reasoning_opaque: None,
+    })
+}
+
+/// The OpenAI tool role only takes a string, so block arrays are flattened:
+/// text is concatenated and images are left as a placeholder.
+fn tool_result_content(content: Option<&serde_json::Value>) -> serde_json::Value {
+    let Some(blocks) = content.and_then(|c| c.as_array()) else {
+        return content.cloned().unwrap_or(serde_json::Value::Null);
+    };
+    let text = blocks
+        .iter()
+        .filter_map(|b| match b.get("type").and_then(|t| t.as_str()) {
+            Some("text") => b.get("text").and_then(|t| t.as_str()).map(str::to_string),
+            Some("image") => Some("[image]".to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    serde_json::Value::String(text)
+}
+
 fn handle_user_message(message: &AnthropicUserMessage) -> Vec<Message> {
     if let Some(arr) = message.content.as_array() {
         let tool_results: Vec<&serde_json::Value> = arr.iter().filter(|b| b.get("type") == Some(&serde_json::Value::String("tool_result".to_string()))).collect();
@@ -344,10 +523,12 @@ fn handle_user_message(message: &AnthropicUserMessage) -> Vec<Message> {
         for block in tool_results {
             out.push(Message {
                 role: "tool".to_string(),
-                content: block.get("content").cloned().unwrap_or(serde_json::Value::Null),
+                content: tool_result_content(block.get("content")),
                 name: None,
                 tool_calls: None,
                 tool_call_id: block.get("tool_use_id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                reasoning_text: None,
+                reasoning_opaque: None,
             });
         }
 
@@ -358,6 +539,8 @@ fn handle_user_message(message: &AnthropicUserMessage) -> Vec<Message> {
                 name: None,
                 tool_calls: None,
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             });
         }
 
@@ -370,6 +553,8 @@ fn handle_user_message(message: &AnthropicUserMessage) -> Vec<Message> {
         name: None,
         tool_calls: None,
         tool_call_id: None,
+        reasoning_text: None,
+        reasoning_opaque: None,
     }]
 }
 
@@ -382,9 +567,19 @@ fn handle_assistant_message(message: &AnthropicAssistantMessage) -> Vec<Message>
         let all_text = text_blocks
             .iter()
             .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
-            .chain(thinking_blocks.iter().filter_map(|b| b.get("thinking").and_then(|t| t.as_str())))
             .collect::<Vec<_>>()
             .join("\n\n");
+        let reasoning_text = thinking_blocks
+            .iter()
+            .filter_map(|b| b.get("thinking").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let reasoning_text = (!reasoning_text.is_empty()).then_some(reasoning_text);
+        let reasoning_opaque = thinking_blocks
+            .iter()
+            .rev()
+            .find_map(|b| b.get("signature").and_then(|t| t.as_str()))
+            .map(|s| s.to_string());
 
         if !tool_uses.is_empty() {
             let tool_calls = tool_uses
@@ -410,6 +605,20 @@ fn handle_assistant_message(message: &AnthropicAssistantMessage) -> Vec<Message>
                 name: None,
                 tool_calls: Some(tool_calls),
                 tool_call_id: None,
+                reasoning_text,
+                reasoning_opaque,
+            }];
+        }
+
+        if reasoning_text.is_some() {
+            return vec![Message {
+                role: "assistant".to_string(),
+                content: serde_json::Value::String(all_text),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+                reasoning_text,
+                reasoning_opaque,
             }];
         }
     }
@@ -420,12 +629,61 @@ fn handle_assistant_message(message: &AnthropicAssistantMessage) -> Vec<Message>
         name: None,
         tool_calls: None,
         tool_call_id: None,
+        reasoning_text: None,
+        reasoning_opaque: None,
     }]
 }
 
+/// Document blocks on the Copilot path: base64 PDFs become chat `file` parts,
+/// plain-text documents become text parts, and any other source is rejected.
+/// Responses API models get no PDFs since that bridge only forwards text.
+fn validate_documents(payload: &AnthropicMessagesPayload, responses_api: bool) -> ApiResult<()> {
+    let documents = payload.messages.iter().filter_map(|m| match m {
+        AnthropicMessage::User(m) => m.content.as_array(),
+        AnthropicMessage::Assistant(_) | AnthropicMessage::System(_) => None,
+    });
+    for block in documents.flatten().filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("document")) {
+        match block.pointer("/source/type").and_then(|t| t.as_str()) {
+            Some("text") => {}
+            Some("base64") if responses_api => {
+                return Err(ApiError::BadRequest(format!(
+                    "PDF document blocks are not supported for {}",
+                    resolve_model_alias(&payload.model)
+                )));
+            }
+            Some("base64") => {}
+            other => {
+                return Err(ApiError::BadRequest(format!(
+                    "document source type {} is not supported; send the document as base64",
+                    other.unwrap_or("(missing)")
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn document_part(block: &serde_json::Value) -> Option<serde_json::Value> {
+    let source = block.get("source")?;
+    let data = source.get("data").and_then(|v| v.as_str()).unwrap_or("");
+    match source.get("type").and_then(|v| v.as_str()) {
+        Some("text") => Some(serde_json::json!({"type": "text", "text": data})),
+        Some("base64") => Some(serde_json::json!({
+            "type": "file",
+            "file": {
+                "filename": block.get("title").and_then(|v| v.as_str()).unwrap_or("document.pdf"),
+                "file_data": format!("data:{};base64,{}", source.get("media_type").and_then(|v| v.as_str()).unwrap_or("application/pdf"), data),
+            }
+        })),
+        _ => None,
+    }
+}
+
 fn map_content(blocks: Vec<&serde_json::Value>) -> serde_json::Value {
-    let has_image = blocks.iter().any(|b| b.get("type") == Some(&serde_json::Value::String("image".to_string())));
-    if !has_image {
+    let needs_parts = blocks
+        .iter()
+        .any(|b| matches!(b.get("type").and_then(|t| t.as_str()), Some("image" | "document")));
+    if !needs_parts {
         let text = blocks
             .iter()
             .filter_map(|b| b.get("text").and_then(|t| t.as_str()).or_else(|| b.get("thinking").and_then(|t| t.as_str())))
@@ -450,6 +708,8 @@ fn map_content(blocks: Vec<&serde_json::Value>) -> serde_json::Value {
                         }
                     }));
                 }
+            } else if kind == "document" {
+                parts.extend(document_part(block));
             }
         }
     }
@@ -457,16 +717,29 @@ fn map_content(blocks: Vec<&serde_json::Value>) -> serde_json::Value {
     serde_json::Value::Array(parts)
 }
 
+fn thinking_block(message: &serde_json::Value) -> Option<serde_json::Value> {
+    let thinking = ["reasoning_text", "reasoning_content", "reasoning"]
+        .iter()
+        .find_map(|key| message.get(*key).and_then(|v| v.as_str()))
+        .filter(|text| !text.is_empty())?;
+    let signature = message.get("reasoning_opaque").and_then(|v| v.as_str()).unwrap_or("");
+    Some(serde_json::json!({ "type": "thinking", "thinking": thinking, "signature": signature }))
+}
+
 fn translate_to_anthropic(openai: &serde_json::Value, model: &str) -> serde_json::Value {
     let mut all_text_blocks: Vec<serde_json::Value> = Vec::new();
     let mut all_tool_blocks: Vec<serde_json::Value> = Vec::new();
 
     let choices = openai.get("choices").and_then(|c| c.as_array()).cloned().unwrap_or_default();
-    let mut stop_reason: Option<String> = None;
+    let mut stop: Option<(&str, Option<String>)> = None;
 
     for choice in &choices {
         let message = choice.get("message");
 
+        if let Some(block) = message.and_then(thinking_block) {
+            all_text_blocks.push(block);
+        }
+
         if let Some(content) = message.and_then(|m| m.get("content")) {
             if let Some(text) = content.as_str() {
                 all_text_blocks.push(serde_json::json!({ "type": "text", "text": text }));
@@ -505,40 +778,15 @@ fn translate_to_anthropic(openai: &serde_json::Value, model: &str) -> serde_json
             }
         }
 
-        if let Some(reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
-            stop_reason = Some(reason.to_string());
+        if choice.get("finish_reason").is_some_and(|v| v.is_string()) {
+            stop = Some(anthropic_stop(choice));
         }
     }
 
-    let usage = openai.get("usage");
-    let prompt_tokens = usage
-        .and_then(|u| u.get("prompt_tokens"))
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0);
-    let completion_tokens = usage
-        .and_then(|u| u.get("completion_tokens"))
-        .and_then(|v| v.as_u64())
-        .unwrap_or(0);
-    let cached_tokens = usage
-        .and_then(|u| u.get("prompt_tokens_details"))
-        .and_then(|d| d.get("cached_tokens"))
-        .and_then(|v| v.as_u64());
-    let input_tokens = cached_tokens
-        .map(|c| prompt_tokens.saturating_sub(c))
-        .unwrap_or(prompt_tokens);
-
-    let mut usage_json = serde_json::json!({
-        "input_tokens": input_tokens,
-        "output_tokens": completion_tokens,
-    });
-    if let Some(cached) = cached_tokens {
-        usage_json["cache_read_input_tokens"] = serde_json::Value::from(cached);
-    }
+    let usage = extract_usage(openai);
+    let usage_json = usage.to_anthropic(usage.output_tokens);
 
-    let stop_reason = stop_reason
-        .as_deref()
-        .map(map_openai_stop_reason)
-        .unwrap_or("end_turn");
+    let (stop_reason, stop_sequence) = stop.unwrap_or(("end_turn", None));
 
     let mut content = all_text_blocks;
     content.extend(all_tool_blocks);
@@ -550,7 +798,7 @@ fn translate_to_anthropic(openai: &serde_json::Value, model: &str) -> serde_json
         "content": content,
         "model": model,
         "stop_reason": stop_reason,
-        "stop_sequence": null,
+        "stop_sequence": stop_sequence,
         "usage": usage_json,
     })
 }
@@ -558,25 +806,16 @@ fn translate_to_anthropic(openai: &serde_json::Value, model: &str) -> serde_json
 #[cfg(test)]
 mod tests {
     use super::{
-        count_tokens, drain_sse_blocks, extract_sse_data, handle_user_message, map_content, resolve_model_alias,
-        translate_chunk_to_anthropic_events, translate_messages, translate_responses_to_anthropic,
-        translate_to_anthropic, translate_to_openai, AnthropicMessage, AnthropicMessagesPayload,
-        AnthropicStreamState, AnthropicTool, AnthropicUserMessage,
+        apply_request_headers, batch, check_context_window, count_tokens, run_batch, drain_sse_blocks, handle, extract_sse_data, handle_assistant_message, handle_user_message, map_content, resolve_model_alias,
+        stream_errors, validate_documents,
+        responses_to_anthropic_stream, translate_chunk_to_anthropic_events, translate_messages, translate_responses_to_anthropic,
+        translate_to_anthropic, translate_to_openai, AnthropicMessage, AnthropicMessagesPayload, anthropic_stop, MessagesBatchRequest, MAX_BATCH_REQUESTS,
+        AnthropicAssistantMessage, AnthropicStreamState, AnthropicTool, AnthropicUserMessage, USAGE_DELTA_INTERVAL,
     };
-    use axum::{body::to_bytes, extract::State, response::IntoResponse, Json};
-
-    fn test_state() -> crate::state::AppState {
-        let client = reqwest::Client::builder()
-            .user_agent("copilot-api-rs-test")
-            .build()
-            .expect("reqwest client");
-        let config = crate::state::AppConfig::default();
-        crate::state::AppState {
-            config: std::sync::Arc::new(tokio::sync::RwLock::new(config)),
-            client,
-            hooks: None,
-        }
-    }
+    use crate::state::test_state;
+    use axum::{body::to_bytes, extract::State, http::HeaderMap, response::IntoResponse, Json};
+    use crate::errors::ApiError;
+
 
     #[test]
     fn translates_tool_calls_and_usage() {
@@ -616,6 +855,122 @@ mod tests {
         assert_eq!(usage.get("cache_read_input_tokens").and_then(|v| v.as_u64()), Some(2));
     }
 
+    #[test]
+    fn translates_cache_read_and_creation_usage() {
+        let response = serde_json::json!({
+            "choices": [{
+                "finish_reason": "stop",
+                "message": { "content": "hello" }
+            }],
+            "usage": {
+                "prompt_tokens": 100,
+                "completion_tokens": 5,
+                "prompt_tokens_details": { "cached_tokens": 60, "cache_creation_tokens": 30 }
+            }
+        });
+
+        let out = translate_to_anthropic(&response, "claude-sonnet-4");
+        let usage = out.get("usage").unwrap();
+        assert_eq!(usage.get("input_tokens").and_then(|v| v.as_u64()), Some(10));
+        assert_eq!(usage.get("cache_read_input_tokens").and_then(|v| v.as_u64()), Some(60));
+        assert_eq!(usage.get("cache_creation_input_tokens").and_then(|v| v.as_u64()), Some(30));
+
+        let mut state = AnthropicStreamState::default();
+        let chunk = serde_json::json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o",
+            "choices": [{ "delta": {}, "finish_reason": "stop" }],
+            "usage": response["usage"].clone()
+        });
+        let events = translate_chunk_to_anthropic_events(&chunk, &mut state);
+        let delta = events
+            .iter()
+            .find(|e| e.get("type").and_then(|v| v.as_str()) == Some("message_delta"))
+            .expect("message_delta");
+        assert_eq!(delta["usage"]["cache_creation_input_tokens"].as_u64(), Some(30));
+        assert_eq!(delta["usage"]["cache_read_input_tokens"].as_u64(), Some(60));
+        assert_eq!(delta["usage"]["output_tokens"].as_u64(), Some(5));
+    }
+
+    #[test]
+    fn thinking_round_trips_through_openai_translation() {
+        let response = serde_json::json!({
+            "choices": [{
+                "finish_reason": "stop",
+                "message": {
+                    "content": "The answer is 4.",
+                    "reasoning_text": "2 + 2 = 4",
+                    "reasoning_opaque": "sig-123"
+                }
+            }],
+            "usage": { "prompt_tokens": 3, "completion_tokens": 7 }
+        });
+
+        let out = translate_to_anthropic(&response, "claude-sonnet-4");
+        let content = out["content"].as_array().unwrap();
+        assert_eq!(content[0]["type"], "thinking");
+        assert_eq!(content[0]["thinking"], "2 + 2 = 4");
+        assert_eq!(content[0]["signature"], "sig-123");
+        assert_eq!(content[1]["type"], "text");
+
+        let replay = AnthropicAssistantMessage {
+            content: out["content"].clone(),
+        };
+        let messages = handle_assistant_message(&replay);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].content, "The answer is 4.");
+        assert_eq!(messages[0].reasoning_text.as_deref(), Some("2 + 2 = 4"));
+        assert_eq!(messages[0].reasoning_opaque.as_deref(), Some("sig-123"));
+    }
+
+    #[test]
+    fn thinking_is_kept_separate_from_tool_call_text() {
+        let message = AnthropicAssistantMessage {
+            content: serde_json::json!([
+                { "type": "thinking", "thinking": "need the weather", "signature": "s1" },
+                { "type": "text", "text": "Checking." },
+                { "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": { "city": "Paris" } }
+            ]),
+        };
+
+        let messages = handle_assistant_message(&message);
+        assert_eq!(messages[0].content, "Checking.");
+        assert_eq!(messages[0].reasoning_text.as_deref(), Some("need the weather"));
+        assert_eq!(messages[0].tool_calls.as_ref().map(|c| c.len()), Some(1));
+    }
+
+    #[test]
+    fn emits_running_output_tokens_during_stream() {
+        let mut state = AnthropicStreamState {
+            estimate_output: true,
+            ..Default::default()
+        };
+        let mut intermediate = Vec::new();
+        for _ in 0..USAGE_DELTA_INTERVAL {
+            let chunk = serde_json::json!({
+                "id": "chatcmpl-1",
+                "model": "gpt-4o",
+                "choices": [{ "delta": { "content": "hello world " }, "finish_reason": null }]
+            });
+            intermediate.extend(translate_chunk_to_anthropic_events(&chunk, &mut state));
+        }
+
+        let delta = intermediate
+            .iter()
+            .find(|e| e["type"] == "message_delta")
+            .expect("intermediate message_delta");
+        assert!(delta["usage"]["output_tokens"].as_u64().unwrap() > 0);
+        assert!(delta["delta"]["stop_reason"].is_null());
+
+        let last = serde_json::json!({
+            "choices": [{ "delta": {}, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 4, "completion_tokens": 42 }
+        });
+        let events = translate_chunk_to_anthropic_events(&last, &mut state);
+        let final_delta = events.iter().find(|e| e["type"] == "message_delta").unwrap();
+        assert_eq!(final_delta["usage"]["output_tokens"], 42);
+    }
+
     #[test]
     fn extracts_sse_data_blocks() {
         let mut buffer = b"data: {\"a\":1}\n\n".to_vec();
@@ -673,6 +1028,209 @@ mod tests {
         assert_eq!(usage.get("output_tokens").and_then(|v| v.as_u64()), Some(7));
     }
 
+    #[test]
+    fn rejects_prompt_larger_than_context_window() {
+        let mut model = crate::routes::models::default_model();
+        model.id = "gpt-4o".to_string();
+        model.capabilities.limits.max_context_window_tokens = Some(100);
+        let models = crate::state::ModelsResponse { data: vec![model], object: "list".to_string() };
+
+        let request = AnthropicMessagesPayload {
+            model: "gpt-4o".to_string(),
+            messages: vec![AnthropicMessage::User(AnthropicUserMessage {
+                content: serde_json::json!("word ".repeat(2000)),
+            })],
+            max_tokens: 16,
+            system: None,
+            metadata: None,
+            stop_sequences: None,
+            stream: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            tools: None,
+            tool_choice: None,
+        };
+        let mut payload = translate_to_openai(&request);
+        let err = check_context_window(&payload, Some(&models)).expect_err("oversized prompt rejected");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(err.to_string().starts_with("prompt exceeds model context window"));
+        assert!(err.to_string().ends_with("gpt-4o allows 100"));
+
+        payload.messages[0].content = serde_json::json!("hi");
+        assert!(check_context_window(&payload, Some(&models)).is_ok());
+        assert!(check_context_window(&payload, None).is_ok());
+    }
+
+    async fn collect_anthropic_events(stream_reasoning: bool) -> Vec<serde_json::Value> {
+        let upstream = "data: {\"type\":\"response.reasoning_summary_text.delta\",\"delta\":\"weighing options\"}\n\n\
+            data: {\"type\":\"response.output_text.delta\",\"delta\":\"Done\"}\n\n\
+            data: {\"type\":\"response.completed\",\"response\":{\"usage\":{\"output_tokens\":4}}}\n\n";
+        anthropic_events(upstream, stream_reasoning).await
+    }
+
+    async fn anthropic_events(upstream: &'static str, stream_reasoning: bool) -> Vec<serde_json::Value> {
+        use futures::StreamExt;
+        let chunks = vec![Ok::<bytes::Bytes, std::io::Error>(bytes::Bytes::from(upstream))];
+        let mut buffer = responses_to_anthropic_stream(futures::stream::iter(chunks), "gpt-5.2-codex".to_string(), stream_reasoning)
+            .map(|c| c.unwrap().to_vec())
+            .concat()
+            .await;
+        drain_sse_blocks(&mut buffer)
+            .iter()
+            .filter_map(|block| extract_sse_data(block))
+            .map(|data| serde_json::from_str(&data).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn responses_stream_emits_thinking_block_when_enabled() {
+        let events = collect_anthropic_events(true).await;
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop",
+            ]
+        );
+        assert_eq!(events[1]["content_block"]["type"], "thinking");
+        assert_eq!(events[2]["delta"], serde_json::json!({ "type": "thinking_delta", "thinking": "weighing options" }));
+        assert_eq!(events[4]["index"], 1);
+        assert_eq!(events[4]["content_block"]["type"], "text");
+        assert_eq!(events[5]["delta"]["text"], "Done");
+        assert_eq!(events[6]["index"], 1);
+        assert_eq!(events[7]["usage"]["output_tokens"], 4);
+
+        let events = collect_anthropic_events(false).await;
+        assert!(events.iter().all(|e| e["content_block"]["type"] != "thinking"));
+        let deltas: Vec<_> = events.iter().filter(|e| e["type"] == "content_block_delta").collect();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0]["index"], 0);
+        assert_eq!(deltas[0]["delta"]["text"], "Done");
+    }
+
+    #[tokio::test]
+    async fn responses_stream_falls_back_to_output_text_done() {
+        let done_only = "data: {\"type\":\"response.output_text.done\",\"item_id\":\"msg_1\",\"content_index\":0,\"text\":\"Hi there\"}\n\n\
+            data: {\"type\":\"response.completed\",\"response\":{\"usage\":{\"output_tokens\":2}}}\n\n";
+        let events = anthropic_events(done_only, false).await;
+        let deltas: Vec<_> = events.iter().filter(|e| e["type"] == "content_block_delta").collect();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0]["delta"], serde_json::json!({ "type": "text_delta", "text": "Hi there" }));
+        assert_eq!(events[1]["content_block"]["type"], "text");
+
+        let with_deltas = "data: {\"type\":\"response.output_text.delta\",\"item_id\":\"msg_1\",\"content_index\":0,\"delta\":\"Hi there\"}\n\n\
+            data: {\"type\":\"response.output_text.done\",\"item_id\":\"msg_1\",\"content_index\":0,\"text\":\"Hi there\"}\n\n";
+        let events = anthropic_events(with_deltas, false).await;
+        assert_eq!(events.iter().filter(|e| e["type"] == "content_block_delta").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn batch_reports_per_item_results_in_order() {
+        let payload = |model: &str, messages: serde_json::Value| -> AnthropicMessagesPayload {
+            serde_json::from_value(serde_json::json!({ "model": model, "max_tokens": 16, "messages": messages })).unwrap()
+        };
+        let requests = vec![
+            payload("slow-ok", serde_json::json!([{ "role": "user", "content": "hi" }])),
+            payload("gpt-4o", serde_json::json!([])),
+        ];
+
+        let responses = run_batch(requests, 2, |payload| async move {
+            if payload.model == "slow-ok" {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                Ok(Json(serde_json::json!({ "type": "message", "model": payload.model })).into_response())
+            } else {
+                handle(State(test_state()), HeaderMap::new(), Json(payload)).await
+            }
+        })
+        .await;
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["type"], "succeeded");
+        assert_eq!(responses[0]["message"]["model"], "slow-ok");
+        assert_eq!(responses[1]["type"], "errored");
+        assert_eq!(responses[1]["status"], 400);
+        assert_eq!(responses[1]["error"]["message"], "messages must not be empty");
+    }
+
+    #[tokio::test]
+    async fn aliases_share_the_resolved_model_rate_limit() {
+        let config = crate::state::AppConfig {
+            rate_limit_seconds: None,
+            rate_limit_wait: false,
+            rate_limit_per_model: [("gpt-5.2-codex".to_string(), (30, Some(std::time::Instant::now())))].into(),
+            ..Default::default()
+        };
+        let state = crate::state::AppState::new(config, reqwest::Client::new(), None);
+        let payload: AnthropicMessagesPayload = serde_json::from_value(serde_json::json!({
+            "model": "claude-opus-4.5",
+            "max_tokens": 16,
+            "messages": [{ "role": "user", "content": "hi" }]
+        }))
+        .unwrap();
+
+        let err = handle(State(state), HeaderMap::new(), Json(payload)).await.expect_err("rate limited");
+        assert!(err.to_string().starts_with("Rate limit exceeded"));
+    }
+
+    #[tokio::test]
+    async fn oversized_batch_is_rejected() {
+        let requests = (0..=MAX_BATCH_REQUESTS)
+            .map(|_| {
+                serde_json::from_value(serde_json::json!({
+                    "model": "gpt-4o",
+                    "max_tokens": 16,
+                    "messages": [{ "role": "user", "content": "hi" }]
+                }))
+                .unwrap()
+            })
+            .collect();
+        let err = batch(State(test_state()), HeaderMap::new(), Json(MessagesBatchRequest { requests }))
+            .await
+            .expect_err("batch over the cap rejected");
+        assert!(matches!(err, ApiError::BadRequest(_)));
+        assert_eq!(err.to_string(), "a batch may hold at most 100 requests, got 101");
+    }
+
+    #[test]
+    fn converts_responses_function_calls_to_tool_use() {
+        let response = serde_json::json!({
+            "output": [
+                { "type": "reasoning", "summary": [] },
+                {
+                    "type": "message",
+                    "content": [{ "type": "output_text", "text": "Checking the weather." }]
+                },
+                {
+                    "type": "function_call",
+                    "id": "fc_1",
+                    "call_id": "call_abc",
+                    "name": "get_weather",
+                    "arguments": "{\"city\":\"Paris\"}"
+                }
+            ],
+            "usage": { "input_tokens": 10, "output_tokens": 5 }
+        });
+
+        let out = translate_responses_to_anthropic(&response, "claude-sonnet-4");
+        assert_eq!(out["stop_reason"], "tool_use");
+        let content = out["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0], serde_json::json!({ "type": "text", "text": "Checking the weather." }));
+        assert_eq!(
+            content[1],
+            serde_json::json!({ "type": "tool_use", "id": "call_abc", "name": "get_weather", "input": { "city": "Paris" } })
+        );
+    }
+
     #[test]
     fn resolves_versioned_claude_aliases() {
         assert_eq!(resolve_model_alias("claude-sonnet-4-20250514"), "gpt-5.1-codex");
@@ -692,10 +1250,53 @@ mod tests {
         assert_eq!(out[0].content.as_str(), Some("sys-1\n\nsys-2"));
     }
 
+    #[test]
+    fn translate_messages_keeps_interleaved_system_in_place() {
+        let messages: Vec<AnthropicMessage> = serde_json::from_value(serde_json::json!([
+            { "role": "user", "content": "first" },
+            { "role": "system", "content": [{ "type": "text", "text": "reminder-1" }, { "type": "text", "text": "reminder-2" }] },
+            { "role": "assistant", "content": "ok" },
+            { "role": "system", "content": "" },
+            { "role": "user", "content": "second" }
+        ]))
+        .unwrap();
+        let out = translate_messages(&messages, Some(serde_json::json!("top")));
+        let roles: Vec<&str> = out.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["system", "user", "system", "assistant", "user"]);
+        assert_eq!(out[0].content, "top");
+        assert_eq!(out[2].content, "reminder-1\n\nreminder-2");
+    }
+
+    #[test]
+    fn anthropic_messages_round_trip_through_role_tag() {
+        let raw = serde_json::json!([
+            { "role": "user", "content": "hi" },
+            { "role": "assistant", "content": [{ "type": "text", "text": "hello" }] }
+        ]);
+        let messages: Vec<AnthropicMessage> = serde_json::from_value(raw.clone()).unwrap();
+        assert!(matches!(messages[0], AnthropicMessage::User(_)));
+        assert!(matches!(messages[1], AnthropicMessage::Assistant(_)));
+        assert_eq!(serde_json::to_value(&messages).unwrap(), raw);
+    }
+
+    #[test]
+    fn request_body_messages_carry_role_exactly_once() {
+        let body = r#"{"model":"gpt-4o","max_tokens":16,"messages":[
+            {"role":"user","content":"hi"},
+            {"role":"system","content":"reminder"},
+            {"role":"assistant","content":"hello"}
+        ]}"#;
+        let payload: AnthropicMessagesPayload = serde_json::from_str(body).expect("role is consumed by the tag");
+        assert_eq!(payload.messages.len(), 3);
+        for message in &payload.messages {
+            let serialized = serde_json::to_string(message).unwrap();
+            assert_eq!(serialized.matches("\"role\"").count(), 1, "{serialized}");
+        }
+    }
+
     #[test]
     fn handle_user_message_splits_tool_result() {
         let message = AnthropicUserMessage {
-            role: "user".to_string(),
             content: serde_json::json!([
                 {"type": "tool_result", "tool_use_id": "call-1", "content": "ok"},
                 {"type": "text", "text": "hello"}
@@ -709,12 +1310,50 @@ mod tests {
         assert_eq!(out[1].content.as_str(), Some("hello"));
     }
 
+    #[tokio::test]
+    async fn streaming_failure_before_first_chunk_is_an_sse_error_event() {
+        let failed = Err(ApiError::Unauthorized("Failed to create chat completions: bad credentials".to_string()));
+        let resp = stream_errors(true, failed).expect("converted to a stream");
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        let data = text
+            .strip_prefix("event: error\ndata: ")
+            .and_then(|rest| rest.strip_suffix("\n\n"))
+            .expect("single error frame");
+        let event: serde_json::Value = serde_json::from_str(data).unwrap();
+        assert_eq!(event["type"], "error");
+        assert_eq!(event["error"]["type"], "authentication_error");
+        assert_eq!(event["error"]["message"], "Failed to create chat completions: bad credentials");
+
+        let upstream = Err(ApiError::Upstream("boom".to_string()));
+        assert!(stream_errors(false, upstream).is_err());
+        let invalid = Err(ApiError::BadRequest("messages must not be empty".to_string()));
+        assert!(matches!(stream_errors(true, invalid), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn handle_user_message_flattens_tool_result_blocks() {
+        let message = AnthropicUserMessage {
+            content: serde_json::json!([
+                {"type": "tool_result", "tool_use_id": "call-1", "content": [
+                    {"type": "text", "text": "line one"},
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}},
+                    {"type": "text", "text": "line two"}
+                ]}
+            ]),
+        };
+        let out = handle_user_message(&message);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].role, "tool");
+        assert_eq!(out[0].content, serde_json::json!("line one\n[image]\nline two"));
+    }
+
     #[tokio::test]
     async fn count_tokens_applies_claude_overhead_and_multiplier() {
         let payload = AnthropicMessagesPayload {
             model: "claude-3.5-sonnet".to_string(),
             messages: vec![AnthropicMessage::User(AnthropicUserMessage {
-                role: "user".to_string(),
                 content: serde_json::json!("Hello"),
             })],
             max_tokens: 16,
@@ -754,6 +1393,55 @@ mod tests {
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn translate_to_openai_honors_anthropic_tool_choice_none() {
+        let payload = AnthropicMessagesPayload {
+            model: "claude-3.5-sonnet".to_string(),
+            messages: vec![AnthropicMessage::User(AnthropicUserMessage {
+                content: serde_json::json!("Hello"),
+            })],
+            max_tokens: 16,
+            system: None,
+            metadata: None,
+            stop_sequences: None,
+            stream: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            tools: Some(vec![AnthropicTool {
+                name: "doit".to_string(),
+                description: None,
+                input_schema: serde_json::json!({"type": "object"}),
+            }]),
+            tool_choice: Some(serde_json::json!({"type": "none"})),
+        };
+
+        let out = translate_to_openai(&payload);
+        assert!(out.tools.is_none());
+        assert!(out.tool_choice.is_none());
+    }
+
+    #[tokio::test]
+    async fn handle_rejects_empty_messages() {
+        let payload = AnthropicMessagesPayload {
+            model: "claude-3.5-sonnet".to_string(),
+            messages: vec![],
+            max_tokens: 16,
+            system: None,
+            metadata: None,
+            stop_sequences: None,
+            stream: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            tools: None,
+            tool_choice: None,
+        };
+
+        let err = handle(State(test_state()), HeaderMap::new(), Json(payload)).await.expect_err("empty messages rejected");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
     #[test]
     fn map_content_builds_image_data_url() {
         let blocks = vec![
@@ -775,6 +1463,116 @@ mod tests {
             .unwrap_or("");
         assert_eq!(url, "data:image/png;base64,abcd");
     }
+
+    fn document_payload(model: &str, source: serde_json::Value) -> AnthropicMessagesPayload {
+        AnthropicMessagesPayload {
+            model: model.to_string(),
+            messages: vec![AnthropicMessage::User(AnthropicUserMessage {
+                content: serde_json::json!([
+                    {"type": "document", "title": "spec.pdf", "source": source},
+                    {"type": "text", "text": "Summarize this"}
+                ]),
+            })],
+            max_tokens: 16,
+            system: None,
+            metadata: None,
+            stop_sequences: None,
+            stream: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            tools: None,
+            tool_choice: None,
+        }
+    }
+
+    #[test]
+    fn document_block_maps_to_file_part() {
+        let payload = document_payload("gpt-4o", serde_json::json!({"type": "base64", "media_type": "application/pdf", "data": "JVBERi0x"}));
+        assert!(validate_documents(&payload, false).is_ok());
+
+        let out = translate_to_openai(&payload);
+        let parts = out.messages[0].content.as_array().expect("array content");
+        assert_eq!(parts[0], serde_json::json!({
+            "type": "file",
+            "file": {"filename": "spec.pdf", "file_data": "data:application/pdf;base64,JVBERi0x"}
+        }));
+        assert_eq!(parts[1], serde_json::json!({"type": "text", "text": "Summarize this"}));
+    }
+
+    #[test]
+    fn timeout_header_reaches_chat_and_responses_payloads() {
+        let mut out = translate_to_openai(&document_payload("gpt-4o", serde_json::json!({"type": "text", "data": "notes"})));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-copilot-timeout", "90".parse().unwrap());
+        apply_request_headers(&mut out, &headers);
+        assert_eq!(out.timeout, Some(std::time::Duration::from_secs(90)));
+
+        let responses = crate::routes::responses::chat_to_responses_payload(&out).expect("responses payload");
+        assert_eq!(responses.timeout, Some(std::time::Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn initiator_header_is_carried_to_the_translated_payload() {
+        let mut out = translate_to_openai(&document_payload("gpt-4o", serde_json::json!({"type": "text", "data": "notes"})));
+        apply_request_headers(&mut out, &HeaderMap::new());
+        assert_eq!(out.initiator, None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-initiator", "Agent".parse().unwrap());
+        apply_request_headers(&mut out, &headers);
+        assert_eq!(out.initiator.as_deref(), Some("agent"));
+
+        headers.insert("x-initiator", "bogus".parse().unwrap());
+        apply_request_headers(&mut out, &headers);
+        assert_eq!(out.initiator, None);
+    }
+
+    #[test]
+    fn unsupported_document_sources_are_rejected() {
+        let text = document_payload("gpt-4o", serde_json::json!({"type": "text", "media_type": "text/plain", "data": "plain notes"}));
+        assert!(validate_documents(&text, false).is_ok());
+        let out = translate_to_openai(&text);
+        assert_eq!(out.messages[0].content[0], serde_json::json!({"type": "text", "text": "plain notes"}));
+
+        let url = document_payload("gpt-4o", serde_json::json!({"type": "url", "url": "https://example.com/a.pdf"}));
+        let err = validate_documents(&url, false).expect_err("url source rejected");
+        assert_eq!(err.to_string(), "document source type url is not supported; send the document as base64");
+
+        let pdf = document_payload("claude-opus-4.5", serde_json::json!({"type": "base64", "media_type": "application/pdf", "data": "JVBERi0x"}));
+        let err = validate_documents(&pdf, true).expect_err("responses bridge rejects PDFs");
+        assert_eq!(err.to_string(), "PDF document blocks are not supported for gpt-5.2-codex");
+    }
+
+    #[test]
+    fn maps_finish_reasons_to_anthropic_stop_reasons() {
+        let cases = [
+            (serde_json::json!({"finish_reason": "stop", "message": {"content": "hi"}}), "end_turn", None),
+            (serde_json::json!({"finish_reason": "length"}), "max_tokens", None),
+            (serde_json::json!({"finish_reason": "tool_calls"}), "tool_use", None),
+            (serde_json::json!({"finish_reason": "content_filter"}), "refusal", None),
+            (serde_json::json!({"finish_reason": "stop", "message": {"content": null, "refusal": "I can't help with that."}}), "refusal", None),
+            (serde_json::json!({"finish_reason": "stop", "stop_reason": "END"}), "stop_sequence", Some("END".to_string())),
+            (serde_json::json!({"finish_reason": "stop", "matched_stop": "\n\n"}), "stop_sequence", Some("\n\n".to_string())),
+        ];
+        for (choice, reason, sequence) in cases {
+            assert_eq!(anthropic_stop(&choice), (reason, sequence), "{choice}");
+        }
+    }
+
+    #[test]
+    fn translate_to_anthropic_reports_stop_sequence() {
+        let response = serde_json::json!({
+            "choices": [{
+                "message": {"role": "assistant", "content": "partial"},
+                "finish_reason": "stop",
+                "stop_reason": "###"
+            }]
+        });
+        let out = translate_to_anthropic(&response, "claude-sonnet-4");
+        assert_eq!(out["stop_reason"], "stop_sequence");
+        assert_eq!(out["stop_sequence"], "###");
+    }
 }
 
 #[derive(Debug, Default)]
@@ -783,6 +1581,39 @@ struct AnthropicStreamState {
     content_block_index: u32,
     content_block_open: bool,
     tool_calls: std::collections::HashMap<u32, ToolCallState>,
+    estimate_output: bool,
+    pending_text: String,
+    deltas_since_usage: u32,
+    estimated_output_tokens: u64,
+}
+
+const USAGE_DELTA_INTERVAL: u32 = 10;
+
+impl AnthropicStreamState {
+    fn record_output(&mut self, text: &str, events: &mut Vec<serde_json::Value>) {
+        if !self.estimate_output {
+            return;
+        }
+        self.pending_text.push_str(text);
+        self.deltas_since_usage += 1;
+        if self.deltas_since_usage < USAGE_DELTA_INTERVAL {
+            return;
+        }
+        self.flush_estimate();
+        self.deltas_since_usage = 0;
+        events.push(serde_json::json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": serde_json::Value::Null, "stop_sequence": serde_json::Value::Null },
+            "usage": { "output_tokens": self.estimated_output_tokens },
+        }));
+    }
+
+    fn flush_estimate(&mut self) {
+        if !self.pending_text.is_empty() {
+            self.estimated_output_tokens += crate::tokenizer::count_text_tokens(&self.pending_text);
+            self.pending_text.clear();
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -800,51 +1631,73 @@ fn is_tool_block_open(state: &AnthropicStreamState) -> bool {
         .any(|tc| tc.anthropic_block_index == state.content_block_index)
 }
 
-fn map_openai_stop_reason(reason: &str) -> &str {
+fn map_openai_stop_reason(reason: &str) -> &'static str {
     match reason {
         "length" => "max_tokens",
-        "tool_calls" => "tool_use",
-        "content_filter" => "content_filter",
+        "tool_calls" | "function_call" => "tool_use",
+        "content_filter" => "refusal",
         _ => "end_turn",
     }
 }
 
-fn anthropic_error_event() -> serde_json::Value {
+/// Anthropic `stop_reason` and `stop_sequence` for a finished choice. OpenAI
+/// reports stop strings and refusals as plain "stop", so the matched string
+/// (`stop_reason`/`matched_stop`, as vLLM-style backends send it) and the
+/// message `refusal` field are consulted too.
+fn anthropic_stop(choice: &serde_json::Value) -> (&'static str, Option<String>) {
+    let reason = choice.get("finish_reason").and_then(|v| v.as_str()).unwrap_or("stop");
+    if reason != "stop" {
+        return (map_openai_stop_reason(reason), None);
+    }
+    let matched = ["stop_reason", "matched_stop"]
+        .iter()
+        .find_map(|key| choice.get(*key).and_then(|v| v.as_str()))
+        .filter(|s| !s.is_empty());
+    if let Some(sequence) = matched {
+        return ("stop_sequence", Some(sequence.to_string()));
+    }
+    let refused = ["message", "delta"]
+        .iter()
+        .filter_map(|key| choice.get(*key))
+        .any(|m| m.get("refusal").and_then(|r| r.as_str()).is_some_and(|r| !r.is_empty()));
+    (if refused { "refusal" } else { "end_turn" }, None)
+}
+
+fn anthropic_error_event(error_type: &str, message: &str) -> serde_json::Value {
     serde_json::json!({
         "type": "error",
         "error": {
-            "type": "api_error",
-            "message": "An unexpected error occurred during streaming."
+            "type": error_type,
+            "message": message
         }
     })
 }
 
-fn find_double_newline(buf: &[u8]) -> Option<usize> {
-    buf.windows(2).position(|w| w == b"\n\n")
+#[derive(Debug, Default, Clone, Copy)]
+struct UsageTokens {
+    input_tokens: u64,
+    output_tokens: u64,
+    cache_read_tokens: Option<u64>,
+    cache_creation_tokens: Option<u64>,
 }
 
-fn drain_sse_blocks(buffer: &mut Vec<u8>) -> Vec<String> {
-    let mut blocks = Vec::new();
-    while let Some(pos) = find_double_newline(buffer) {
-        let block = buffer.drain(..pos + 2).collect::<Vec<u8>>();
-        blocks.push(String::from_utf8_lossy(&block).to_string());
-    }
-    blocks
-}
-
-fn extract_sse_data(block: &str) -> Option<String> {
-    let lines: Vec<&str> = block
-        .lines()
-        .filter_map(|line| line.strip_prefix("data: "))
-        .collect();
-    if lines.is_empty() {
-        None
-    } else {
-        Some(lines.join("\n"))
+impl UsageTokens {
+    fn to_anthropic(self, output_tokens: u64) -> serde_json::Value {
+        let mut usage = serde_json::json!({
+            "input_tokens": self.input_tokens,
+            "output_tokens": output_tokens,
+        });
+        if let Some(cached) = self.cache_read_tokens {
+            usage["cache_read_input_tokens"] = serde_json::Value::from(cached);
+        }
+        if let Some(created) = self.cache_creation_tokens {
+            usage["cache_creation_input_tokens"] = serde_json::Value::from(created);
+        }
+        usage
     }
 }
 
-fn extract_usage(chunk: &serde_json::Value) -> (u64, u64, Option<u64>) {
+fn extract_usage(chunk: &serde_json::Value) -> UsageTokens {
     let usage = chunk.get("usage");
     let prompt_tokens = usage
         .and_then(|u| u.get("prompt_tokens"))
@@ -854,16 +1707,27 @@ fn extract_usage(chunk: &serde_json::Value) -> (u64, u64, Option<u64>) {
         .and_then(|u| u.get("completion_tokens"))
         .and_then(|v| v.as_u64())
         .unwrap_or(0);
-    let cached_tokens = usage
-        .and_then(|u| u.get("prompt_tokens_details"))
+    let details = usage.and_then(|u| u.get("prompt_tokens_details"));
+    let cached_tokens = details
         .and_then(|d| d.get("cached_tokens"))
         .and_then(|v| v.as_u64());
+    // Copilot reports creation either under the details object or flattened on usage.
+    let creation_tokens = details
+        .and_then(|d| d.get("cache_creation_tokens"))
+        .or_else(|| usage.and_then(|u| u.get("cache_creation_input_tokens")))
+        .and_then(|v| v.as_u64());
 
-    let input_tokens = cached_tokens
-        .map(|c| prompt_tokens.saturating_sub(c))
-        .unwrap_or(prompt_tokens);
+    // Anthropic's input_tokens excludes both cache reads and cache writes.
+    let input_tokens = prompt_tokens
+        .saturating_sub(cached_tokens.unwrap_or(0))
+        .saturating_sub(creation_tokens.unwrap_or(0));
 
-    (input_tokens, completion_tokens, cached_tokens)
+    UsageTokens {
+        input_tokens,
+        output_tokens: completion_tokens,
+        cache_read_tokens: cached_tokens,
+        cache_creation_tokens: creation_tokens,
+    }
 }
 
 fn translate_chunk_to_anthropic_events(
@@ -880,14 +1744,7 @@ fn translate_chunk_to_anthropic_events(
     let delta = choice.get("delta").cloned().unwrap_or(serde_json::json!({}));
 
     if !state.message_start_sent {
-        let (input_tokens, _output_tokens, cached_tokens) = extract_usage(chunk);
-        let mut usage = serde_json::json!({
-            "input_tokens": input_tokens,
-            "output_tokens": 0,
-        });
-        if let Some(cached) = cached_tokens {
-            usage["cache_read_input_tokens"] = serde_json::Value::from(cached);
-        }
+        let usage = extract_usage(chunk).to_anthropic(0);
 
         events.push(serde_json::json!({
             "type": "message_start",
@@ -929,6 +1786,7 @@ fn translate_chunk_to_anthropic_events(
             "index": state.content_block_index,
             "delta": { "type": "text_delta", "text": content },
         }));
+        state.record_output(content, &mut events);
     }
 
     if let Some(tool_calls) = delta.get("tool_calls").and_then(|v| v.as_array()) {
@@ -979,12 +1837,13 @@ fn translate_chunk_to_anthropic_events(
                         "index": info.anthropic_block_index,
                         "delta": { "type": "input_json_delta", "partial_json": args },
                     }));
+                    state.record_output(args, &mut events);
                 }
             }
         }
     }
 
-    if let Some(reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
+    if choice.get("finish_reason").is_some_and(|v| v.is_string()) {
         if state.content_block_open {
             events.push(serde_json::json!({
                 "type": "content_block_stop",
@@ -993,18 +1852,19 @@ fn translate_chunk_to_anthropic_events(
             state.content_block_open = false;
         }
 
-        let (input_tokens, output_tokens, cached_tokens) = extract_usage(chunk);
-        let mut usage = serde_json::json!({
-            "input_tokens": input_tokens,
-            "output_tokens": output_tokens,
-        });
-        if let Some(cached) = cached_tokens {
-            usage["cache_read_input_tokens"] = serde_json::Value::from(cached);
-        }
+        let usage = extract_usage(chunk);
+        let output_tokens = if usage.output_tokens == 0 && state.estimate_output {
+            state.flush_estimate();
+            state.estimated_output_tokens
+        } else {
+            usage.output_tokens
+        };
+        let usage = usage.to_anthropic(output_tokens);
+        let (stop_reason, stop_sequence) = anthropic_stop(choice);
 
         events.push(serde_json::json!({
             "type": "message_delta",
-            "delta": { "stop_reason": map_openai_stop_reason(reason), "stop_sequence": serde_json::Value::Null },
+            "delta": { "stop_reason": stop_reason, "stop_sequence": stop_sequence },
             "usage": usage,
         }));
         events.push(serde_json::json!({ "type": "message_stop" }));
@@ -1013,10 +1873,16 @@ fn translate_chunk_to_anthropic_events(
     events
 }
 
-fn stream_anthropic(resp: reqwest::Response) -> axum::response::Response {
-    let stream = resp.bytes_stream();
+fn stream_anthropic(resp: reqwest::Response, upstream_model: &str) -> axum::response::Response {
+    let stream = crate::metrics::tap_usage(
+        crate::services::copilot::chat_completion_stream(resp),
+        upstream_model.to_string(),
+    );
     let out_stream = async_stream::stream! {
-        let mut state = AnthropicStreamState::default();
+        let mut state = AnthropicStreamState {
+            estimate_output: crate::tokenizer::use_precise_tokenizer(),
+            ..Default::default()
+        };
         let mut buffer: Vec<u8> = Vec::new();
         futures::pin_mut!(stream);
         while let Some(chunk) = stream.next().await {
@@ -1039,7 +1905,7 @@ fn stream_anthropic(resp: reqwest::Response) -> axum::response::Response {
                                 }
                             }
                             Err(_) => {
-                                let ev = anthropic_error_event();
+                                let ev = anthropic_error_event("api_error", "An unexpected error occurred during streaming.");
                                 let payload = format!("event: {}\ndata: {}\n\n", ev["type"].as_str().unwrap_or("error"), ev.to_string());
                                 yield Ok(Bytes::from(payload));
                             }
@@ -1053,14 +1919,30 @@ fn stream_anthropic(resp: reqwest::Response) -> axum::response::Response {
     crate::routes::streaming::sse_response(out_stream)
 }
 
-fn stream_anthropic_from_responses(resp: reqwest::Response, model: &str) -> axum::response::Response {
-    let stream = resp.bytes_stream();
-    let model = model.to_string();
-    let out_stream = async_stream::stream! {
+fn stream_anthropic_from_responses(resp: reqwest::Response, model: &str, upstream_model: &str) -> axum::response::Response {
+    let stream = crate::metrics::tap_usage(resp.bytes_stream(), upstream_model.to_string());
+    let stream_reasoning = crate::routes::streaming::stream_reasoning();
+    crate::routes::streaming::sse_response(responses_to_anthropic_stream(stream, model.to_string(), stream_reasoning))
+}
+
+/// Content blocks open lazily so a reasoning summary, when forwarded, lands in
+/// a `thinking` block ahead of the text it precedes.
+fn responses_to_anthropic_stream<S, E>(
+    stream: S,
+    model: String,
+    stream_reasoning: bool,
+) -> impl futures::Stream<Item = Result<Bytes, std::io::Error>>
+where
+    S: futures::Stream<Item = Result<Bytes, E>>,
+{
+    async_stream::stream! {
         futures::pin_mut!(stream);
 
         let mut output_tokens: u64 = 0;
         let mut buffer: Vec<u8> = Vec::new();
+        let mut block_index: u32 = 0;
+        let mut open_block: Option<&'static str> = None;
+        let mut streamed_text = std::collections::HashSet::new();
 
         let message_id = format!("msg_{}", Uuid::new_v4());
         let start = serde_json::json!({
@@ -1078,13 +1960,6 @@ fn stream_anthropic_from_responses(resp: reqwest::Response, model: &str) -> axum
         });
         yield Ok::<Bytes, std::io::Error>(Bytes::from(format!("event: message_start\ndata: {}\n\n", start)));
 
-        let block_start = serde_json::json!({
-            "type": "content_block_start",
-            "index": 0,
-            "content_block": { "type": "text", "text": "" }
-        });
-        yield Ok(Bytes::from(format!("event: content_block_start\ndata: {}\n\n", block_start)));
-
         while let Some(chunk) = stream.next().await {
             if let Ok(bytes) = chunk {
                 buffer.extend_from_slice(&bytes);
@@ -1098,30 +1973,51 @@ fn stream_anthropic_from_responses(resp: reqwest::Response, model: &str) -> axum
                         }
                         match serde_json::from_str::<serde_json::Value>(&data) {
                             Ok(json) => {
-                                if json.get("type") == Some(&serde_json::Value::String("response.output_text.delta".to_string())) {
-                                    if let Some(delta) = json.get("delta").and_then(|v| v.as_str()) {
-                                        let ev = serde_json::json!({
-                                            "type": "content_block_delta",
-                                            "index": 0,
-                                            "delta": { "type": "text_delta", "text": delta }
-                                        });
-                                        yield Ok(Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", ev)));
+                                let event_type = json.get("type").and_then(|t| t.as_str()).or(extract_sse_event(&block));
+                                if event_type == Some("response.output_text.delta") {
+                                    streamed_text.insert(output_text_part(&json));
+                                }
+                                let delta = match event_type {
+                                    Some("response.output_text.delta") => json.get("delta").and_then(|v| v.as_str()).map(|d| ("text", d)),
+                                    Some("response.output_text.done") => json
+                                        .get("text")
+                                        .and_then(|v| v.as_str())
+                                        .filter(|t| !t.is_empty() && !streamed_text.contains(&output_text_part(&json)))
+                                        .map(|t| ("text", t)),
+                                    Some("response.reasoning_summary_text.delta") if stream_reasoning => {
+                                        json.get("delta").and_then(|v| v.as_str()).map(|d| ("thinking", d))
                                     }
+                                    _ => None,
+                                };
+                                if let Some((kind, delta)) = delta {
+                                    for ev in switch_block(&mut open_block, &mut block_index, kind) {
+                                        yield Ok(Bytes::from(format!("event: {}\ndata: {}\n\n", ev["type"].as_str().unwrap_or_default(), ev)));
+                                    }
+                                    let delta = if kind == "thinking" {
+                                        serde_json::json!({ "type": "thinking_delta", "thinking": delta })
+                                    } else {
+                                        serde_json::json!({ "type": "text_delta", "text": delta })
+                                    };
+                                    let ev = serde_json::json!({
+                                        "type": "content_block_delta",
+                                        "index": block_index,
+                                        "delta": delta
+                                    });
+                                    yield Ok(Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", ev)));
                                 }
 
-                                if json.get("type") == Some(&serde_json::Value::String("response.completed".to_string())) {
-                                    if let Some(tokens) = json
+                                if event_type == Some("response.completed")
+                                    && let Some(tokens) = json
                                         .get("response")
                                         .and_then(|r| r.get("usage"))
                                         .and_then(|u| u.get("output_tokens"))
                                         .and_then(|v| v.as_u64())
-                                    {
-                                        output_tokens = tokens;
-                                    }
+                                {
+                                    output_tokens = tokens;
                                 }
                             }
                             Err(_) => {
-                                let ev = anthropic_error_event();
+                                let ev = anthropic_error_event("api_error", "An unexpected error occurred during streaming.");
                                 let payload = format!("event: {}\ndata: {}\n\n", ev["type"].as_str().unwrap_or("error"), ev.to_string());
                                 yield Ok(Bytes::from(payload));
                             }
@@ -1131,7 +2027,12 @@ fn stream_anthropic_from_responses(resp: reqwest::Response, model: &str) -> axum
             }
         }
 
-        let block_stop = serde_json::json!({ "type": "content_block_stop", "index": 0 });
+        if open_block.is_none() {
+            for ev in switch_block(&mut open_block, &mut block_index, "text") {
+                yield Ok(Bytes::from(format!("event: {}\ndata: {}\n\n", ev["type"].as_str().unwrap_or_default(), ev)));
+            }
+        }
+        let block_stop = serde_json::json!({ "type": "content_block_stop", "index": block_index });
         yield Ok(Bytes::from(format!("event: content_block_stop\ndata: {}\n\n", block_stop)));
 
         let delta = serde_json::json!({
@@ -1143,30 +2044,77 @@ fn stream_anthropic_from_responses(resp: reqwest::Response, model: &str) -> axum
 
         let stop = serde_json::json!({ "type": "message_stop" });
         yield Ok(Bytes::from(format!("event: message_stop\ndata: {}\n\n", stop)));
-    };
+    }
+}
 
-    crate::routes::streaming::sse_response(out_stream)
+/// Events that close the open content block (if it is a different kind) and
+/// start a `kind` block ("text" or "thinking").
+fn switch_block(open_block: &mut Option<&'static str>, index: &mut u32, kind: &'static str) -> Vec<serde_json::Value> {
+    let mut events = Vec::new();
+    if *open_block == Some(kind) {
+        return events;
+    }
+    if open_block.is_some() {
+        events.push(serde_json::json!({ "type": "content_block_stop", "index": *index }));
+        *index += 1;
+    }
+    let content_block = if kind == "thinking" {
+        serde_json::json!({ "type": "thinking", "thinking": "" })
+    } else {
+        serde_json::json!({ "type": "text", "text": "" })
+    };
+    events.push(serde_json::json!({ "type": "content_block_start", "index": *index, "content_block": content_block }));
+    *open_block = Some(kind);
+    events
 }
 
 fn translate_responses_to_anthropic(response: &serde_json::Value, model: &str) -> serde_json::Value {
-    let output_text = response
-        .get("output")
-        .and_then(|o| o.as_array())
-        .and_then(|arr| arr.iter().find(|x| x.get("type") == Some(&serde_json::Value::String("message".to_string()))))
-        .and_then(|msg| msg.get("content"))
-        .and_then(|c| c.as_array())
-        .and_then(|arr| arr.iter().find(|x| x.get("type") == Some(&serde_json::Value::String("output_text".to_string()))))
-        .and_then(|t| t.get("text"))
-        .and_then(|t| t.as_str())
-        .unwrap_or("");
+    let mut text_blocks = Vec::new();
+    let mut tool_blocks = Vec::new();
+    for item in response.get("output").and_then(|o| o.as_array()).into_iter().flatten() {
+        match item.get("type").and_then(|t| t.as_str()) {
+            Some("message") => {
+                for part in item.get("content").and_then(|c| c.as_array()).into_iter().flatten() {
+                    if part.get("type").and_then(|t| t.as_str()) != Some("output_text") {
+                        continue;
+                    }
+                    let text = part.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                    text_blocks.push(serde_json::json!({ "type": "text", "text": text }));
+                }
+            }
+            Some("function_call") => {
+                let id = item
+                    .get("call_id")
+                    .or_else(|| item.get("id"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let name = item.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                let arguments = item.get("arguments").and_then(|v| v.as_str()).unwrap_or("{}");
+                let input = serde_json::from_str::<serde_json::Value>(arguments).unwrap_or(serde_json::json!({}));
+                tool_blocks.push(serde_json::json!({
+                    "type": "tool_use",
+                    "id": id,
+                    "name": name,
+                    "input": input,
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    let stop_reason = if tool_blocks.is_empty() { "end_turn" } else { "tool_use" };
+    if text_blocks.is_empty() && tool_blocks.is_empty() {
+        text_blocks.push(serde_json::json!({ "type": "text", "text": "" }));
+    }
+    text_blocks.extend(tool_blocks);
 
     serde_json::json!({
         "id": format!("msg_{}", Uuid::new_v4()),
         "type": "message",
         "role": "assistant",
-        "content": [{ "type": "text", "text": output_text }],
+        "content": text_blocks,
         "model": model,
-        "stop_reason": "end_turn",
+        "stop_reason": stop_reason,
         "stop_sequence": null,
         "usage": response.get("usage").cloned().unwrap_or(serde_json::json!({}))
     })
@@ -1,19 +1,80 @@
-use axum::{extract::State, response::IntoResponse, Json};
+use axum::{extract::{Request, State}, http::{HeaderMap, StatusCode}, middleware::Next, response::{IntoResponse, Response}, Json};
+use bytes::Bytes;
+use futures::Stream;
 
 use crate::{
     approval::check_manual_approval,
     auth_flow::{ensure_copilot_token, ensure_github_token},
     errors::{ApiError, ApiResult},
     rate_limit::check_rate_limit,
-    services::{copilot::EmbeddingRequest, azure, openai},
+    routes::models::cached_models,
+    services::{copilot::{get_models, parse_json, EmbeddingRequest}, azure, openai},
     services::github::get_copilot_usage,
-    state::AppState,
+    state::{AppConfig, AppState, ModelsResponse},
 };
 
 pub async fn root() -> impl IntoResponse {
     "Server running"
 }
 
+/// Ready once the prewarm has a Copilot token and model list; other providers
+/// need neither.
+pub fn is_ready(provider: &str, config: &AppConfig) -> bool {
+    provider != "copilot" || (config.copilot_token.is_some() && config.models.is_some())
+}
+
+fn provider() -> String {
+    std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string())
+}
+
+pub async fn health_live() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "live" }))
+}
+
+/// 200 when ready, 503 while the token/models prewarm is still pending.
+pub async fn health(State(state): State<AppState>) -> Response {
+    let config = state.config.read().await;
+    let ready = is_ready(&provider(), &config);
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "starting" },
+        "live": true,
+        "ready": ready,
+        "token": config.copilot_token.is_some(),
+        "models": config.models.is_some(),
+    });
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(body)).into_response()
+}
+
+/// Polls until [`is_ready`] holds or `timeout` passes; returns whether it got ready.
+pub async fn wait_ready(state: &AppState, timeout: std::time::Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let provider = provider();
+    loop {
+        if is_ready(&provider, &*state.config.read().await) {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+}
+
+pub async fn debug_stream() -> Response {
+    crate::routes::streaming::sse_response(tick_stream(5, std::time::Duration::from_secs(1)))
+}
+
+fn tick_stream(count: u32, interval: std::time::Duration) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+    async_stream::stream! {
+        for n in 1..=count {
+            tokio::time::sleep(interval).await;
+            yield Ok::<Bytes, std::io::Error>(Bytes::from(format!("data: tick {}\n\n", n)));
+        }
+        yield Ok(Bytes::from("data: [DONE]\n\n"));
+    }
+}
+
 pub async fn token(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
     let token = ensure_copilot_token(&state).await?;
     Ok(Json(serde_json::json!({
@@ -21,6 +82,42 @@ pub async fn token(State(state): State<AppState>) -> ApiResult<impl IntoResponse
     })))
 }
 
+pub async fn reload_hooks(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
+    let hooks = state.hooks.as_ref().ok_or_else(|| ApiError::NotFound("hooks are disabled".to_string()))?;
+    let summary = hooks.reload().await?;
+    tracing::info!("Reloaded {} ({} events, {} entries)", hooks.path.display(), summary.events, summary.entries);
+    Ok(Json(serde_json::json!({
+        "path": hooks.path.display().to_string(),
+        "events": summary.events,
+        "entries": summary.entries,
+    })))
+}
+
+/// Gates every route it wraps on COPILOT_API_KEY; a no-op when no key is set.
+pub async fn require_api_key(State(expected): State<Option<String>>, req: Request, next: Next) -> Response {
+    match check_api_key(req.headers(), expected.as_deref()) {
+        Ok(()) => next.run(req).await,
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Accepts `Authorization: Bearer <key>` or `x-api-key: <key>` when a key is configured.
+fn check_api_key(headers: &HeaderMap, expected: Option<&str>) -> ApiResult<()> {
+    let Some(expected) = expected.filter(|key| !key.is_empty()) else {
+        return Ok(());
+    };
+    let bearer = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let api_key = headers.get("x-api-key").and_then(|v| v.to_str().ok());
+    if bearer == Some(expected) || api_key == Some(expected) {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized("invalid API key".to_string()))
+    }
+}
+
 pub async fn usage(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
     let github_token = ensure_github_token(&state).await?;
     let config = state.config.read().await.clone();
@@ -33,7 +130,7 @@ pub async fn embeddings(
     Json(payload): Json<EmbeddingRequest>,
 ) -> ApiResult<impl IntoResponse> {
     check_manual_approval(&state).await?;
-    check_rate_limit(&state).await?;
+    check_rate_limit(&state, &payload.model).await?;
     let provider = std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string());
 
     if provider == "azure" || payload.model.starts_with("azure:") {
@@ -43,7 +140,7 @@ pub async fn embeddings(
                 azure_payload.model = cfg.deployment.clone();
             }
             let resp = azure::create_embeddings(&state.client, &cfg, &serde_json::to_value(&azure_payload).unwrap()).await?;
-            let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid Azure embeddings response: {e}")))?;
+            let json: serde_json::Value = parse_json(resp, "Invalid Azure embeddings response").await?;
             return Ok(Json(json));
         }
     }
@@ -54,22 +151,141 @@ pub async fn embeddings(
             payload.model = payload.model.trim_start_matches("openai:").to_string();
         }
         let resp = openai::create_embeddings(&state.client, &serde_json::to_value(&payload).unwrap()).await?;
-        let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid OpenAI embeddings response: {e}")))?;
+        let json: serde_json::Value = parse_json(resp, "Invalid OpenAI embeddings response").await?;
         return Ok(Json(json));
     }
 
     let token = ensure_copilot_token(&state).await?;
-    let config = state.config.read().await.clone();
+    let mut config = state.config.read().await.clone();
 
-    let resp = crate::services::copilot::create_embeddings(&state.client, &config, &token, &payload).await?;
-    let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid embeddings response: {e}")))?;
+    if payload.dimensions.is_some() {
+        if config.models.is_none() {
+            let models = cached_models(&state, |config| {
+                let (client, token) = (&state.client, &token);
+                async move { get_models(client, &config, token).await }
+            })
+            .await?;
+            config.models = Some(models);
+        }
+        validate_dimensions(&payload, config.models.as_ref())?;
+    }
+
+    let json = embed_in_batches(payload, embed_batch_size(), |batch| {
+        let (client, config, token) = (&state.client, &config, &token);
+        async move {
+            let resp = crate::services::copilot::create_embeddings(client, config, token, &batch).await?;
+            parse_json::<serde_json::Value>(resp, "Invalid embeddings response").await
+        }
+    })
+    .await?;
     Ok(Json(json))
 }
 
+fn embed_batch_size() -> usize {
+    std::env::var("COPILOT_EMBED_BATCH")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(96)
+}
+
+/// Splits a list of inputs larger than `batch_size` into several upstream calls
+/// and merges the results as if they came from a single request. An array of
+/// integers is one pre-tokenized input and is never split.
+async fn embed_in_batches<F, Fut>(
+    payload: EmbeddingRequest,
+    batch_size: usize,
+    mut send: F,
+) -> ApiResult<serde_json::Value>
+where
+    F: FnMut(EmbeddingRequest) -> Fut,
+    Fut: std::future::Future<Output = ApiResult<serde_json::Value>>,
+{
+    let items = match payload.input.as_array() {
+        Some(items) if items.len() > batch_size && items.iter().all(|item| item.is_string() || item.is_array()) => {
+            items.clone()
+        }
+        _ => return send(payload).await,
+    };
+
+    let mut merged: Option<serde_json::Value> = None;
+    let mut data = Vec::with_capacity(items.len());
+    let mut prompt_tokens = 0;
+    let mut total_tokens = 0;
+    for (batch_index, chunk) in items.chunks(batch_size).enumerate() {
+        let offset = batch_index * batch_size;
+        let batch = EmbeddingRequest {
+            input: serde_json::Value::Array(chunk.to_vec()),
+            ..payload.clone()
+        };
+        let mut json = send(batch).await?;
+
+        let entries = json
+            .get_mut("data")
+            .and_then(|v| v.as_array_mut())
+            .map(std::mem::take)
+            .unwrap_or_default();
+        for mut entry in entries {
+            let index = entry.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            entry["index"] = serde_json::json!(offset + index);
+            data.push(entry);
+        }
+        if let Some(usage) = json.get("usage") {
+            prompt_tokens += usage.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+            total_tokens += usage.get("total_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+        }
+        merged.get_or_insert(json);
+    }
+
+    let mut merged = merged.unwrap_or_else(|| serde_json::json!({ "object": "list" }));
+    merged["data"] = serde_json::Value::Array(data);
+    merged["usage"] = serde_json::json!({
+        "prompt_tokens": prompt_tokens,
+        "total_tokens": total_tokens,
+    });
+    Ok(merged)
+}
+
+fn validate_dimensions(payload: &EmbeddingRequest, models: Option<&ModelsResponse>) -> ApiResult<()> {
+    if payload.dimensions.is_none() {
+        return Ok(());
+    }
+    let supported = models
+        .and_then(|m| m.data.iter().find(|model| model.id == payload.model))
+        .and_then(|model| model.capabilities.supports.dimensions)
+        .unwrap_or(false);
+    if supported {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest(format!("Model {} does not support dimensions", payload.model)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::root;
+    use super::{check_api_key, embed_in_batches, is_ready, root, tick_stream, validate_dimensions};
+    use crate::services::copilot::EmbeddingRequest;
+    use crate::state::ModelsResponse;
     use axum::response::IntoResponse;
+    use futures::StreamExt;
+
+    #[test]
+    fn api_key_gates_admin_routes_when_configured() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert!(check_api_key(&headers, None).is_ok());
+        assert!(check_api_key(&headers, Some("")).is_ok());
+
+        let err = check_api_key(&headers, Some("secret")).expect_err("missing key rejected");
+        assert_eq!(err.status_code(), axum::http::StatusCode::UNAUTHORIZED);
+        headers.insert("authorization", "Bearer wrong".parse().unwrap());
+        assert!(check_api_key(&headers, Some("secret")).is_err());
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(check_api_key(&headers, Some("secret")).is_ok());
+
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        assert!(check_api_key(&headers, Some("secret")).is_ok());
+    }
 
     #[tokio::test]
     async fn root_is_alive() {
@@ -79,4 +295,158 @@ mod tests {
             .expect("body bytes");
         assert_eq!(bytes, "Server running");
     }
+
+    #[tokio::test]
+    async fn tick_stream_emits_ticks_then_done() {
+        let chunks: Vec<_> = tick_stream(3, std::time::Duration::ZERO).collect().await;
+        let body = chunks
+            .into_iter()
+            .map(|c| String::from_utf8_lossy(&c.expect("chunk")).to_string())
+            .collect::<String>();
+        assert_eq!(body, "data: tick 1\n\ndata: tick 2\n\ndata: tick 3\n\ndata: [DONE]\n\n");
+    }
+
+    fn embedding_models() -> ModelsResponse {
+        let mut with_dims = crate::routes::models::default_model();
+        with_dims.id = "text-embedding-3-small".to_string();
+        with_dims.capabilities.supports.dimensions = Some(true);
+        let mut without_dims = crate::routes::models::default_model();
+        without_dims.id = "text-embedding-ada-002".to_string();
+        ModelsResponse {
+            data: vec![with_dims, without_dims],
+            object: "list".to_string(),
+        }
+    }
+
+    fn embedding_request(model: &str, dimensions: Option<u32>) -> EmbeddingRequest {
+        EmbeddingRequest {
+            input: serde_json::json!("hello"),
+            model: model.to_string(),
+            dimensions,
+            encoding_format: None,
+        }
+    }
+
+    #[test]
+    fn dimensions_allowed_for_supporting_model() {
+        let models = embedding_models();
+        let payload = embedding_request("text-embedding-3-small", Some(256));
+        assert!(validate_dimensions(&payload, Some(&models)).is_ok());
+    }
+
+    #[test]
+    fn dimensions_rejected_for_unsupported_model() {
+        let models = embedding_models();
+        let payload = embedding_request("text-embedding-ada-002", Some(256));
+        assert!(validate_dimensions(&payload, Some(&models)).is_err());
+        assert!(validate_dimensions(&embedding_request("text-embedding-ada-002", None), Some(&models)).is_ok());
+    }
+
+    #[test]
+    fn embedding_request_serializes_optional_fields_only_when_present() {
+        let mut payload = embedding_request("text-embedding-3-small", Some(256));
+        payload.encoding_format = Some("base64".to_string());
+        let json = serde_json::to_value(&payload).expect("json");
+        assert_eq!(json.get("dimensions").and_then(|v| v.as_u64()), Some(256));
+        assert_eq!(json.get("encoding_format").and_then(|v| v.as_str()), Some("base64"));
+
+        let json = serde_json::to_value(embedding_request("text-embedding-3-small", None)).expect("json");
+        assert!(json.get("dimensions").is_none());
+        assert!(json.get("encoding_format").is_none());
+    }
+
+    /// Echoes each input back as its embedding, treating a string or an array
+    /// of integers as a single input like OpenAI does.
+    fn fake_embeddings(batch: &EmbeddingRequest) -> serde_json::Value {
+        let inputs = match batch.input.as_array() {
+            Some(items) if items.iter().all(|item| item.is_string() || item.is_array()) => items.clone(),
+            _ => vec![batch.input.clone()],
+        };
+        let items = inputs.len();
+        let data: Vec<_> = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(i, input)| serde_json::json!({ "object": "embedding", "index": i, "embedding": [input] }))
+            .collect();
+        serde_json::json!({
+            "object": "list",
+            "model": batch.model,
+            "data": data,
+            "usage": { "prompt_tokens": items, "total_tokens": items },
+        })
+    }
+
+    #[tokio::test]
+    async fn large_inputs_are_split_and_reindexed() {
+        let mut payload = embedding_request("text-embedding-3-small", None);
+        payload.input = serde_json::json!((0..200).map(|i| format!("item-{i}")).collect::<Vec<_>>());
+
+        let mut batch_sizes = Vec::new();
+        let json = embed_in_batches(payload, 96, |batch| {
+            batch_sizes.push(batch.input.as_array().unwrap().len());
+            let json = fake_embeddings(&batch);
+            async move { Ok(json) }
+        })
+        .await
+        .expect("embeddings");
+
+        assert_eq!(batch_sizes, vec![96, 96, 8]);
+        let data = json["data"].as_array().unwrap();
+        assert_eq!(data.len(), 200);
+        for (i, entry) in data.iter().enumerate() {
+            assert_eq!(entry["index"], i);
+            assert_eq!(entry["embedding"][0], format!("item-{i}"));
+        }
+        assert_eq!(json["usage"]["prompt_tokens"], 200);
+        assert_eq!(json["usage"]["total_tokens"], 200);
+        assert_eq!(json["model"], "text-embedding-3-small");
+    }
+
+    #[tokio::test]
+    async fn long_token_array_is_one_input() {
+        let mut payload = embedding_request("text-embedding-3-small", None);
+        payload.input = serde_json::json!((0..200).collect::<Vec<_>>());
+
+        let mut calls = 0;
+        let json = embed_in_batches(payload, 96, |batch| {
+            calls += 1;
+            let json = fake_embeddings(&batch);
+            async move { Ok(json) }
+        })
+        .await
+        .expect("embeddings");
+
+        assert_eq!(calls, 1);
+        let data = json["data"].as_array().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0]["embedding"][0].as_array().unwrap().len(), 200);
+    }
+
+    #[tokio::test]
+    async fn single_string_input_passes_through() {
+        let mut calls = 0;
+        let json = embed_in_batches(embedding_request("text-embedding-3-small", None), 1, |batch| {
+            calls += 1;
+            let json = fake_embeddings(&batch);
+            async move { Ok(json) }
+        })
+        .await
+        .expect("embeddings");
+
+        assert_eq!(calls, 1);
+        assert_eq!(json["data"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn ready_needs_token_and_models_for_copilot() {
+        let mut config = crate::state::AppConfig::default();
+        assert!(!is_ready("copilot", &config));
+        assert!(is_ready("openai", &config));
+
+        config.copilot_token = Some("tid=1".to_string());
+        assert!(!is_ready("copilot", &config));
+
+        config.models = Some(ModelsResponse { data: vec![], object: "list".to_string() });
+        assert!(is_ready("copilot", &config));
+    }
 }
@@ -1,7 +1,13 @@
+pub mod audio;
 pub mod auth;
 pub mod chat_completions;
+pub mod completions;
+pub mod fallback;
 pub mod messages;
 pub mod models;
+pub mod moderations;
 pub mod responses;
+pub mod sampling;
 pub mod misc;
 pub mod streaming;
+pub mod tool_choice;
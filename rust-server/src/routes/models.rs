@@ -2,15 +2,29 @@ use axum::{extract::State, response::IntoResponse, Json};
 
 use crate::{
     auth_flow::ensure_copilot_token,
-    errors::ApiResult,
+    errors::{ApiError, ApiResult},
     services::{copilot::get_models, openai, azure},
-    state::{AppState, Model},
+    state::{AppConfig, AppState, Model, ModelsResponse},
 };
 
 pub async fn list(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
     let provider = std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string());
+    let allowed = state.config.read().await.allowed_models.clone();
+    if let Some(models) = state.config.read().await.static_models.as_ref() {
+        let data = models.data.iter().map(model_to_openai).collect();
+        return Ok(Json(serde_json::json!({
+            "object": "list",
+            "data": filter_allowed(data, allowed.as_deref()),
+            "has_more": false,
+        })));
+    }
+
     if provider == "openai" {
-        let models = openai::list_models(&state.client).await?;
+        let mut models = openai::list_models(&state.client).await?;
+        if let Some(data) = models.get_mut("data").and_then(|d| d.as_array_mut()) {
+            let filtered = filter_allowed(std::mem::take(data), allowed.as_deref());
+            *data = filtered;
+        }
         return Ok(Json(models));
     }
 
@@ -37,19 +51,48 @@ pub async fn list(State(state): State<AppState>) -> ApiResult<impl IntoResponse>
 
     let token = ensure_copilot_token(&state).await?;
 
-    let models = {
-        let config = state.config.read().await;
-        if let Some(models) = &config.models {
-            models.clone()
-        } else {
-            drop(config);
-            let config_snapshot = state.config.read().await.clone();
-            let models = get_models(&state.client, &config_snapshot, &token).await?;
-            state.config.write().await.models = Some(models.clone());
-            models
-        }
-    };
+    let models = cached_models(&state, |config| {
+        let (client, token) = (&state.client, &token);
+        async move { get_models(client, &config, token).await }
+    })
+    .await?;
+
+    let data = filter_allowed(openai_model_list(&models), allowed.as_deref());
+
+    Ok(Json(serde_json::json!({
+        "object": "list",
+        "data": data,
+        "has_more": false,
+    })))
+}
+
+/// The cached model list, fetched with `fetch` on a miss. Concurrent misses
+/// queue on `models_fetch` and reuse whatever the first caller stored.
+pub(crate) async fn cached_models<F, Fut>(state: &AppState, fetch: F) -> ApiResult<ModelsResponse>
+where
+    F: FnOnce(AppConfig) -> Fut,
+    Fut: std::future::Future<Output = ApiResult<ModelsResponse>>,
+{
+    if let Some(models) = state.config.read().await.models.clone() {
+        return Ok(models);
+    }
+    let _fetching = state.models_fetch.lock().await;
+    let config = state.config.read().await.clone();
+    if let Some(models) = config.models {
+        return Ok(models);
+    }
+    let models = fetch(config).await?;
+    state.config.write().await.models = Some(models.clone());
+    Ok(models)
+}
+
+pub(crate) fn load_models_file(path: &str) -> ApiResult<ModelsResponse> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ApiError::Internal(format!("Failed to read models file {path}: {e}")))?;
+    serde_json::from_str(&content).map_err(|e| ApiError::Internal(format!("Invalid models file {path}: {e}")))
+}
 
+pub(crate) fn openai_model_list(models: &ModelsResponse) -> Vec<serde_json::Value> {
     let mut data: Vec<serde_json::Value> = models
         .data
         .iter()
@@ -70,11 +113,37 @@ pub async fn list(State(state): State<AppState>) -> ApiResult<impl IntoResponse>
         }
     }
 
-    Ok(Json(serde_json::json!({
-        "object": "list",
-        "data": data,
-        "has_more": false,
-    })))
+    data
+}
+
+/// COPILOT_ALLOWED_MODELS: comma-separated model ids (after alias resolution)
+/// the proxy will serve. Unset means everything is allowed.
+pub(crate) fn allowed_models() -> Option<Vec<String>> {
+    let raw = std::env::var("COPILOT_ALLOWED_MODELS").ok()?;
+    let models: Vec<String> = raw.split(',').map(|m| m.trim().to_string()).filter(|m| !m.is_empty()).collect();
+    (!models.is_empty()).then_some(models)
+}
+
+pub(crate) fn check_model_allowed(model: &str, allowed: Option<&[String]>) -> ApiResult<()> {
+    match allowed {
+        Some(allowed) if !allowed.iter().any(|m| m == model) => {
+            Err(ApiError::BadRequest(format!("model not permitted: {model}")))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn filter_allowed(data: Vec<serde_json::Value>, allowed: Option<&[String]>) -> Vec<serde_json::Value> {
+    let Some(allowed) = allowed else {
+        return data;
+    };
+    data.into_iter()
+        .filter(|m| {
+            let id = m.get("id").and_then(|v| v.as_str()).unwrap_or_default();
+            let resolved = crate::services::model_alias::resolve_model_alias(id);
+            allowed.iter().any(|a| a == id || *a == resolved)
+        })
+        .collect()
 }
 
 fn model_to_openai(model: &Model) -> serde_json::Value {
@@ -138,7 +207,9 @@ fn alias(id: &str, target: &str) -> serde_json::Value {
 
 #[cfg(test)]
 mod tests {
-    use super::{alias_models, alias};
+    use super::{alias_models, alias, cached_models, check_model_allowed, default_model, filter_allowed, list, load_models_file};
+    use crate::state::{AppConfig, AppState};
+    use axum::{extract::State, response::IntoResponse};
 
     #[test]
     fn alias_model_display_name() {
@@ -153,9 +224,85 @@ mod tests {
         assert!(aliases.iter().any(|m| m.get("id") == Some(&serde_json::Value::String("o3".to_string()))));
         assert!(aliases.iter().any(|m| m.get("id") == Some(&serde_json::Value::String("claude-4-sonnet".to_string()))));
     }
+
+    #[test]
+    fn allowlist_permits_and_denies_models() {
+        let allowed = vec!["gpt-4o".to_string(), "gpt-5-mini".to_string()];
+        assert!(check_model_allowed("gpt-4o", Some(&allowed)).is_ok());
+        assert!(check_model_allowed("gpt-5.2-codex", None).is_ok());
+
+        let err = check_model_allowed("gpt-5.2-codex", Some(&allowed)).expect_err("premium model denied");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "model not permitted: gpt-5.2-codex");
+    }
+
+    #[test]
+    fn allowlist_filters_model_list() {
+        let data = vec![
+            serde_json::json!({"id": "gpt-4o"}),
+            serde_json::json!({"id": "gpt-5.2-codex"}),
+            serde_json::json!({"id": "claude-3.5-haiku"}),
+        ];
+        let allowed = vec!["gpt-4o".to_string(), "gpt-5-mini".to_string()];
+        let ids: Vec<serde_json::Value> = filter_allowed(data.clone(), Some(&allowed)).into_iter().map(|m| m["id"].clone()).collect();
+        assert_eq!(ids, vec!["gpt-4o", "claude-3.5-haiku"]);
+        assert_eq!(filter_allowed(data, None).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn serves_models_file_without_upstream() {
+        let path = std::env::temp_dir().join(format!("copilot-models-{}.json", std::process::id()));
+        let mut model = serde_json::to_value(super::default_model()).unwrap();
+        model["id"] = serde_json::json!("local-coder");
+        model["name"] = serde_json::json!("Local Coder");
+        model["vendor"] = serde_json::json!("acme");
+        std::fs::write(&path, serde_json::json!({ "object": "list", "data": [model] }).to_string()).unwrap();
+
+        let models = load_models_file(path.to_str().unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let config = AppConfig { static_models: Some(models), ..AppConfig::default() };
+        let state = AppState::new(config, reqwest::Client::new(), None);
+
+        let resp = list(State(state)).await.unwrap().into_response();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let data = json["data"].as_array().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0]["id"], "local-coder");
+        assert_eq!(data[0]["owned_by"], "acme");
+        assert_eq!(data[0]["display_name"], "Local Coder");
+    }
+
+    #[tokio::test]
+    async fn concurrent_cache_misses_fetch_models_once() {
+        let state = crate::state::test_state();
+        let fetches = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let calls = (0..8).map(|_| {
+            let (state, fetches) = (state.clone(), fetches.clone());
+            tokio::spawn(async move {
+                cached_models(&state, |_| async move {
+                    fetches.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    Ok(crate::state::ModelsResponse { data: vec![default_model()], object: "list".to_string() })
+                })
+                .await
+            })
+        });
+        for result in futures::future::join_all(calls).await {
+            assert_eq!(result.unwrap().unwrap().data.len(), 1);
+        }
+        assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(state.config.read().await.models.is_some());
+    }
+
+    #[test]
+    fn invalid_models_file_is_an_error() {
+        assert!(load_models_file("/nonexistent/models.json").is_err());
+    }
 }
 
-fn default_model() -> Model {
+pub(crate) fn default_model() -> Model {
     Model {
         capabilities: crate::state::ModelCapabilities {
             family: "".to_string(),
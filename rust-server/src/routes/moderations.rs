@@ -0,0 +1,90 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::header,
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    approval::check_manual_approval,
+    errors::{ApiError, ApiResult},
+    rate_limit::check_rate_limit,
+    services::{azure, openai},
+    state::AppState,
+};
+
+const DEFAULT_MODERATION_MODEL: &str = "omni-moderation-latest";
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ModerationRequest {
+    pub input: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+pub async fn handle(State(state): State<AppState>, Json(payload): Json<ModerationRequest>) -> ApiResult<Response> {
+    let provider = std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string());
+    check_provider(&provider)?;
+
+    let model = payload.model.clone().unwrap_or_else(|| DEFAULT_MODERATION_MODEL.to_string());
+    check_manual_approval(&state).await?;
+    check_rate_limit(&state, &model).await?;
+
+    let resp = if provider == "azure" {
+        let cfg = azure::load_azure_config(&model)
+            .ok_or_else(|| ApiError::BadRequest("Missing Azure OpenAI configuration".to_string()))?;
+        let mut payload = payload;
+        payload.model = Some(cfg.deployment.clone());
+        azure::create_moderations(&state.client, &cfg, &serde_json::to_value(&payload).unwrap()).await?
+    } else {
+        let mut payload = payload;
+        payload.model = payload.model.map(|m| m.trim_start_matches("openai:").to_string());
+        openai::create_moderations(&state.client, &serde_json::to_value(&payload).unwrap()).await?
+    };
+
+    let status = resp.status();
+    let content_type = resp.headers().get(header::CONTENT_TYPE).cloned();
+    let body = resp
+        .bytes()
+        .await
+        .map_err(|e| ApiError::Upstream(format!("Invalid moderations response: {e}")))?;
+
+    let mut builder = Response::builder().status(status);
+    if let Some(content_type) = content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    builder
+        .body(Body::from(body))
+        .map_err(|e| ApiError::Internal(format!("Failed to build response: {e}")))
+}
+
+fn check_provider(provider: &str) -> ApiResult<()> {
+    match provider {
+        "openai" | "azure" => Ok(()),
+        other => Err(ApiError::BadRequest(format!("moderations not supported for {other} provider"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_provider, ModerationRequest};
+    use crate::errors::ApiError;
+
+    #[test]
+    fn copilot_provider_is_rejected() {
+        match check_provider("copilot") {
+            Err(ApiError::BadRequest(message)) => assert_eq!(message, "moderations not supported for copilot provider"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(check_provider("openai").is_ok());
+        assert!(check_provider("azure").is_ok());
+    }
+
+    #[test]
+    fn request_without_model_omits_it_upstream() {
+        let payload: ModerationRequest = serde_json::from_value(serde_json::json!({ "input": ["a", "b"] })).unwrap();
+        assert_eq!(serde_json::to_value(&payload).unwrap(), serde_json::json!({ "input": ["a", "b"] }));
+    }
+}
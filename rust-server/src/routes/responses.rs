@@ -1,13 +1,14 @@
-use axum::{extract::State, response::{IntoResponse, Response}, Json};
+use axum::{extract::State, http::HeaderMap, response::{IntoResponse, Response}, Json};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     approval::check_manual_approval,
-    auth_flow::ensure_copilot_token,
+    auth_flow::{create_responses_with_retry, ensure_copilot_token},
     errors::{ApiError, ApiResult},
     hooks::types::HookInput,
     rate_limit::check_rate_limit,
-    services::{copilot::{create_responses, ResponsesPayload}, openai, azure},
+    routes::models::check_model_allowed,
+    services::{copilot::{parse_json, rate_limit_headers, request_timeout, ResponsesPayload}, model_alias::resolve_model_alias, openai, azure},
     state::AppState,
 };
 
@@ -43,7 +44,14 @@ pub struct ResponsesResponse {
     pub usage: Option<serde_json::Value>,
 }
 
-pub async fn handle(State(state): State<AppState>, Json(payload): Json<ResponsesPayload>) -> ApiResult<Response> {
+pub async fn handle(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut payload): Json<ResponsesPayload>,
+) -> ApiResult<Response> {
+    validate_input(&payload.input)?;
+    payload.timeout = request_timeout(&headers);
+    dedup_instructions(&mut payload);
     if let Some(hooks) = &state.hooks {
         let input = HookInput {
             hook_type: Some("PreToolUse".to_string()),
@@ -57,13 +65,16 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Responses
             return Err(ApiError::BadRequest("Hook blocked request".to_string()));
         }
     }
+    let resolved_model = resolve_model_alias(&payload.model);
+    check_model_allowed(&resolved_model, state.config.read().await.allowed_models.as_deref())?;
     check_manual_approval(&state).await?;
-    check_rate_limit(&state).await?;
+    check_rate_limit(&state, &resolved_model).await?;
     let provider = std::env::var("COPILOT_PROVIDER").unwrap_or_else(|_| "copilot".to_string());
     if provider == "azure" || payload.model.starts_with("azure:") {
-        if let Some(cfg) = azure::load_azure_config(&payload.model) {
+        let azure_model = azure::resolve_model(&payload.model);
+        if let Some(cfg) = azure::load_azure_config(&azure_model) {
             let mut azure_payload = payload.clone();
-            if azure_payload.model.starts_with("azure:") {
+            if azure_model.starts_with("azure:") {
                 azure_payload.model = cfg.deployment.clone();
             }
             let resp = azure::create_responses(&state.client, &cfg, &serde_json::to_value(&azure_payload).unwrap()).await?;
@@ -81,7 +92,7 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Responses
                 }
                 return Ok(crate::routes::streaming::sse_response(stream));
             }
-            let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid Azure responses payload: {e}")))?;
+            let json: serde_json::Value = parse_json(resp, "Invalid Azure responses payload").await?;
             if let Some(hooks) = &state.hooks {
                 let input = HookInput {
                     hook_type: Some("PostToolUse".to_string()),
@@ -100,6 +111,7 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Responses
         if payload.model.starts_with("openai:") {
             payload.model = payload.model.trim_start_matches("openai:").to_string();
         }
+        payload.model = openai::resolve_model(&payload.model);
         let resp = openai::create_responses(&state.client, &serde_json::to_value(&payload).unwrap()).await?;
         if payload.stream.unwrap_or(false) {
             let stream = crate::services::copilot::response_body_stream(resp);
@@ -115,7 +127,7 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Responses
             }
             return Ok(crate::routes::streaming::sse_response(stream));
         }
-        let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid OpenAI responses payload: {e}")))?;
+        let json: serde_json::Value = parse_json(resp, "Invalid OpenAI responses payload").await?;
         if let Some(hooks) = &state.hooks {
             let input = HookInput {
                 hook_type: Some("PostToolUse".to_string()),
@@ -129,13 +141,15 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Responses
         return Ok(Json(json).into_response());
     }
 
+    payload.model = resolved_model;
     let token = ensure_copilot_token(&state).await?;
     let config = state.config.read().await.clone();
 
-    let resp = create_responses(&state.client, &config, &token, &payload).await?;
+    let resp = create_responses_with_retry(&state, &config, &token, &payload).await?;
+    let upstream_headers = rate_limit_headers(resp.headers());
 
     if payload.stream.unwrap_or(false) {
-        let stream = crate::services::copilot::response_body_stream(resp);
+        let stream = crate::metrics::tap_usage(crate::services::copilot::response_body_stream(resp), payload.model.clone());
         if let Some(hooks) = &state.hooks {
             let input = HookInput {
                 hook_type: Some("PostToolUse".to_string()),
@@ -146,10 +160,11 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Responses
             };
             let _ = hooks.execute_event("PostToolUse", &input).await;
         }
-        return Ok(crate::routes::streaming::sse_response(stream));
+        return Ok((upstream_headers, crate::routes::streaming::sse_response(stream)).into_response());
     }
 
-    let json: serde_json::Value = resp.json().await.map_err(|e| ApiError::Upstream(format!("Invalid responses payload: {e}")))?;
+    let json: serde_json::Value = parse_json(resp, "Invalid responses payload").await?;
+    crate::metrics::record_usage(&payload.model, &json);
     if let Some(hooks) = &state.hooks {
         let input = HookInput {
             hook_type: Some("PostToolUse".to_string()),
@@ -160,7 +175,42 @@ pub async fn handle(State(state): State<AppState>, Json(payload): Json<Responses
         };
         let _ = hooks.execute_event("PostToolUse", &input).await;
     }
-    Ok(Json(json).into_response())
+    Ok((upstream_headers, Json(json)).into_response())
+}
+
+fn validate_input(input: &serde_json::Value) -> ApiResult<()> {
+    match input {
+        serde_json::Value::String(_) | serde_json::Value::Array(_) => Ok(()),
+        _ => Err(ApiError::BadRequest("input must be a string or an array of input items".to_string())),
+    }
+}
+
+/// When `instructions` is set, system/developer input items repeating the same
+/// text are dropped so the prompt is not sent twice. Items with different text
+/// are kept as-is.
+fn dedup_instructions(payload: &mut ResponsesPayload) {
+    let Some(instructions) = payload.instructions.as_deref().map(str::trim).filter(|i| !i.is_empty()) else {
+        return;
+    };
+    let Some(items) = payload.input.as_array_mut() else {
+        return;
+    };
+    items.retain(|item| {
+        let is_system = matches!(item.get("role").and_then(|r| r.as_str()), Some("system" | "developer"));
+        !(is_system && item_text(item).trim() == instructions)
+    });
+}
+
+fn item_text(item: &serde_json::Value) -> String {
+    match item.get("content") {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join(""),
+        _ => String::new(),
+    }
 }
 
 pub fn messages_to_responses_input(messages: &[crate::services::copilot::Message]) -> Vec<ResponsesInputItem> {
@@ -263,6 +313,54 @@ pub fn messages_to_responses_input(messages: &[crate::services::copilot::Message
     input
 }
 
+pub fn chat_to_responses_payload(payload: &crate::services::copilot::ChatCompletionsPayload) -> ApiResult<ResponsesPayload> {
+    let instructions = extract_instructions(&payload.messages);
+    let input = messages_to_responses_input(&payload.messages);
+
+    if input.is_empty() {
+        return Err(ApiError::BadRequest("No valid input messages".to_string()));
+    }
+
+    if payload.logit_bias.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "logit_bias is not supported by the Responses API used for model {}",
+            payload.model
+        )));
+    }
+
+    Ok(ResponsesPayload {
+        model: payload.model.clone(),
+        input: serde_json::to_value(input).unwrap_or(serde_json::json!([])),
+        instructions,
+        max_output_tokens: payload.max_tokens,
+        temperature: payload.temperature,
+        top_p: payload.top_p,
+        frequency_penalty: payload.frequency_penalty,
+        presence_penalty: payload.presence_penalty,
+        stream: payload.stream,
+        tools: payload.tools.as_ref().map(|tools| {
+            serde_json::Value::Array(
+                tools
+                    .iter()
+                    .map(|t| {
+                        serde_json::json!({
+                            "type": "function",
+                            "name": t.function.name,
+                            "description": t.function.description,
+                            "parameters": t.function.parameters,
+                        })
+                    })
+                    .collect(),
+            )
+        }),
+        tool_choice: payload.tool_choice.clone(),
+        parallel_tool_calls: payload.parallel_tool_calls,
+        previous_response_id: None,
+        user: payload.user.clone(),
+        timeout: payload.timeout,
+    })
+}
+
 pub fn extract_instructions(messages: &[crate::services::copilot::Message]) -> Option<String> {
     let system: Vec<String> = messages
         .iter()
@@ -279,8 +377,8 @@ pub fn extract_instructions(messages: &[crate::services::copilot::Message]) -> O
 
 #[cfg(test)]
 mod tests {
-    use super::{extract_instructions, messages_to_responses_input};
-    use crate::services::copilot::{Message, ToolCall, ToolCallFunction};
+    use super::{chat_to_responses_payload, dedup_instructions, extract_instructions, handle, messages_to_responses_input, validate_input};
+    use crate::services::copilot::{ChatCompletionsPayload, Message, Tool, ToolCall, ToolCallFunction, ToolFunction};
 
     #[test]
     fn extracts_system_instructions_joined() {
@@ -291,6 +389,8 @@ mod tests {
                 name: None,
                 tool_calls: None,
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             },
             Message {
                 role: "system".to_string(),
@@ -298,6 +398,8 @@ mod tests {
                 name: None,
                 tool_calls: None,
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             },
         ];
 
@@ -314,6 +416,8 @@ mod tests {
                 name: None,
                 tool_calls: None,
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             },
             Message {
                 role: "user".to_string(),
@@ -321,6 +425,8 @@ mod tests {
                 name: None,
                 tool_calls: None,
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             },
             Message {
                 role: "user".to_string(),
@@ -331,6 +437,8 @@ mod tests {
                 name: None,
                 tool_calls: None,
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             },
             Message {
                 role: "assistant".to_string(),
@@ -345,6 +453,8 @@ mod tests {
                     },
                 }]),
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             },
             Message {
                 role: "tool".to_string(),
@@ -352,6 +462,8 @@ mod tests {
                 name: None,
                 tool_calls: None,
                 tool_call_id: Some("call-1".to_string()),
+                reasoning_text: None,
+                reasoning_opaque: None,
             },
         ];
 
@@ -375,4 +487,136 @@ mod tests {
         assert_eq!(out[4].call_id.as_deref(), Some("call-1"));
         assert_eq!(out[4].output.as_deref(), Some("{\"ok\":true}"));
     }
+
+    #[test]
+    fn bridge_drops_tools_when_tool_choice_none() {
+        let mut payload = ChatCompletionsPayload {
+            model: "gpt-5.2-codex".to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: serde_json::Value::String("hi".to_string()),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
+            }],
+            tools: Some(vec![Tool {
+                r#type: "function".to_string(),
+                function: ToolFunction {
+                    name: "doit".to_string(),
+                    description: None,
+                    parameters: serde_json::json!({"type": "object"}),
+                },
+            }]),
+            tool_choice: Some(serde_json::json!("none")),
+            ..Default::default()
+        };
+        crate::routes::tool_choice::apply_none(&mut payload);
+
+        let out = chat_to_responses_payload(&payload).expect("responses payload");
+        assert!(out.tools.is_none());
+        assert!(out.tool_choice.is_none());
+    }
+
+    fn user_message(text: &str) -> Message {
+        Message {
+            role: "user".to_string(),
+            content: serde_json::Value::String(text.to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_text: None,
+            reasoning_opaque: None,
+        }
+    }
+
+    #[test]
+    fn bridge_forwards_penalties() {
+        let payload = ChatCompletionsPayload {
+            model: "gpt-5.2-codex".to_string(),
+            messages: vec![user_message("hi")],
+            frequency_penalty: Some(0.5),
+            presence_penalty: Some(-0.25),
+            ..Default::default()
+        };
+
+        let out = chat_to_responses_payload(&payload).expect("responses payload");
+        let json = serde_json::to_value(&out).unwrap();
+        assert_eq!(json["frequency_penalty"], 0.5);
+        assert_eq!(json["presence_penalty"], -0.25);
+    }
+
+    #[test]
+    fn bridge_forwards_parallel_tool_calls() {
+        let payload = ChatCompletionsPayload {
+            model: "gpt-5.2-codex".to_string(),
+            messages: vec![user_message("hi")],
+            parallel_tool_calls: Some(false),
+            ..Default::default()
+        };
+
+        let out = chat_to_responses_payload(&payload).expect("responses payload");
+        let json = serde_json::to_value(&out).unwrap();
+        assert_eq!(json["parallel_tool_calls"], false);
+
+        let json = serde_json::to_value(ChatCompletionsPayload { parallel_tool_calls: None, ..payload }).unwrap();
+        assert!(json.get("parallel_tool_calls").is_none());
+    }
+
+    #[test]
+    fn bridge_rejects_logit_bias() {
+        let payload = ChatCompletionsPayload {
+            model: "gpt-5.2-codex".to_string(),
+            messages: vec![user_message("hi")],
+            logit_bias: Some(serde_json::json!({"50256": -100})),
+            ..Default::default()
+        };
+
+        let err = chat_to_responses_payload(&payload).expect_err("logit_bias rejected");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("logit_bias"));
+    }
+
+    #[test]
+    fn validates_input_shape() {
+        assert!(validate_input(&serde_json::json!("hello")).is_ok());
+        assert!(validate_input(&serde_json::json!([{ "type": "message", "role": "user", "content": "hi" }])).is_ok());
+
+        let err = validate_input(&serde_json::json!(42)).expect_err("number rejected");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "input must be a string or an array of input items");
+    }
+
+    #[test]
+    fn drops_system_item_duplicating_instructions() {
+        let mut payload: crate::services::copilot::ResponsesPayload = serde_json::from_value(serde_json::json!({
+            "model": "gpt-5",
+            "instructions": "Be terse.",
+            "input": [
+                { "type": "message", "role": "system", "content": [{ "type": "input_text", "text": "Be terse.\n" }] },
+                { "type": "message", "role": "developer", "content": "Use metric units." },
+                { "type": "message", "role": "user", "content": "Be terse." }
+            ]
+        }))
+        .unwrap();
+
+        dedup_instructions(&mut payload);
+
+        let roles: Vec<&str> = payload.input.as_array().unwrap().iter().map(|i| i["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["developer", "user"]);
+        assert_eq!(payload.instructions.as_deref(), Some("Be terse."));
+    }
+
+    #[tokio::test]
+    async fn alias_outside_the_allowlist_is_denied() {
+        let config = crate::state::AppConfig { allowed_models: Some(vec!["gpt-4o".to_string()]), ..Default::default() };
+        let state = crate::state::AppState::new(config, reqwest::Client::new(), None);
+        let payload = serde_json::from_value(serde_json::json!({ "model": "claude-opus-4.5", "input": "hi" })).unwrap();
+        let err = handle(axum::extract::State(state), axum::http::HeaderMap::new(), axum::Json(payload))
+            .await
+            .expect_err("premium model denied");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(err.to_string(), "model not permitted: gpt-5.2-codex");
+    }
 }
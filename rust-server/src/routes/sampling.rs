@@ -0,0 +1,65 @@
+use crate::services::copilot::ChatCompletionsPayload;
+
+const TEMPERATURE_RANGE: (f64, f64) = (0.0, 2.0);
+const TOP_P_RANGE: (f64, f64) = (0.0, 1.0);
+
+fn clamp_enabled() -> bool {
+    std::env::var("COPILOT_CLAMP_SAMPLING")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Clamps `temperature`/`top_p` into the ranges upstream accepts when
+/// COPILOT_CLAMP_SAMPLING is set; otherwise values pass through untouched.
+pub fn apply(payload: &mut ChatCompletionsPayload) {
+    if clamp_enabled() {
+        clamp(payload);
+    }
+}
+
+fn clamp(payload: &mut ChatCompletionsPayload) {
+    clamp_value("temperature", &mut payload.temperature, TEMPERATURE_RANGE, &payload.model);
+    clamp_value("top_p", &mut payload.top_p, TOP_P_RANGE, &payload.model);
+}
+
+fn clamp_value(name: &str, value: &mut Option<f64>, (min, max): (f64, f64), model: &str) {
+    if let Some(v) = value.as_mut().filter(|v| **v < min || **v > max) {
+        let clamped = v.clamp(min, max);
+        tracing::info!("Clamped {} {} to {} for {}", name, v, clamped, model);
+        *v = clamped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clamp;
+    use crate::services::copilot::ChatCompletionsPayload;
+
+    fn payload(temperature: Option<f64>, top_p: Option<f64>) -> ChatCompletionsPayload {
+        ChatCompletionsPayload { model: "gpt-4o".to_string(), temperature, top_p, ..Default::default() }
+    }
+
+    #[test]
+    fn clamps_values_above_bounds() {
+        let mut p = payload(Some(3.5), Some(1.2));
+        clamp(&mut p);
+        assert_eq!(p.temperature, Some(2.0));
+        assert_eq!(p.top_p, Some(1.0));
+    }
+
+    #[test]
+    fn clamps_values_below_bounds() {
+        let mut p = payload(Some(-0.5), Some(-0.1));
+        clamp(&mut p);
+        assert_eq!(p.temperature, Some(0.0));
+        assert_eq!(p.top_p, Some(0.0));
+    }
+
+    #[test]
+    fn leaves_in_range_and_missing_values_alone() {
+        let mut p = payload(Some(0.7), None);
+        clamp(&mut p);
+        assert_eq!(p.temperature, Some(0.7));
+        assert_eq!(p.top_p, None);
+    }
+}
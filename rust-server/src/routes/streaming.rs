@@ -17,9 +17,59 @@ where
     response
 }
 
+fn find_double_newline(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\n\n")
+}
+
+pub(crate) fn drain_sse_blocks(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut blocks = Vec::new();
+    while let Some(pos) = find_double_newline(buffer) {
+        let block = buffer.drain(..pos + 2).collect::<Vec<u8>>();
+        blocks.push(String::from_utf8_lossy(&block).to_string());
+    }
+    blocks
+}
+
+pub(crate) fn extract_sse_data(block: &str) -> Option<String> {
+    let lines: Vec<&str> = block
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// The `event:` name of an SSE block, if it carries one. Some upstreams only
+/// identify the event this way rather than in the JSON `type` field.
+pub(crate) fn extract_sse_event(block: &str) -> Option<&str> {
+    block
+        .lines()
+        .find_map(|line| line.strip_prefix("event:"))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// Identifies the text part a Responses `output_text` event belongs to, so a
+/// `.done` event can tell whether its deltas were already relayed.
+pub(crate) fn output_text_part(json: &serde_json::Value) -> (String, u64) {
+    let item = json.get("item_id").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    (item, json.get("content_index").and_then(|v| v.as_u64()).unwrap_or(0))
+}
+
+/// COPILOT_STREAM_REASONING=1 forwards Responses reasoning summaries to
+/// clients; off by default so existing clients see no new delta shapes.
+pub(crate) fn stream_reasoning() -> bool {
+    std::env::var("COPILOT_STREAM_REASONING")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::sse_response;
+    use super::{drain_sse_blocks, extract_sse_data, extract_sse_event, find_double_newline, sse_response};
     use bytes::Bytes;
     use futures::stream;
 
@@ -32,5 +82,39 @@ mod tests {
         assert_eq!(headers.get("cache-control").and_then(|v| v.to_str().ok()), Some("no-cache"));
         assert_eq!(headers.get("connection").and_then(|v| v.to_str().ok()), Some("keep-alive"));
     }
-}
 
+    #[test]
+    fn finds_double_newline_in_buffer() {
+        let buf = b"data: {\"a\":1}\n\nrest";
+        assert_eq!(find_double_newline(buf), Some(13));
+    }
+
+    #[test]
+    fn drain_sse_blocks_keeps_partial_event_buffered() {
+        let mut buffer = b"data: one\n\ndata: tw".to_vec();
+        assert_eq!(drain_sse_blocks(&mut buffer), vec!["data: one\n\n".to_string()]);
+        assert_eq!(buffer, b"data: tw");
+
+        buffer.extend_from_slice(b"o\n\n");
+        let blocks = drain_sse_blocks(&mut buffer);
+        assert_eq!(extract_sse_data(&blocks[0]).as_deref(), Some("two"));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn extract_sse_data_joins_multiline_data() {
+        let block = "event: message\ndata: {\"a\":\ndata: 1}\n\n";
+        assert_eq!(extract_sse_data(block).as_deref(), Some("{\"a\":\n1}"));
+        assert_eq!(extract_sse_data("event: ping\n\n"), None);
+    }
+
+    #[test]
+    fn extract_sse_event_reads_event_name() {
+        let mut buffer = b"event: response.output_text.delta\ndata: {\"delta\":\"hi\"}\n\ndata: {}\n\n".to_vec();
+        let blocks = drain_sse_blocks(&mut buffer);
+        assert_eq!(extract_sse_event(&blocks[0]), Some("response.output_text.delta"));
+        assert_eq!(extract_sse_data(&blocks[0]).as_deref(), Some("{\"delta\":\"hi\"}"));
+        assert_eq!(extract_sse_event(&blocks[1]), None);
+        assert_eq!(extract_sse_event("event:ping\n\n"), Some("ping"));
+    }
+}
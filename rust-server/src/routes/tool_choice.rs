@@ -0,0 +1,137 @@
+use crate::services::copilot::ChatCompletionsPayload;
+
+/// True when the client asked for a plain-text reply, in either the OpenAI
+/// (`"none"`) or Anthropic (`{"type":"none"}`) shape.
+pub fn is_none(choice: Option<&serde_json::Value>) -> bool {
+    match choice {
+        Some(serde_json::Value::String(s)) => s == "none",
+        Some(v) => v.get("type").and_then(|t| t.as_str()) == Some("none"),
+        None => false,
+    }
+}
+
+/// Maps an Anthropic `tool_choice` object onto the OpenAI shape. Values that
+/// are already OpenAI-shaped pass through unchanged.
+pub fn from_anthropic(choice: Option<&serde_json::Value>) -> Option<serde_json::Value> {
+    let choice = choice?;
+    let mapped = match choice.get("type").and_then(|t| t.as_str()) {
+        Some("auto") => serde_json::json!("auto"),
+        Some("any") => serde_json::json!("required"),
+        Some("none") => serde_json::json!("none"),
+        Some("tool") => match choice.get("name").and_then(|n| n.as_str()) {
+            Some(name) => serde_json::json!({ "type": "function", "function": { "name": name } }),
+            None => choice.clone(),
+        },
+        _ => choice.clone(),
+    };
+    Some(mapped)
+}
+
+/// Honors `tool_choice: "none"` at the proxy by dropping tools from the upstream
+/// request, so providers that ignore or reject `"none"` still reply with text.
+/// A history that already holds tool calls or results needs the tool
+/// definitions upstream, so there the tools stay and `"none"` is forwarded.
+pub fn apply_none(payload: &mut ChatCompletionsPayload) {
+    if !is_none(payload.tool_choice.as_ref()) {
+        return;
+    }
+    let uses_tools = payload
+        .messages
+        .iter()
+        .any(|m| m.role == "tool" || m.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty()));
+    if uses_tools {
+        payload.tool_choice = Some(serde_json::json!("none"));
+    } else {
+        payload.tools = None;
+        payload.tool_choice = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_none, from_anthropic, is_none};
+    use crate::services::copilot::{ChatCompletionsPayload, Tool, ToolFunction};
+
+    fn payload_with_tools(tool_choice: serde_json::Value) -> ChatCompletionsPayload {
+        ChatCompletionsPayload {
+            model: "gpt-4o".to_string(),
+            tools: Some(vec![Tool {
+                r#type: "function".to_string(),
+                function: ToolFunction {
+                    name: "doit".to_string(),
+                    description: None,
+                    parameters: serde_json::json!({"type": "object"}),
+                },
+            }]),
+            tool_choice: Some(tool_choice),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn recognizes_openai_and_anthropic_none() {
+        assert!(is_none(Some(&serde_json::json!("none"))));
+        assert!(is_none(Some(&serde_json::json!({"type": "none"}))));
+        assert!(!is_none(Some(&serde_json::json!("auto"))));
+        assert!(!is_none(Some(&serde_json::json!({"type": "any"}))));
+        assert!(!is_none(None));
+    }
+
+    #[test]
+    fn apply_none_strips_tools() {
+        let mut payload = payload_with_tools(serde_json::json!("none"));
+        apply_none(&mut payload);
+        assert!(payload.tools.is_none());
+        assert!(payload.tool_choice.is_none());
+    }
+
+    #[test]
+    fn apply_none_keeps_tools_when_history_uses_them() {
+        let history: Vec<crate::services::copilot::Message> = serde_json::from_value(serde_json::json!([
+            { "role": "user", "content": "weather?" },
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{ "id": "call_1", "type": "function", "function": { "name": "doit", "arguments": "{}" } }]
+            },
+            { "role": "tool", "content": "sunny", "tool_call_id": "call_1" }
+        ]))
+        .unwrap();
+        let mut payload = ChatCompletionsPayload { messages: history, ..payload_with_tools(serde_json::json!({"type": "none"})) };
+        apply_none(&mut payload);
+        assert!(payload.tools.is_some());
+        assert_eq!(payload.tool_choice, Some(serde_json::json!("none")));
+    }
+
+    #[test]
+    fn apply_none_keeps_tools_for_other_choices() {
+        let mut payload = payload_with_tools(serde_json::json!("auto"));
+        apply_none(&mut payload);
+        assert!(payload.tools.is_some());
+        assert_eq!(payload.tool_choice, Some(serde_json::json!("auto")));
+    }
+
+    #[test]
+    fn maps_anthropic_auto_any_and_none() {
+        assert_eq!(from_anthropic(Some(&serde_json::json!({"type": "auto"}))), Some(serde_json::json!("auto")));
+        assert_eq!(from_anthropic(Some(&serde_json::json!({"type": "any"}))), Some(serde_json::json!("required")));
+        assert_eq!(from_anthropic(Some(&serde_json::json!({"type": "none"}))), Some(serde_json::json!("none")));
+        assert_eq!(from_anthropic(None), None);
+    }
+
+    #[test]
+    fn maps_anthropic_named_tool_to_function() {
+        let choice = serde_json::json!({"type": "tool", "name": "get_weather", "disable_parallel_tool_use": true});
+        assert_eq!(
+            from_anthropic(Some(&choice)),
+            Some(serde_json::json!({"type": "function", "function": {"name": "get_weather"}}))
+        );
+    }
+
+    #[test]
+    fn passes_openai_shapes_through() {
+        let named = serde_json::json!({"type": "function", "function": {"name": "doit"}});
+        assert_eq!(from_anthropic(Some(&named)), Some(named.clone()));
+        assert_eq!(from_anthropic(Some(&serde_json::json!("required"))), Some(serde_json::json!("required")));
+    }
+}
@@ -27,6 +27,20 @@ pub fn load_azure_config(model: &str) -> Option<AzureConfig> {
     })
 }
 
+/// Rewrites an inbound model id to `azure:<deployment>` via `AZURE_MODEL_MAP` /
+/// `AZURE_DEFAULT_MODEL`. Explicit `azure:` ids are left alone.
+pub fn resolve_model(model: &str) -> String {
+    if model.starts_with("azure:") {
+        return model.to_string();
+    }
+    let map = std::env::var("AZURE_MODEL_MAP").ok();
+    let default = std::env::var("AZURE_DEFAULT_MODEL").ok();
+    match crate::utils::mapped_model(map.as_deref(), default.as_deref(), model) {
+        Some(deployment) => format!("azure:{deployment}"),
+        None => model.to_string(),
+    }
+}
+
 pub async fn create_chat_completions(
     client: &reqwest::Client,
     config: &AzureConfig,
@@ -105,18 +119,63 @@ pub async fn create_responses(
     Ok(resp)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::load_azure_config;
-    use once_cell::sync::Lazy;
-    use std::sync::Mutex;
+pub async fn create_moderations(
+    client: &reqwest::Client,
+    config: &AzureConfig,
+    payload: &serde_json::Value,
+) -> ApiResult<reqwest::Response> {
+    let url = format!(
+        "{}/openai/deployments/{}/moderations?api-version={}",
+        config.endpoint, config.deployment, config.api_version
+    );
 
-    static ENV_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+    let resp = client
+        .post(url)
+        .header("api-key", &config.api_key)
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| ApiError::Upstream(format!("Azure moderations failed: {e}")))?;
 
-    fn lock_env() -> std::sync::MutexGuard<'static, ()> {
-        ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    if !resp.status().is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(ApiError::Upstream(format!("Azure moderations failed: {text}")));
     }
 
+    Ok(resp)
+}
+
+pub async fn create_transcription(
+    client: &reqwest::Client,
+    config: &AzureConfig,
+    form: reqwest::multipart::Form,
+) -> ApiResult<reqwest::Response> {
+    let url = format!(
+        "{}/openai/deployments/{}/audio/transcriptions?api-version={}",
+        config.endpoint, config.deployment, config.api_version
+    );
+
+    let resp = client
+        .post(url)
+        .header("api-key", &config.api_key)
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| ApiError::Upstream(format!("Azure transcriptions failed: {e}")))?;
+
+    if !resp.status().is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(ApiError::Upstream(format!("Azure transcriptions failed: {text}")));
+    }
+
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load_azure_config;
+    use crate::utils::env_lock;
+
     fn set_env(key: &str, value: &str) {
         unsafe {
             std::env::set_var(key, value);
@@ -131,7 +190,7 @@ mod tests {
 
     #[test]
     fn load_azure_config_uses_model_prefix_deployment() {
-        let _lock = lock_env();
+        let _lock = env_lock();
         set_env("AZURE_OPENAI_ENDPOINT", "https://example.openai.azure.com/");
         set_env("AZURE_OPENAI_KEY", "key");
         set_env("AZURE_OPENAI_API_VERSION", "2024-10-01-preview");
@@ -150,7 +209,7 @@ mod tests {
 
     #[test]
     fn load_azure_config_falls_back_to_env_deployment() {
-        let _lock = lock_env();
+        let _lock = env_lock();
         set_env("AZURE_OPENAI_ENDPOINT", "https://example.openai.azure.com/");
         set_env("AZURE_OPENAI_KEY", "key");
         set_env("AZURE_OPENAI_DEPLOYMENT", "env-deployment");
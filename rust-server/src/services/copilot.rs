@@ -8,7 +8,7 @@ use crate::{
     state::{AppConfig, ModelsResponse},
 };
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ChatCompletionsPayload {
     pub messages: Vec<Message>,
     pub model: String,
@@ -19,6 +19,8 @@ pub struct ChatCompletionsPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stop: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub n: Option<u32>,
@@ -41,7 +43,31 @@ pub struct ChatCompletionsPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<String>,
+    /// Inbound `x-initiator` override; sent as a header, never in the body.
+    #[serde(skip)]
+    pub initiator: Option<String>,
+    /// Inbound `x-copilot-timeout` override for the upstream call.
+    #[serde(skip)]
+    pub timeout: Option<std::time::Duration>,
+}
+
+impl ChatCompletionsPayload {
+    /// Folds `max_completion_tokens` into `max_tokens` (which wins when both
+    /// are set) so the rest of the pipeline sees a single limit.
+    pub fn normalize_token_limit(&mut self) {
+        if let Some(limit) = self.max_completion_tokens.take() {
+            self.max_tokens.get_or_insert(limit);
+        }
+    }
+}
+
+/// Reasoning models reject `max_tokens` and only accept `max_completion_tokens`.
+fn uses_max_completion_tokens(model: &str) -> bool {
+    ["o1", "o3", "o4", "gpt-5"].iter().any(|prefix| model == *prefix || model.starts_with(&format!("{prefix}-")))
+        || model.starts_with("gpt-5.")
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -54,6 +80,10 @@ pub struct Message {
     pub tool_calls: Option<Vec<ToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_opaque: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -96,19 +126,33 @@ pub struct ResponsesPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_p: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub previous_response_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip)]
+    pub timeout: Option<std::time::Duration>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EmbeddingRequest {
     pub input: serde_json::Value,
     pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding_format: Option<String>,
 }
 
 pub async fn create_embeddings(
@@ -156,77 +200,480 @@ pub async fn get_models(
         return Err(ApiError::Upstream(format!("Failed to get models: {text}")));
     }
 
-    resp.json::<ModelsResponse>()
-        .await
-        .map_err(|e| ApiError::Upstream(format!("Invalid models response: {e}")))
+    parse_json(resp, "Invalid models response").await
 }
 
-pub async fn create_chat_completions(
-    client: &reqwest::Client,
-    config: &AppConfig,
-    copilot_token: &str,
-    payload: &ChatCompletionsPayload,
-) -> ApiResult<reqwest::Response> {
-    let enable_vision = payload.messages.iter().any(|msg| {
+const RAW_BODY_LOG_LIMIT: usize = 2048;
+
+static SECRET_PATTERN: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+    regex::Regex::new(r#"(?i)("[a-z_]*(token|secret|key)"\s*:\s*")[^"]*|(bearer\s+)[^\s"]+|gh[opsur]_[A-Za-z0-9]+"#)
+        .unwrap()
+});
+
+/// Deserializes an upstream JSON body. When that fails, the start of the raw
+/// body is logged at debug level (visible with --verbose) with secrets masked.
+pub async fn parse_json<T: serde::de::DeserializeOwned>(resp: reqwest::Response, context: &str) -> ApiResult<T> {
+    let body = resp.bytes().await.map_err(|e| ApiError::Upstream(format!("{context}: {e}")))?;
+    serde_json::from_slice(&body).map_err(|e| {
+        tracing::debug!("{}; raw body: {}", context, raw_body_excerpt(&body));
+        ApiError::Upstream(format!("{context}: {e}"))
+    })
+}
+
+fn raw_body_excerpt(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&body[..body.len().min(RAW_BODY_LOG_LIMIT)]);
+    let masked = SECRET_PATTERN.replace_all(&text, |caps: &regex::Captures| match (caps.get(1), caps.get(3)) {
+        (Some(key), _) => format!("{}***", key.as_str()),
+        (None, Some(bearer)) => format!("{}***", bearer.as_str()),
+        _ => "***".to_string(),
+    });
+    if body.len() > RAW_BODY_LOG_LIMIT {
+        format!("{masked}... ({} bytes total)", body.len())
+    } else {
+        masked.into_owned()
+    }
+}
+
+pub fn has_image_content(messages: &[Message]) -> bool {
+    messages.iter().any(|msg| {
         msg.content
             .as_array()
             .map(|arr| arr.iter().any(|v| v.get("type") == Some(&serde_json::Value::String("image_url".to_string()))))
             .unwrap_or(false)
-    });
+    })
+}
 
-    let mut headers = reqwest::header::HeaderMap::new();
-    apply_headers(&mut headers, copilot_headers(config, copilot_token, enable_vision));
+/// Standing system prompt for `model`: a COPILOT_SYSTEM_PREFIX_MAP entry (JSON
+/// object of model id to prompt) wins over COPILOT_SYSTEM_PREFIX.
+pub fn system_prefix(model: &str) -> Option<String> {
+    let map = std::env::var("COPILOT_SYSTEM_PREFIX_MAP").ok();
+    let default = std::env::var("COPILOT_SYSTEM_PREFIX").ok();
+    crate::utils::mapped_model(map.as_deref(), default.as_deref(), model).filter(|p| !p.is_empty())
+}
+
+/// Puts `prefix` ahead of the client's leading system message, or inserts a
+/// system message when there is none.
+pub fn prepend_system_prefix(messages: &mut Vec<Message>, prefix: Option<String>) {
+    let Some(prefix) = prefix else {
+        return;
+    };
+    match messages.first_mut() {
+        Some(first) if first.role == "system" || first.role == "developer" => match &mut first.content {
+            serde_json::Value::String(text) => *text = format!("{prefix}\n\n{text}"),
+            serde_json::Value::Array(parts) => parts.insert(0, serde_json::json!({ "type": "text", "text": prefix })),
+            content => *content = serde_json::Value::String(prefix),
+        },
+        _ => messages.insert(
+            0,
+            Message {
+                role: "system".to_string(),
+                content: serde_json::Value::String(prefix),
+                name: None,
+                tool_calls: None,
+                tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
+            },
+        ),
+    }
+}
+
+fn default_user() -> Option<String> {
+    std::env::var("COPILOT_DEFAULT_USER")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+}
+
+fn user_fallback(user: Option<&String>, default: Option<String>) -> Option<String> {
+    if user.is_some() { None } else { default }
+}
+
+const MAX_REQUEST_TIMEOUT_SECS: u64 = 600;
+
+/// Per-request upstream timeout from `x-copilot-timeout` (seconds), capped at
+/// ten minutes. Absent or unparsable values keep the client default.
+pub fn request_timeout(headers: &axum::http::HeaderMap) -> Option<std::time::Duration> {
+    let secs = headers
+        .get("x-copilot-timeout")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)?;
+    Some(std::time::Duration::from_secs(secs.min(MAX_REQUEST_TIMEOUT_SECS)))
+}
 
+fn with_timeout(builder: reqwest::RequestBuilder, timeout: Option<std::time::Duration>) -> reqwest::RequestBuilder {
+    match timeout {
+        Some(timeout) => builder.timeout(timeout),
+        None => builder,
+    }
+}
+
+/// Only "user" and "agent" are meaningful to Copilot; anything else is ignored.
+pub fn parse_initiator(value: &str) -> Option<&'static str> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "user" => Some("user"),
+        "agent" => Some("agent"),
+        _ => None,
+    }
+}
+
+/// The inbound override, then COPILOT_FORCE_INITIATOR, then "agent" once the
+/// conversation has assistant or tool turns.
+fn initiator(payload: &ChatCompletionsPayload, forced: Option<&str>) -> &'static str {
+    if let Some(value) = payload.initiator.as_deref().or(forced).and_then(parse_initiator) {
+        return value;
+    }
     let is_agent_call = payload
         .messages
         .iter()
         .any(|m| m.role == "assistant" || m.role == "tool");
-    headers.insert(
-        "X-Initiator",
-        if is_agent_call { "agent" } else { "user" }.parse().unwrap(),
-    );
+    if is_agent_call { "agent" } else { "user" }
+}
 
-    let resp = client
-        .post(format!("{}/chat/completions", copilot_base_url(config)))
+pub async fn send_chat_completions(
+    client: &reqwest::Client,
+    config: &AppConfig,
+    copilot_token: &str,
+    payload: &ChatCompletionsPayload,
+) -> ApiResult<reqwest::Response> {
+    let defaulted = user_fallback(payload.user.as_ref(), default_user())
+        .map(|user| ChatCompletionsPayload { user: Some(user), ..payload.clone() });
+    let payload = defaulted.as_ref().unwrap_or(payload);
+    let renamed = payload
+        .max_tokens
+        .filter(|_| uses_max_completion_tokens(&payload.model))
+        .map(|limit| ChatCompletionsPayload { max_tokens: None, max_completion_tokens: Some(limit), ..payload.clone() });
+    let payload = renamed.as_ref().unwrap_or(payload);
+    let sanitized = payload
+        .tools
+        .as_deref()
+        .filter(|_| super::tool_schema::enabled())
+        .map(|tools| ChatCompletionsPayload { tools: Some(super::tool_schema::sanitize_tools(tools)), ..payload.clone() });
+    let payload = sanitized.as_ref().unwrap_or(payload);
+
+    let enable_vision = has_image_content(&payload.messages);
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    apply_headers(&mut headers, copilot_headers(config, copilot_token, enable_vision));
+
+    let forced = std::env::var("COPILOT_FORCE_INITIATOR").ok();
+    headers.insert("X-Initiator", initiator(payload, forced.as_deref()).parse().unwrap());
+
+    let capture = crate::capture::Capture::start(&payload.model, &headers, payload);
+    with_timeout(client.post(format!("{}/chat/completions", copilot_base_url(config))), payload.timeout)
         .headers(headers)
         .json(payload)
         .send()
         .await
-        .map_err(|e| ApiError::Upstream(format!("Failed to create chat completions: {e}")))?;
+        .map(|resp| crate::capture::tee(capture, resp))
+        .map_err(|e| ApiError::Upstream(format!("Failed to create chat completions: {e}")))
+}
 
+/// Turns a non-2xx upstream response into an `Upstream` error carrying its body.
+pub async fn ensure_success(resp: reqwest::Response, context: &str) -> ApiResult<reqwest::Response> {
     if !resp.status().is_success() {
         let text = resp.text().await.unwrap_or_default();
-        return Err(ApiError::Upstream(format!("Failed to create chat completions: {text}")));
+        return Err(ApiError::Upstream(format!("{context}: {text}")));
     }
 
     Ok(resp)
 }
 
-pub async fn create_responses(
+pub async fn send_responses(
     client: &reqwest::Client,
     config: &AppConfig,
     copilot_token: &str,
     payload: &ResponsesPayload,
 ) -> ApiResult<reqwest::Response> {
+    let defaulted = user_fallback(payload.user.as_ref(), default_user())
+        .map(|user| ResponsesPayload { user: Some(user), ..payload.clone() });
+    let payload = defaulted.as_ref().unwrap_or(payload);
+
     let mut headers = reqwest::header::HeaderMap::new();
     apply_headers(&mut headers, copilot_headers(config, copilot_token, false));
 
-    let resp = client
-        .post(format!("{}/responses", copilot_base_url(config)))
+    let capture = crate::capture::Capture::start(&payload.model, &headers, payload);
+    with_timeout(client.post(format!("{}/responses", copilot_base_url(config))), payload.timeout)
         .headers(headers)
         .json(payload)
         .send()
         .await
-        .map_err(|e| ApiError::Upstream(format!("Failed to create responses: {e}")))?;
+        .map(|resp| crate::capture::tee(capture, resp))
+        .map_err(|e| ApiError::Upstream(format!("Failed to create responses: {e}")))
+}
 
-    if !resp.status().is_success() {
-        let text = resp.text().await.unwrap_or_default();
-        return Err(ApiError::Upstream(format!("Failed to create responses: {text}")));
-    }
+const RATE_LIMIT_HEADERS: [&str; 3] = ["x-ratelimit-remaining", "x-ratelimit-reset", "retry-after"];
 
-    Ok(resp)
+/// Upstream rate-limit headers to re-emit on our response.
+pub fn rate_limit_headers(headers: &reqwest::header::HeaderMap) -> reqwest::header::HeaderMap {
+    RATE_LIMIT_HEADERS
+        .iter()
+        .filter_map(|name| headers.get(*name).map(|value| (reqwest::header::HeaderName::from_static(name), value.clone())))
+        .collect()
 }
 
 pub fn response_body_stream(resp: reqwest::Response) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
     resp.bytes_stream().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
 }
+
+fn is_json_response(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// Relays a streamed chat completion. When upstream ignores `stream: true` and
+/// returns a plain JSON body, it is replayed as one chunk followed by `[DONE]`.
+pub fn chat_completion_stream(resp: reqwest::Response) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+    let buffered = is_json_response(resp.headers());
+    async_stream::stream! {
+        if !buffered {
+            let stream = response_body_stream(resp);
+            futures::pin_mut!(stream);
+            while let Some(chunk) = futures::StreamExt::next(&mut stream).await {
+                yield chunk;
+            }
+            return;
+        }
+
+        tracing::debug!("Upstream returned JSON for a streaming request; replaying as SSE");
+        let body = resp.bytes().await.map_err(std::io::Error::other)?;
+        let chunk = match serde_json::from_slice::<serde_json::Value>(&body) {
+            Ok(json) if json.get("choices").is_some() => completion_to_chunk(&json),
+            Ok(json) => json,
+            Err(_) => serde_json::json!({ "error": { "message": String::from_utf8_lossy(&body) } }),
+        };
+        yield Ok(Bytes::from(format!("data: {}\n\n", chunk)));
+        yield Ok(Bytes::from("data: [DONE]\n\n"));
+    }
+}
+
+fn completion_to_chunk(json: &serde_json::Value) -> serde_json::Value {
+    let choices: Vec<serde_json::Value> = json
+        .get("choices")
+        .and_then(|v| v.as_array())
+        .map(|choices| {
+            choices
+                .iter()
+                .map(|choice| {
+                    let mut delta = choice.get("message").cloned().unwrap_or_else(|| serde_json::json!({}));
+                    if let Some(calls) = delta.get_mut("tool_calls").and_then(|v| v.as_array_mut()) {
+                        for (index, call) in calls.iter_mut().enumerate() {
+                            call["index"] = serde_json::json!(index);
+                        }
+                    }
+                    serde_json::json!({
+                        "index": choice.get("index").cloned().unwrap_or(serde_json::json!(0)),
+                        "delta": delta,
+                        "finish_reason": choice.get("finish_reason").cloned().unwrap_or(serde_json::Value::Null),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut chunk = serde_json::json!({
+        "id": json.get("id").cloned().unwrap_or(serde_json::Value::Null),
+        "object": "chat.completion.chunk",
+        "created": json.get("created").cloned().unwrap_or(serde_json::json!(0)),
+        "model": json.get("model").cloned().unwrap_or(serde_json::Value::Null),
+        "choices": choices,
+    });
+    if let Some(usage) = json.get("usage") {
+        chunk["usage"] = usage.clone();
+    }
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chat_completion_stream, initiator, prepend_system_prefix, request_timeout, uses_max_completion_tokens, with_timeout, parse_json, rate_limit_headers, raw_body_excerpt, user_fallback, ChatCompletionsPayload, Message};
+    use axum::{response::IntoResponse, Json};
+    use futures::StreamExt;
+
+    fn upstream(content_type: &str, body: &'static str) -> reqwest::Response {
+        reqwest::Response::from(
+            axum::http::Response::builder()
+                .header("content-type", content_type)
+                .body(body)
+                .unwrap(),
+        )
+    }
+
+    async fn collect(resp: reqwest::Response) -> Vec<String> {
+        chat_completion_stream(resp)
+            .map(|c| String::from_utf8_lossy(&c.expect("chunk")).to_string())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn json_body_is_replayed_as_sse_for_streaming_requests() {
+        let body = r#"{"id":"chatcmpl-1","created":5,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"hello","tool_calls":[{"id":"call_1","type":"function","function":{"name":"f","arguments":"{}"}}]},"finish_reason":"tool_calls"}],"usage":{"prompt_tokens":3,"completion_tokens":1,"total_tokens":4}}"#;
+        let out = collect(upstream("application/json; charset=utf-8", body)).await;
+
+        assert_eq!(out.len(), 2);
+        let chunk: serde_json::Value = serde_json::from_str(out[0].trim().trim_start_matches("data: ")).unwrap();
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+        assert_eq!(chunk["id"], "chatcmpl-1");
+        assert_eq!(chunk["choices"][0]["delta"]["content"], "hello");
+        assert_eq!(chunk["choices"][0]["delta"]["tool_calls"][0]["index"], 0);
+        assert_eq!(chunk["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(chunk["usage"]["total_tokens"], 4);
+        assert_eq!(out[1], "data: [DONE]\n\n");
+    }
+
+    #[tokio::test]
+    async fn sse_body_is_relayed_unchanged() {
+        let body = "data: {\"choices\":[]}\n\ndata: [DONE]\n\n";
+        let out = collect(upstream("text/event-stream", body)).await;
+        assert_eq!(out.concat(), body);
+    }
+
+    #[test]
+    fn system_prefix_precedes_client_system_prompt() {
+        let message = |role: &str, content: serde_json::Value| Message {
+            role: role.to_string(),
+            content,
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_text: None,
+            reasoning_opaque: None,
+        };
+        let prefix = || Some("House style: be brief.".to_string());
+
+        let mut messages = vec![message("system", serde_json::json!("You are a pirate.")), message("user", serde_json::json!("hi"))];
+        prepend_system_prefix(&mut messages, prefix());
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "House style: be brief.\n\nYou are a pirate.");
+
+        let mut messages = vec![message("system", serde_json::json!([{ "type": "text", "text": "You are a pirate." }]))];
+        prepend_system_prefix(&mut messages, prefix());
+        assert_eq!(messages[0].content[0]["text"], "House style: be brief.");
+        assert_eq!(messages[0].content[1]["text"], "You are a pirate.");
+
+        let mut messages = vec![message("user", serde_json::json!("hi"))];
+        prepend_system_prefix(&mut messages, prefix());
+        assert_eq!((messages[0].role.as_str(), messages[1].role.as_str()), ("system", "user"));
+        assert_eq!(messages[0].content, "House style: be brief.");
+
+        prepend_system_prefix(&mut messages, None);
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[test]
+    fn timeout_header_is_applied_to_upstream_request() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert_eq!(request_timeout(&headers), None);
+        headers.insert("x-copilot-timeout", "0".parse().unwrap());
+        assert_eq!(request_timeout(&headers), None);
+        headers.insert("x-copilot-timeout", "soon".parse().unwrap());
+        assert_eq!(request_timeout(&headers), None);
+        headers.insert("x-copilot-timeout", "3600".parse().unwrap());
+        assert_eq!(request_timeout(&headers), Some(std::time::Duration::from_secs(600)));
+        headers.insert("x-copilot-timeout", "90".parse().unwrap());
+        let timeout = request_timeout(&headers);
+        assert_eq!(timeout, Some(std::time::Duration::from_secs(90)));
+
+        let client = reqwest::Client::new();
+        let request = with_timeout(client.post("http://localhost/chat/completions"), timeout).build().unwrap();
+        assert_eq!(request.timeout(), Some(&std::time::Duration::from_secs(90)));
+        let request = with_timeout(client.post("http://localhost/chat/completions"), None).build().unwrap();
+        assert_eq!(request.timeout(), None);
+    }
+
+    #[test]
+    fn max_completion_tokens_sets_effective_limit() {
+        let mut payload: ChatCompletionsPayload = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [],
+            "max_completion_tokens": 256
+        }))
+        .unwrap();
+        payload.normalize_token_limit();
+        assert_eq!(payload.max_tokens, Some(256));
+        assert_eq!(payload.max_completion_tokens, None);
+
+        let mut both = ChatCompletionsPayload { max_tokens: Some(64), max_completion_tokens: Some(256), ..Default::default() };
+        both.normalize_token_limit();
+        assert_eq!(both.max_tokens, Some(64));
+
+        assert!(uses_max_completion_tokens("o3-mini"));
+        assert!(uses_max_completion_tokens("gpt-5"));
+        assert!(uses_max_completion_tokens("gpt-5.2-codex"));
+        assert!(!uses_max_completion_tokens("gpt-4o"));
+        assert!(!uses_max_completion_tokens("o1x"));
+    }
+
+    #[test]
+    fn initiator_override_wins_over_heuristic() {
+        let message = |role: &str| Message {
+            role: role.to_string(),
+            content: serde_json::json!("hi"),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_text: None,
+            reasoning_opaque: None,
+        };
+        let fresh = ChatCompletionsPayload { messages: vec![message("user")], ..Default::default() };
+        let agentic = ChatCompletionsPayload { messages: vec![message("user"), message("assistant"), message("user")], ..Default::default() };
+        assert_eq!(initiator(&fresh, None), "user");
+        assert_eq!(initiator(&agentic, None), "agent");
+
+        assert_eq!(initiator(&agentic, Some("user")), "user");
+        assert_eq!(initiator(&agentic, Some("bogus")), "agent");
+        let overridden = ChatCompletionsPayload { initiator: Some("Agent".to_string()), ..fresh.clone() };
+        assert_eq!(initiator(&overridden, None), "agent");
+        assert_eq!(initiator(&overridden, Some("user")), "agent");
+        assert!(serde_json::to_value(&overridden).unwrap().get("initiator").is_none());
+    }
+
+    #[test]
+    fn rate_limit_headers_propagate_to_client_response() {
+        let upstream = reqwest::Response::from(
+            axum::http::Response::builder()
+                .header("x-ratelimit-remaining", "42")
+                .header("x-ratelimit-reset", "1700000000")
+                .header("retry-after", "7")
+                .header("x-request-id", "abc")
+                .body("{}")
+                .unwrap(),
+        );
+
+        let headers = rate_limit_headers(upstream.headers());
+        let resp = (headers, Json(serde_json::json!({}))).into_response();
+        assert_eq!(resp.headers()["x-ratelimit-remaining"], "42");
+        assert_eq!(resp.headers()["x-ratelimit-reset"], "1700000000");
+        assert_eq!(resp.headers()["retry-after"], "7");
+        assert!(resp.headers().get("x-request-id").is_none());
+    }
+
+    #[test]
+    fn default_user_applies_only_when_user_missing() {
+        let default = Some("team-bot".to_string());
+        assert_eq!(user_fallback(None, default.clone()), Some("team-bot".to_string()));
+        assert_eq!(user_fallback(Some(&"alice".to_string()), default), None);
+        assert_eq!(user_fallback(None, None), None);
+    }
+
+    #[test]
+    fn raw_body_excerpt_masks_secrets_and_truncates() {
+        let body = br#"{"token":"tid=abc;exp=1","access_token":"gho_secret123","note":"Authorization: Bearer sk-live-1 ghu_Zz9"}"#;
+        let excerpt = raw_body_excerpt(body);
+        assert_eq!(excerpt, r#"{"token":"***","access_token":"***","note":"Authorization: Bearer *** ***"}"#);
+
+        let long = vec![b'x'; 5000];
+        assert!(raw_body_excerpt(&long).ends_with("... (5000 bytes total)"));
+    }
+
+    #[tokio::test]
+    async fn parse_json_keeps_context_in_error() {
+        let err = parse_json::<crate::state::ModelsResponse>(upstream("application/json", r#"{"models":[]}"#), "Invalid models response")
+            .await
+            .expect_err("schema drift");
+        assert!(err.to_string().starts_with("Invalid models response: missing field `data`"));
+    }
+}
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    config::{apply_headers, GITHUB_API_BASE_URL, GITHUB_BASE_URL, GITHUB_CLIENT_ID, GITHUB_APP_SCOPES, github_headers, standard_headers},
+    config::{apply_headers, GITHUB_API_BASE_URL, github_headers, standard_headers},
     errors::{ApiError, ApiResult},
+    services::copilot::parse_json,
     state::AppConfig,
     utils::sleep_ms,
 };
@@ -35,17 +36,21 @@ pub struct GitHubUser {
     pub login: String,
 }
 
-pub async fn get_device_code(client: &reqwest::Client) -> ApiResult<DeviceCodeResponse> {
+fn device_code_body(config: &AppConfig) -> serde_json::Value {
+    serde_json::json!({
+        "client_id": config.github_client_id,
+        "scope": config.github_scopes,
+    })
+}
+
+pub async fn get_device_code(client: &reqwest::Client, config: &AppConfig) -> ApiResult<DeviceCodeResponse> {
     let mut headers = reqwest::header::HeaderMap::new();
     apply_headers(&mut headers, standard_headers());
 
     let resp = client
-        .post(format!("{GITHUB_BASE_URL}/login/device/code"))
+        .post(format!("{}/login/device/code", config.github_base_url))
         .headers(headers)
-        .json(&serde_json::json!({
-            "client_id": GITHUB_CLIENT_ID,
-            "scope": GITHUB_APP_SCOPES,
-        }))
+        .json(&device_code_body(config))
         .send()
         .await
         .map_err(|e| ApiError::Upstream(format!("Failed to get device code: {e}")))?;
@@ -55,26 +60,28 @@ pub async fn get_device_code(client: &reqwest::Client) -> ApiResult<DeviceCodeRe
         return Err(ApiError::Upstream(format!("Failed to get device code: {text}")));
     }
 
-    resp.json::<DeviceCodeResponse>()
-        .await
-        .map_err(|e| ApiError::Upstream(format!("Invalid device code response: {e}")))
+    parse_json(resp, "Invalid device code response").await
 }
 
+/// Polls until the user completes the device flow, giving up at `deadline`
+/// (normally when the device code expires); `None` polls until GitHub answers.
 pub async fn poll_access_token(
     client: &reqwest::Client,
+    config: &AppConfig,
     device: &DeviceCodeResponse,
+    deadline: Option<std::time::Instant>,
 ) -> ApiResult<String> {
     let sleep_duration = (device.interval + 1) * 1000;
 
-    loop {
+    poll_until(deadline, sleep_duration, || async move {
         let mut headers = reqwest::header::HeaderMap::new();
         apply_headers(&mut headers, standard_headers());
 
         let resp = client
-            .post(format!("{GITHUB_BASE_URL}/login/oauth/access_token"))
+            .post(format!("{}/login/oauth/access_token", config.github_base_url))
             .headers(headers)
             .json(&serde_json::json!({
-                "client_id": GITHUB_CLIENT_ID,
+                "client_id": config.github_client_id,
                 "device_code": device.device_code,
                 "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
             }))
@@ -82,18 +89,33 @@ pub async fn poll_access_token(
             .await
             .map_err(|e| ApiError::Upstream(format!("Failed to poll access token: {e}")))?;
 
-        if resp.status().is_success() {
-            let json = resp
-                .json::<AccessTokenResponse>()
-                .await
-                .map_err(|e| ApiError::Upstream(format!("Invalid access token response: {e}")))?;
-
-            if let Some(token) = json.access_token {
-                return Ok(token);
-            }
+        if !resp.status().is_success() {
+            return Ok(None);
         }
+        let json = parse_json::<AccessTokenResponse>(resp, "Invalid access token response").await?;
+        Ok(json.access_token)
+    })
+    .await
+}
 
-        sleep_ms(sleep_duration).await;
+async fn poll_until<F, Fut>(deadline: Option<std::time::Instant>, interval_ms: u64, mut attempt: F) -> ApiResult<String>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = ApiResult<Option<String>>>,
+{
+    loop {
+        if let Some(token) = attempt().await? {
+            return Ok(token);
+        }
+        let Some(deadline) = deadline else {
+            sleep_ms(interval_ms).await;
+            continue;
+        };
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() {
+            return Err(ApiError::Upstream("device code expired".to_string()));
+        }
+        sleep_ms(interval_ms.min(remaining.as_millis() as u64)).await;
     }
 }
 
@@ -117,9 +139,7 @@ pub async fn get_copilot_token(
         return Err(ApiError::Upstream(format!("Failed to get Copilot token: {text}")));
     }
 
-    resp.json::<CopilotTokenResponse>()
-        .await
-        .map_err(|e| ApiError::Upstream(format!("Invalid Copilot token response: {e}")))
+    parse_json(resp, "Invalid Copilot token response").await
 }
 
 pub async fn get_github_user(
@@ -142,9 +162,7 @@ pub async fn get_github_user(
         return Err(ApiError::Upstream(format!("Failed to fetch user: {text}")));
     }
 
-    resp.json::<GitHubUser>()
-        .await
-        .map_err(|e| ApiError::Upstream(format!("Invalid user response: {e}")))
+    parse_json(resp, "Invalid user response").await
 }
 
 pub async fn get_copilot_usage(
@@ -167,7 +185,74 @@ pub async fn get_copilot_usage(
         return Err(ApiError::Upstream(format!("Failed to get Copilot usage: {text}")));
     }
 
-    resp.json::<serde_json::Value>()
+    parse_json(resp, "Invalid usage response").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{device_code_body, poll_until};
+    use crate::config::{GITHUB_APP_SCOPES, GITHUB_CLIENT_ID};
+    use crate::state::AppConfig;
+
+    #[test]
+    fn device_code_body_uses_overridden_client_id() {
+        let config = AppConfig {
+            github_client_id: "Iv1.custom".to_string(),
+            github_scopes: "read:user read:org".to_string(),
+            ..AppConfig::default()
+        };
+
+        let body = device_code_body(&config);
+        assert_eq!(body.get("client_id").and_then(|v| v.as_str()), Some("Iv1.custom"));
+        assert_eq!(body.get("scope").and_then(|v| v.as_str()), Some("read:user read:org"));
+    }
+
+    #[test]
+    fn device_code_body_defaults_to_builtin_app() {
+        let config = AppConfig {
+            github_client_id: GITHUB_CLIENT_ID.to_string(),
+            github_scopes: GITHUB_APP_SCOPES.to_string(),
+            ..AppConfig::default()
+        };
+
+        let body = device_code_body(&config);
+        assert_eq!(body.get("client_id").and_then(|v| v.as_str()), Some(GITHUB_CLIENT_ID));
+        assert_eq!(body.get("scope").and_then(|v| v.as_str()), Some(GITHUB_APP_SCOPES));
+    }
+
+    #[tokio::test]
+    async fn polling_stops_at_device_code_deadline() {
+        let attempts = std::cell::Cell::new(0);
+        let started = std::time::Instant::now();
+        let deadline = started + std::time::Duration::from_millis(120);
+
+        let err = poll_until(Some(deadline), 50, || {
+            attempts.set(attempts.get() + 1);
+            async { Ok(None) }
+        })
+        .await
+        .expect_err("expired");
+        assert_eq!(err.to_string(), "device code expired");
+        assert_eq!(err.status_code(), axum::http::StatusCode::BAD_GATEWAY);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(120));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert!(attempts.get() >= 3);
+
+        let token = poll_until(Some(deadline), 50, || async { Ok(Some("gho_done".to_string())) }).await.unwrap();
+        assert_eq!(token, "gho_done");
+    }
+
+    #[tokio::test]
+    async fn polling_without_deadline_keeps_going() {
+        let attempts = std::cell::Cell::new(0);
+        let token = poll_until(None, 1, || {
+            attempts.set(attempts.get() + 1);
+            let done = attempts.get() == 5;
+            async move { Ok(done.then(|| "gho_late".to_string())) }
+        })
         .await
-        .map_err(|e| ApiError::Upstream(format!("Invalid usage response: {e}")))
+        .unwrap();
+        assert_eq!(token, "gho_late");
+        assert_eq!(attempts.get(), 5);
+    }
 }
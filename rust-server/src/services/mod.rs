@@ -4,3 +4,5 @@ pub mod vscode;
 pub mod azure;
 pub mod openai;
 pub mod anthropic;
+pub mod tool_schema;
+pub mod model_alias;
@@ -0,0 +1,64 @@
+//! Maps Claude, o-series and shorthand model names onto the Copilot models that
+//! serve them. Callers resolve once and pass the result on.
+
+pub fn resolve_model_alias(model: &str) -> String {
+    let aliases = [
+        ("claude-opus-4.5", "gpt-5.2-codex"),
+        ("claude-opus-4", "gpt-5.2-codex"),
+        ("claude-4-opus", "gpt-5.2-codex"),
+        ("claude-3-opus", "gpt-5.2-codex"),
+        ("claude-3-opus-20240229", "gpt-5.2-codex"),
+        ("claude-sonnet-4", "gpt-5.1-codex"),
+        ("claude-4-sonnet", "gpt-5.1-codex"),
+        ("claude-3.5-sonnet", "gpt-5.1-codex"),
+        ("claude-3-5-sonnet-20241022", "gpt-5.1-codex"),
+        ("claude-3-sonnet", "gpt-5.1-codex"),
+        ("claude-3-sonnet-20240229", "gpt-5.1-codex"),
+        ("claude-haiku-3.5", "gpt-5-mini"),
+        ("claude-3.5-haiku", "gpt-5-mini"),
+        ("claude-3-haiku", "gpt-5-mini"),
+        ("claude-3-haiku-20240307", "gpt-5-mini"),
+        ("claude-2.1", "gpt-5.1"),
+        ("claude-2.0", "gpt-5.1"),
+        ("claude-instant-1.2", "gpt-5-mini"),
+        ("codex-5.2", "gpt-5.2-codex"),
+        ("codex-5.1", "gpt-5.1-codex"),
+        ("o3", "gpt-5.2-codex"),
+        ("o3-mini", "gpt-5-mini"),
+        ("o1", "gpt-5.1"),
+        ("o1-preview", "gpt-5.1"),
+        ("o1-mini", "gpt-5-mini"),
+    ];
+
+    if model.starts_with("claude-sonnet-4-") {
+        return "gpt-5.1-codex".to_string();
+    }
+    if model.starts_with("claude-opus-4-") || model.starts_with("claude-opus-4.5-") {
+        return "gpt-5.2-codex".to_string();
+    }
+    if model.starts_with("claude-haiku-") {
+        return "gpt-5-mini".to_string();
+    }
+
+    for (from, to) in aliases {
+        if model == from {
+            return to.to_string();
+        }
+    }
+
+    model.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_model_alias;
+
+    #[test]
+    fn resolves_claude_aliases() {
+        assert_eq!(resolve_model_alias("claude-opus-4.5"), "gpt-5.2-codex");
+        assert_eq!(resolve_model_alias("claude-3.5-haiku"), "gpt-5-mini");
+        assert_eq!(resolve_model_alias("claude-2.1"), "gpt-5.1");
+        assert_eq!(resolve_model_alias("codex-5.1"), "gpt-5.1-codex");
+        assert_eq!(resolve_model_alias("gpt-4o"), "gpt-4o");
+    }
+}
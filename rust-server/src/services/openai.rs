@@ -1,4 +1,5 @@
 use crate::errors::{ApiError, ApiResult};
+use crate::services::copilot::parse_json;
 
 fn openai_base_url() -> String {
     std::env::var("OPENAI_BASE_URL").unwrap_or_else(|_| "https://api.openai.com/v1".to_string())
@@ -9,6 +10,13 @@ fn openai_api_key() -> ApiResult<String> {
         .map_err(|_| ApiError::BadRequest("Missing OPENAI_API_KEY".to_string()))
 }
 
+/// Rewrites an inbound model id via `OPENAI_MODEL_MAP` / `OPENAI_DEFAULT_MODEL`.
+pub fn resolve_model(model: &str) -> String {
+    let map = std::env::var("OPENAI_MODEL_MAP").ok();
+    let default = std::env::var("OPENAI_DEFAULT_MODEL").ok();
+    crate::utils::mapped_model(map.as_deref(), default.as_deref(), model).unwrap_or_else(|| model.to_string())
+}
+
 pub async fn create_chat_completions(
     client: &reqwest::Client,
     payload: &serde_json::Value,
@@ -75,6 +83,50 @@ pub async fn create_embeddings(
     Ok(resp)
 }
 
+pub async fn create_moderations(
+    client: &reqwest::Client,
+    payload: &serde_json::Value,
+) -> ApiResult<reqwest::Response> {
+    let key = openai_api_key()?;
+    let url = format!("{}/moderations", openai_base_url());
+    let resp = client
+        .post(url)
+        .bearer_auth(key)
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| ApiError::Upstream(format!("OpenAI moderations failed: {e}")))?;
+
+    if !resp.status().is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(ApiError::Upstream(format!("OpenAI moderations failed: {text}")));
+    }
+
+    Ok(resp)
+}
+
+pub async fn create_transcription(
+    client: &reqwest::Client,
+    form: reqwest::multipart::Form,
+) -> ApiResult<reqwest::Response> {
+    let key = openai_api_key()?;
+    let url = format!("{}/audio/transcriptions", openai_base_url());
+    let resp = client
+        .post(url)
+        .bearer_auth(key)
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| ApiError::Upstream(format!("OpenAI transcriptions failed: {e}")))?;
+
+    if !resp.status().is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(ApiError::Upstream(format!("OpenAI transcriptions failed: {text}")));
+    }
+
+    Ok(resp)
+}
+
 pub async fn list_models(client: &reqwest::Client) -> ApiResult<serde_json::Value> {
     let key = openai_api_key()?;
     let url = format!("{}/models", openai_base_url());
@@ -90,7 +142,5 @@ pub async fn list_models(client: &reqwest::Client) -> ApiResult<serde_json::Valu
         return Err(ApiError::Upstream(format!("OpenAI models failed: {text}")));
     }
 
-    resp.json::<serde_json::Value>()
-        .await
-        .map_err(|e| ApiError::Upstream(format!("Invalid OpenAI models response: {e}")))
+    parse_json(resp, "Invalid OpenAI models response").await
 }
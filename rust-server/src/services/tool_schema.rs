@@ -0,0 +1,136 @@
+//! Cleans tool `parameters` schemas that Copilot rejects (COPILOT_SANITIZE_TOOLS=1).
+//!
+//! Applied to every subschema (`properties`, `patternProperties`, `$defs`,
+//! `definitions`, `items`, `prefixItems`, `additionalProperties`, `not`,
+//! `anyOf`, `oneOf`, `allOf`):
+//! - `$schema`, `$id` and `$comment` are removed.
+//! - `format` is kept only for `date-time`, `date`, `time`, `duration`,
+//!   `email`, `hostname`, `ipv4`, `ipv6` and `uuid`; other values are removed.
+//! - `additionalProperties: false` is removed from schemas that also use
+//!   `anyOf`, `oneOf` or `allOf`.
+//!
+//! Property names are never touched, so a parameter called `format` survives.
+
+use serde_json::Value;
+
+use super::copilot::Tool;
+
+const DROPPED_KEYWORDS: &[&str] = &["$schema", "$id", "$comment"];
+const SUPPORTED_FORMATS: &[&str] = &["date-time", "date", "time", "duration", "email", "hostname", "ipv4", "ipv6", "uuid"];
+const SCHEMA_MAPS: &[&str] = &["properties", "patternProperties", "$defs", "definitions"];
+const SCHEMA_LISTS: &[&str] = &["anyOf", "oneOf", "allOf", "prefixItems", "items"];
+const SCHEMA_VALUES: &[&str] = &["items", "additionalProperties", "not"];
+
+pub fn enabled() -> bool {
+    std::env::var("COPILOT_SANITIZE_TOOLS")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+pub fn sanitize_tools(tools: &[Tool]) -> Vec<Tool> {
+    tools
+        .iter()
+        .map(|tool| {
+            let mut tool = tool.clone();
+            sanitize_schema(&mut tool.function.parameters);
+            tool
+        })
+        .collect()
+}
+
+pub fn sanitize_schema(schema: &mut Value) {
+    let Some(obj) = schema.as_object_mut() else {
+        return;
+    };
+
+    for keyword in DROPPED_KEYWORDS {
+        obj.remove(*keyword);
+    }
+    if obj.get("format").and_then(|f| f.as_str()).is_some_and(|f| !SUPPORTED_FORMATS.contains(&f)) {
+        obj.remove("format");
+    }
+    let composed = ["anyOf", "oneOf", "allOf"].iter().any(|k| obj.contains_key(*k));
+    if composed && obj.get("additionalProperties") == Some(&Value::Bool(false)) {
+        obj.remove("additionalProperties");
+    }
+
+    for key in SCHEMA_MAPS {
+        if let Some(map) = obj.get_mut(*key).and_then(|v| v.as_object_mut()) {
+            map.values_mut().for_each(sanitize_schema);
+        }
+    }
+    for key in SCHEMA_LISTS {
+        if let Some(list) = obj.get_mut(*key).and_then(|v| v.as_array_mut()) {
+            list.iter_mut().for_each(sanitize_schema);
+        }
+    }
+    for key in SCHEMA_VALUES {
+        if let Some(value) = obj.get_mut(*key).filter(|v| v.is_object()) {
+            sanitize_schema(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sanitize_schema, sanitize_tools};
+    use crate::services::copilot::{Tool, ToolFunction};
+
+    #[test]
+    fn strips_schema_keyword_and_unknown_formats() {
+        let mut schema = serde_json::json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "when": { "type": "string", "format": "date-time" },
+                "path": { "type": "string", "format": "uri-reference" },
+                "format": { "type": "string", "enum": ["json", "text"] },
+                "tags": { "type": "array", "items": { "type": "string", "format": "regex", "$comment": "x" } },
+                "target": {
+                    "anyOf": [{ "type": "string", "format": "binary" }, { "type": "integer" }],
+                    "additionalProperties": false
+                }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        });
+        sanitize_schema(&mut schema);
+
+        assert_eq!(
+            schema,
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "when": { "type": "string", "format": "date-time" },
+                    "path": { "type": "string" },
+                    "format": { "type": "string", "enum": ["json", "text"] },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "target": { "anyOf": [{ "type": "string" }, { "type": "integer" }] }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            })
+        );
+    }
+
+    #[test]
+    fn sanitizes_each_tool_parameters() {
+        let tool = Tool {
+            r#type: "function".to_string(),
+            function: ToolFunction {
+                name: "fetch".to_string(),
+                description: None,
+                parameters: serde_json::json!({
+                    "$schema": "https://json-schema.org/draft/2020-12/schema",
+                    "type": "object",
+                    "properties": { "url": { "type": "string", "format": "uri" } }
+                }),
+            },
+        };
+        let cleaned = sanitize_tools(&[tool]);
+        assert_eq!(
+            cleaned[0].function.parameters,
+            serde_json::json!({ "type": "object", "properties": { "url": { "type": "string" } } })
+        );
+    }
+}
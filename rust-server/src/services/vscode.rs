@@ -1,5 +1,80 @@
-use crate::utils::get_vscode_version;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    paths::get_paths,
+    utils::{fetch_latest_vscode_version, FALLBACK_VSCODE_VERSION},
+};
+
+const CACHE_TTL_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedVersion {
+    version: String,
+    fetched_at: i64,
+}
 
 pub async fn fetch_vscode_version() -> String {
-    get_vscode_version().await
+    let cache_path = get_paths().ok().map(|p| p.vscode_version_path);
+    let cached = match &cache_path {
+        Some(path) => read_cache(path).await,
+        None => None,
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if let Some(cached) = cached.as_ref().filter(|c| !is_stale(c.fetched_at, now)) {
+        return cached.version.clone();
+    }
+
+    match fetch_latest_vscode_version().await {
+        Some(version) => {
+            if let Some(path) = &cache_path {
+                write_cache(path, &CachedVersion { version: version.clone(), fetched_at: now }).await;
+            }
+            version
+        }
+        None => cached
+            .map(|c| c.version)
+            .unwrap_or_else(|| FALLBACK_VSCODE_VERSION.to_string()),
+    }
+}
+
+fn is_stale(fetched_at: i64, now: i64) -> bool {
+    now - fetched_at >= CACHE_TTL_SECS || fetched_at > now
+}
+
+async fn read_cache(path: &std::path::Path) -> Option<CachedVersion> {
+    let content = tokio::fs::read_to_string(path).await.ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+async fn write_cache(path: &std::path::Path, cached: &CachedVersion) {
+    if let Some(parent) = path.parent() {
+        let _ = tokio::fs::create_dir_all(parent).await;
+    }
+    let Ok(json) = serde_json::to_string(cached) else {
+        return;
+    };
+    if let Err(e) = tokio::fs::write(path, json).await {
+        tracing::warn!("Failed to cache vscode version: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_stale, CACHE_TTL_SECS};
+
+    #[test]
+    fn cache_is_fresh_within_ttl() {
+        let now = 1_700_000_000;
+        assert!(!is_stale(now, now));
+        assert!(!is_stale(now - CACHE_TTL_SECS + 1, now));
+    }
+
+    #[test]
+    fn cache_is_stale_after_ttl_or_from_the_future() {
+        let now = 1_700_000_000;
+        assert!(is_stale(now - CACHE_TTL_SECS, now));
+        assert!(is_stale(now - CACHE_TTL_SECS * 3, now));
+        assert!(is_stale(now + 60, now));
+    }
 }
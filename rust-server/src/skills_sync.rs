@@ -1,10 +1,14 @@
 use serde::Deserialize;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use crate::config::http_client_builder;
 use crate::errors::{ApiError, ApiResult};
 
 const TREE_URL: &str = "https://api.github.com/repos/affaan-m/everything-claude-code/git/trees/main?recursive=1";
 const RAW_BASE: &str = "https://raw.githubusercontent.com/affaan-m/everything-claude-code/main/";
+const MAX_RETRIES: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Deserialize)]
 struct TreeResponse {
@@ -20,16 +24,13 @@ struct TreeItem {
 }
 
 pub async fn sync_skills() -> ApiResult<()> {
-    let client = reqwest::Client::builder()
-        .user_agent("copilot-api-rs")
+    let client = http_client_builder()
+        .timeout(sync_timeout())
         .build()
         .map_err(|e| ApiError::Internal(format!("Failed to build client: {e}")))?;
 
-    let tree = client
-        .get(TREE_URL)
-        .send()
-        .await
-        .map_err(|e| ApiError::Internal(format!("Failed to fetch skills tree: {e}")))?
+    let tree = get_with_retry(&client, TREE_URL, "skills tree", RETRY_BACKOFF)
+        .await?
         .json::<TreeResponse>()
         .await
         .map_err(|e| ApiError::Internal(format!("Invalid tree response: {e}")))?;
@@ -59,11 +60,8 @@ pub async fn sync_skills() -> ApiResult<()> {
                 .map_err(|e| ApiError::Internal(format!("Failed to create dir: {e}")))?;
         }
         let url = format!("{}{}", RAW_BASE, item.path);
-        let bytes = client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| ApiError::Internal(format!("Failed to download skill: {e}")))?
+        let bytes = get_with_retry(&client, &url, &item.path, RETRY_BACKOFF)
+            .await?
             .bytes()
             .await
             .map_err(|e| ApiError::Internal(format!("Failed to read skill bytes: {e}")))?;
@@ -75,6 +73,45 @@ pub async fn sync_skills() -> ApiResult<()> {
     Ok(())
 }
 
+fn sync_timeout() -> Duration {
+    std::env::var("COPILOT_SKILLS_TIMEOUT")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(30))
+}
+
+/// GETs `url`, retrying network errors and 5xx responses with exponential backoff.
+async fn get_with_retry(
+    client: &reqwest::Client,
+    url: &str,
+    label: &str,
+    backoff: Duration,
+) -> ApiResult<reqwest::Response> {
+    let mut attempt = 0;
+    loop {
+        let last_error = match client.get(url).send().await {
+            Ok(resp) if resp.status().is_server_error() => format!("HTTP {}", resp.status()),
+            Ok(resp) if !resp.status().is_success() => {
+                return Err(ApiError::Internal(format!("Failed to fetch {label}: HTTP {}", resp.status())));
+            }
+            Ok(resp) => return Ok(resp),
+            Err(err) => err.to_string(),
+        };
+
+        if attempt >= MAX_RETRIES {
+            return Err(ApiError::Internal(format!(
+                "Failed to fetch {label} after {} attempts: {last_error}",
+                attempt + 1
+            )));
+        }
+        tracing::warn!("Fetching {} failed ({}), retrying", label, last_error);
+        tokio::time::sleep(backoff * 2u32.pow(attempt)).await;
+        attempt += 1;
+    }
+}
+
 fn resolve_project_skills_dir() -> ApiResult<PathBuf> {
     let cwd = std::env::current_dir()
         .map_err(|e| ApiError::Internal(format!("Failed to read cwd: {e}")))?;
@@ -108,3 +145,59 @@ fn ensure_notice_file(root: &Path) -> ApiResult<()> {
         .map_err(|e| ApiError::Internal(format!("Failed to write notice: {e}")))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::get_with_retry;
+    use crate::errors::ApiError;
+    use axum::{extract::State, http::StatusCode, routing::get, Router};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+
+    async fn flaky_server(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route(
+                "/skills/a.md",
+                get(move |State(hits): State<Arc<AtomicUsize>>| async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) < failures {
+                        (StatusCode::SERVICE_UNAVAILABLE, "busy")
+                    } else {
+                        (StatusCode::OK, "skill body")
+                    }
+                }),
+            )
+            .with_state(hits.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/skills/a.md", addr), hits)
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_success() {
+        let (url, hits) = flaky_server(2).await;
+        let resp = get_with_retry(&reqwest::Client::new(), &url, "skills/a.md", Duration::from_millis(1))
+            .await
+            .expect("third attempt succeeds");
+
+        assert_eq!(resp.text().await.unwrap(), "skill body");
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn names_path_after_retries_are_exhausted() {
+        let (url, hits) = flaky_server(usize::MAX).await;
+        let err = get_with_retry(&reqwest::Client::new(), &url, "skills/a.md", Duration::from_millis(1))
+            .await
+            .expect_err("always failing");
+
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+        assert!(matches!(err, ApiError::Internal(msg) if msg.contains("skills/a.md") && msg.contains("503")));
+    }
+}
@@ -1,43 +1,99 @@
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
+use crate::config::{GITHUB_APP_SCOPES, GITHUB_BASE_URL, GITHUB_CLIENT_ID};
 use crate::hooks::HookExecutor;
+use crate::idempotency::IdempotencyCache;
+use crate::idle::ActivityTracker;
+use crate::response_cache::ResponseCache;
+use crate::services::github::DeviceCodeResponse;
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub config: Arc<RwLock<AppConfig>>,
     pub client: reqwest::Client,
     pub hooks: Option<Arc<HookExecutor>>,
+    pub response_cache: Arc<std::sync::Mutex<ResponseCache>>,
+    pub activity: Arc<ActivityTracker>,
+    pub shutdown: CancellationToken,
+    /// Held while fetching the model list so concurrent cache misses share one fetch.
+    pub models_fetch: Arc<tokio::sync::Mutex<()>>,
+    pub idempotency: Arc<IdempotencyCache>,
+    /// Held while fetching a Copilot token so concurrent refreshes share one fetch.
+    pub token_refresh: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl AppState {
+    pub fn new(config: AppConfig, client: reqwest::Client, hooks: Option<Arc<HookExecutor>>) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(config)),
+            client,
+            hooks,
+            response_cache: Default::default(),
+            activity: Default::default(),
+            shutdown: Default::default(),
+            models_fetch: Default::default(),
+            idempotency: Default::default(),
+            token_refresh: Default::default(),
+        }
+    }
+}
+
+/// Default config, a plain client and no hooks.
+#[cfg(test)]
+pub fn test_state() -> AppState {
+    AppState::new(AppConfig::default(), reqwest::Client::new(), None)
 }
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub account_type: String,
+    pub github_client_id: String,
+    pub github_scopes: String,
+    /// Device-flow endpoints; only tests point this elsewhere.
+    pub github_base_url: String,
     pub github_token: Option<String>,
     pub copilot_token: Option<String>,
     pub show_token: bool,
     pub vscode_version: String,
     pub models: Option<ModelsResponse>,
+    /// Served by `/v1/models` in place of the live list (`--models-file`).
+    pub static_models: Option<ModelsResponse>,
     pub manual_approve: bool,
     pub rate_limit_seconds: Option<u64>,
     pub rate_limit_wait: bool,
     pub last_request_timestamp: Option<std::time::Instant>,
+    pub rate_limit_per_model: std::collections::HashMap<String, (u64, Option<std::time::Instant>)>,
+    /// COPILOT_ALLOWED_MODELS; `None` serves every model.
+    pub allowed_models: Option<Vec<String>>,
+    /// COPILOT_FALLBACK_MODELS, keyed by the resolved model.
+    pub fallback_models: std::collections::HashMap<String, Vec<String>>,
+    pub auth_sessions: std::collections::HashMap<String, (DeviceCodeResponse, std::time::Instant)>,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             account_type: std::env::var("COPILOT_ACCOUNT_TYPE").unwrap_or_else(|_| "individual".to_string()),
+            github_client_id: std::env::var("COPILOT_GITHUB_CLIENT_ID").unwrap_or_else(|_| GITHUB_CLIENT_ID.to_string()),
+            github_scopes: std::env::var("COPILOT_GITHUB_SCOPES").unwrap_or_else(|_| GITHUB_APP_SCOPES.to_string()),
+            github_base_url: GITHUB_BASE_URL.to_string(),
             github_token: std::env::var("COPILOT_GITHUB_TOKEN").ok(),
             copilot_token: None,
             show_token: std::env::var("COPILOT_SHOW_TOKEN").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false),
             vscode_version: "1.104.3".to_string(),
             models: None,
+            static_models: None,
             manual_approve: std::env::var("COPILOT_MANUAL_APPROVE").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false),
             rate_limit_seconds: std::env::var("COPILOT_RATE_LIMIT").ok().and_then(|v| v.parse::<u64>().ok()),
             rate_limit_wait: std::env::var("COPILOT_RATE_LIMIT_WAIT").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false),
             last_request_timestamp: None,
+            rate_limit_per_model: std::env::var("COPILOT_RATE_LIMIT_PER_MODEL").map(|v| crate::rate_limit::parse_per_model_limits(&v)).unwrap_or_default(),
+            allowed_models: crate::routes::models::allowed_models(),
+            fallback_models: crate::routes::fallback::fallback_models(),
+            auth_sessions: std::collections::HashMap::new(),
         }
     }
 }
@@ -91,4 +147,7 @@ pub struct ModelSupports {
     pub tool_calls: Option<bool>,
     pub parallel_tool_calls: Option<bool>,
     pub dimensions: Option<bool>,
+    pub vision: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub streaming: Option<bool>,
 }
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::{response::IntoResponse, Json};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{ApiError, ApiResult};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelStats {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Local per-model request and token counts, kept in `usage_stats.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelStats>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl UsageStats {
+    /// Missing or unreadable files start from zero.
+    pub fn load(path: &Path) -> Self {
+        let Ok(data) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&data).unwrap_or_else(|err| {
+            tracing::warn!("Ignoring invalid usage stats {}: {}", path.display(), err);
+            Self::default()
+        })
+    }
+
+    pub fn record(&mut self, model: &str, input: u64, output: u64) {
+        let entry = self.models.entry(model.to_string()).or_default();
+        entry.requests += 1;
+        entry.input_tokens += input;
+        entry.output_tokens += output;
+        self.dirty = true;
+    }
+
+    /// Snapshot to write, or `None` when nothing changed since the last one.
+    fn take_dirty(&mut self) -> Option<String> {
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        serde_json::to_string_pretty(self).ok()
+    }
+}
+
+async fn write_stats(path: &Path, data: &str) -> ApiResult<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| ApiError::Internal(format!("Failed to create {}: {e}", parent.display())))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, data)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to write {}: {e}", tmp.display())))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to write {}: {e}", path.display())))
+}
+
+pub fn stats_path() -> ApiResult<PathBuf> {
+    Ok(crate::paths::get_paths()?.usage_stats_path)
+}
+
+static STATS: Lazy<Mutex<UsageStats>> =
+    Lazy::new(|| Mutex::new(stats_path().map(|path| UsageStats::load(&path)).unwrap_or_default()));
+
+/// Counts one request for `model` along with whatever usage upstream reported.
+pub fn record(model: &str, input: u64, output: u64) {
+    STATS.lock().unwrap().record(model, input, output);
+}
+
+pub async fn flush() {
+    let Some(data) = STATS.lock().unwrap().take_dirty() else {
+        return;
+    };
+    let result = match stats_path() {
+        Ok(path) => write_stats(&path, &data).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        tracing::warn!("Failed to save usage stats: {}", err);
+        STATS.lock().unwrap().dirty = true;
+    }
+}
+
+/// Writes changed counters every 30s, and once more on shutdown.
+pub fn spawn_flusher(shutdown: CancellationToken) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(FLUSH_INTERVAL) => {}
+            }
+            flush().await;
+        }
+        flush().await;
+    })
+}
+
+pub async fn stats() -> impl IntoResponse {
+    let models = STATS.lock().unwrap().models.clone();
+    Json(serde_json::json!({ "models": models }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_stats, ModelStats, UsageStats};
+
+    #[tokio::test]
+    async fn counters_round_trip_through_the_stats_file() {
+        let dir = std::env::temp_dir().join(format!("copilot-stats-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("usage_stats.json");
+
+        let mut stats = UsageStats::load(&path);
+        assert!(stats.models.is_empty());
+        stats.record("gpt-4o", 10, 4);
+        stats.record("gpt-4o", 5, 1);
+        stats.record("claude-sonnet-4", 0, 0);
+        write_stats(&path, &stats.take_dirty().expect("dirty after record")).await.unwrap();
+        assert!(stats.take_dirty().is_none());
+
+        let mut reloaded = UsageStats::load(&path);
+        assert_eq!(reloaded.models["gpt-4o"], ModelStats { requests: 2, input_tokens: 15, output_tokens: 5 });
+        assert_eq!(reloaded.models["claude-sonnet-4"].requests, 1);
+
+        reloaded.record("gpt-4o", 1, 1);
+        write_stats(&path, &reloaded.take_dirty().unwrap()).await.unwrap();
+        assert_eq!(UsageStats::load(&path).models["gpt-4o"].requests, 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
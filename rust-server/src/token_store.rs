@@ -2,9 +2,20 @@ use crate::{errors::{ApiError, ApiResult}, paths::ensure_paths};
 
 pub async fn read_github_token() -> ApiResult<Option<String>> {
     let paths = ensure_paths().await?;
-    let content = tokio::fs::read_to_string(paths.github_token_path)
+    read_token_file(&paths.github_token_path).await
+}
+
+pub async fn read_github_token_file() -> ApiResult<Option<String>> {
+    match std::env::var("COPILOT_GITHUB_TOKEN_FILE") {
+        Ok(path) if !path.trim().is_empty() => read_token_file(std::path::Path::new(path.trim())).await,
+        _ => Ok(None),
+    }
+}
+
+async fn read_token_file(path: &std::path::Path) -> ApiResult<Option<String>> {
+    let content = tokio::fs::read_to_string(path)
         .await
-        .map_err(|e| ApiError::Internal(format!("Failed to read token: {e}")))?;
+        .map_err(|e| ApiError::Internal(format!("Failed to read token file {}: {e}", path.display())))?;
     let trimmed = content.trim().to_string();
     if trimmed.is_empty() {
         Ok(None)
@@ -15,8 +26,47 @@ pub async fn read_github_token() -> ApiResult<Option<String>> {
 
 pub async fn write_github_token(token: &str) -> ApiResult<()> {
     let paths = ensure_paths().await?;
-    tokio::fs::write(paths.github_token_path, token)
+    write_token_file(&paths.github_token_path, token).await
+}
+
+async fn write_token_file(path: &std::path::Path, token: &str) -> ApiResult<()> {
+    tokio::fs::write(path, token)
         .await
-        .map_err(|e| ApiError::Internal(format!("Failed to write token: {e}")))?;
-    Ok(())
+        .map_err(|e| ApiError::Internal(format!("Failed to write token: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_token_file, write_token_file};
+    use crate::paths::ensure_token_file;
+
+    #[tokio::test]
+    async fn reads_trimmed_token_from_secret_file() {
+        let path = std::env::temp_dir().join(format!("copilot-token-{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, "  gho_secret\n").await.unwrap();
+        assert_eq!(read_token_file(&path).await.unwrap().as_deref(), Some("gho_secret"));
+
+        tokio::fs::write(&path, "\n").await.unwrap();
+        assert_eq!(read_token_file(&path).await.unwrap(), None);
+
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(read_token_file(&path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn round_trips_through_overridden_token_path() {
+        let dir = std::env::temp_dir().join(format!("copilot-token-dir-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("nested").join("github_token");
+
+        ensure_token_file(&path).await.unwrap();
+        assert!(path.exists());
+        assert_eq!(read_token_file(&path).await.unwrap(), None);
+        write_token_file(&path, "gho_override").await.unwrap();
+        assert_eq!(read_token_file(&path).await.unwrap().as_deref(), Some("gho_override"));
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "gho_override");
+
+        ensure_token_file(&path).await.unwrap();
+        assert_eq!(read_token_file(&path).await.unwrap().as_deref(), Some("gho_override"));
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
 }
@@ -45,6 +45,11 @@ fn encoder_from_tokenizer(name: &str) -> &CoreBPE {
     }
 }
 
+/// Forces the encoder for `name` to load now instead of on first use.
+pub fn preload(name: &str) {
+    let _ = encoder_from_tokenizer(name);
+}
+
 pub fn estimate_chat_tokens(payload: &ChatCompletionsPayload, tokenizer: &str) -> u64 {
     let encoder = encoder_from_tokenizer(tokenizer);
     let constants = constants_for_model(&payload.model);
@@ -106,6 +111,10 @@ fn tool_calls_tokens(tool_calls: &Vec<ToolCall>, encoder: &CoreBPE, constants: T
     tokens
 }
 
+pub fn count_text_tokens(text: &str) -> u64 {
+    O200K.encode_ordinary(text).len() as u64
+}
+
 pub fn use_precise_tokenizer() -> bool {
     std::env::var("COPILOT_USE_TIKTOKEN")
         .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
@@ -114,7 +123,7 @@ pub fn use_precise_tokenizer() -> bool {
 
 #[cfg(test)]
 mod tests {
-    use super::{estimate_chat_tokens, encoder_from_tokenizer};
+    use super::{count_text_tokens, estimate_chat_tokens, encoder_from_tokenizer, preload};
     use crate::services::copilot::{ChatCompletionsPayload, Message};
 
     #[test]
@@ -122,6 +131,12 @@ mod tests {
         let _ = encoder_from_tokenizer("o200k_base");
     }
 
+    #[test]
+    fn preload_then_estimate() {
+        preload("o200k_base");
+        assert!(count_text_tokens("hello world") > 0);
+    }
+
     #[test]
     fn estimates_tokens_for_simple_payload() {
         let payload = ChatCompletionsPayload {
@@ -132,10 +147,13 @@ mod tests {
                 name: None,
                 tool_calls: None,
                 tool_call_id: None,
+                reasoning_text: None,
+                reasoning_opaque: None,
             }],
             temperature: None,
             top_p: None,
             max_tokens: None,
+            max_completion_tokens: None,
             stop: None,
             n: None,
             stream: None,
@@ -147,7 +165,10 @@ mod tests {
             seed: None,
             tools: None,
             tool_choice: None,
+            parallel_tool_calls: None,
             user: None,
+            initiator: None,
+            timeout: None,
         };
 
         let count = estimate_chat_tokens(&payload, "o200k_base");
@@ -2,31 +2,32 @@ pub async fn sleep_ms(ms: u64) {
     tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
 }
 
-pub async fn get_vscode_version() -> String {
-    const FALLBACK: &str = "1.104.3";
+pub const FALLBACK_VSCODE_VERSION: &str = "1.104.3";
 
-    let client = reqwest::Client::new();
+pub async fn fetch_latest_vscode_version() -> Option<String> {
+    let client = crate::config::http_client_builder().build().ok()?;
     let request = client
         .get("https://aur.archlinux.org/cgit/aur.git/plain/PKGBUILD?h=visual-studio-code-bin")
         .timeout(std::time::Duration::from_secs(5));
 
-    match request.send().await {
-        Ok(resp) => match resp.text().await {
-            Ok(body) => {
-                let re = regex::Regex::new(r"pkgver=([0-9.]+)").ok();
-                if let Some(re) = re {
-                    if let Some(caps) = re.captures(&body) {
-                        if let Some(m) = caps.get(1) {
-                            return m.as_str().to_string();
-                        }
-                    }
-                }
-                FALLBACK.to_string()
-            }
-            Err(_) => FALLBACK.to_string(),
-        },
-        Err(_) => FALLBACK.to_string(),
-    }
+    let body = request.send().await.ok()?.text().await.ok()?;
+    let re = regex::Regex::new(r"pkgver=([0-9.]+)").ok()?;
+    re.captures(&body)
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Provider-native model for `model`: an explicit `map` entry (a JSON object of
+/// inbound id to provider id) wins, then `default`. `None` leaves it unchanged.
+pub fn mapped_model(map: Option<&str>, default: Option<&str>, model: &str) -> Option<String> {
+    let mapped = map.and_then(|raw| match serde_json::from_str::<std::collections::HashMap<String, String>>(raw) {
+        Ok(mut map) => map.remove(model),
+        Err(err) => {
+            tracing::warn!("Ignoring invalid model map: {}", err);
+            None
+        }
+    });
+    mapped.or_else(|| default.filter(|d| !d.trim().is_empty()).map(|d| d.trim().to_string()))
 }
 
 pub fn estimate_tokens_from_json(value: &serde_json::Value) -> u64 {
@@ -34,4 +35,71 @@ pub fn estimate_tokens_from_json(value: &serde_json::Value) -> u64 {
     ((serialized.len() as f64) / 4.0).ceil() as u64
 }
 
+/// The platform command that opens `url` in the default browser.
+pub fn open_command(url: &str) -> std::process::Command {
+    if cfg!(target_os = "windows") {
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.args(["/c", "start", "", url]);
+        cmd
+    } else if cfg!(target_os = "macos") {
+        let mut cmd = std::process::Command::new("open");
+        cmd.arg(url);
+        cmd
+    } else {
+        let mut cmd = std::process::Command::new("xdg-open");
+        cmd.arg(url);
+        cmd
+    }
+}
+
+pub fn open_url(url: &str) -> std::io::Result<()> {
+    open_command(url).spawn().map(|_| ())
+}
+
 // intentionally left without env helpers to keep runtime dependency surface minimal
+
+/// Serializes tests that change process environment variables; a lock per
+/// module would still let tests in different modules race.
+#[cfg(test)]
+pub fn env_lock() -> std::sync::MutexGuard<'static, ()> {
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mapped_model, open_command};
+
+    const MAP: &str = r#"{"gpt-5.2-codex":"gpt-4o","claude-sonnet-4":"gpt-4.1"}"#;
+
+    #[test]
+    fn mapped_model_prefers_explicit_mapping() {
+        assert_eq!(mapped_model(Some(MAP), Some("gpt-4o-mini"), "gpt-5.2-codex").as_deref(), Some("gpt-4o"));
+        assert_eq!(mapped_model(Some(MAP), None, "claude-sonnet-4").as_deref(), Some("gpt-4.1"));
+    }
+
+    #[test]
+    fn mapped_model_falls_back_to_default() {
+        assert_eq!(mapped_model(Some(MAP), Some("gpt-4o-mini"), "o3").as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(mapped_model(Some("not json"), Some("gpt-4o-mini"), "o3").as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(mapped_model(Some(MAP), None, "o3"), None);
+        assert_eq!(mapped_model(None, Some(" "), "o3"), None);
+    }
+
+    #[test]
+    fn open_command_passes_url_as_last_argument() {
+        let url = "https://github.com/login/device?a=1&b=2";
+        let cmd = open_command(url);
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args.last().and_then(|a| a.to_str()), Some(url));
+        let program = cmd.get_program().to_str().unwrap();
+        if cfg!(target_os = "windows") {
+            assert_eq!(program, "cmd");
+            assert_eq!(args[..3], ["/c", "start", ""]);
+        } else if cfg!(target_os = "macos") {
+            assert_eq!(program, "open");
+        } else {
+            assert_eq!(program, "xdg-open");
+        }
+    }
+}
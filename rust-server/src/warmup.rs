@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::{config::copilot_base_url, state::AppState};
+
+/// COPILOT_WARMUP_INTERVAL in seconds; unset or 0 disables the warmup.
+pub fn warmup_interval() -> Option<Duration> {
+    parse_interval(std::env::var("COPILOT_WARMUP_INTERVAL").ok())
+}
+
+fn parse_interval(value: Option<String>) -> Option<Duration> {
+    value
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// Periodically sends a HEAD to the Copilot host through the shared client so
+/// a pooled TLS connection is ready when the next request arrives after idle.
+pub fn spawn(state: AppState, interval: Option<Duration>) -> Option<JoinHandle<()>> {
+    let interval = interval?;
+    Some(tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                biased;
+                _ = state.shutdown.cancelled() => break,
+                _ = tokio::time::sleep(interval) => {}
+            }
+            let base_url = copilot_base_url(&*state.config.read().await);
+            match state.client.head(&base_url).timeout(Duration::from_secs(10)).send().await {
+                Ok(resp) => tracing::debug!("Warmup {} -> {}", base_url, resp.status()),
+                Err(err) => tracing::debug!("Warmup {} failed: {}", base_url, err),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_interval, spawn};
+    use crate::state::test_state;
+    use std::time::Duration;
+
+
+    #[test]
+    fn zero_or_missing_interval_disables_warmup() {
+        assert_eq!(parse_interval(None), None);
+        assert_eq!(parse_interval(Some("0".to_string())), None);
+        assert_eq!(parse_interval(Some("abc".to_string())), None);
+        assert_eq!(parse_interval(Some(" 45 ".to_string())), Some(Duration::from_secs(45)));
+    }
+
+    #[tokio::test]
+    async fn disabled_interval_spawns_no_task() {
+        assert!(spawn(test_state(), None).is_none());
+    }
+
+    #[tokio::test]
+    async fn warmup_task_stops_on_shutdown() {
+        let state = test_state();
+        let shutdown = state.shutdown.clone();
+        let handle = spawn(state, Some(Duration::from_secs(3600))).expect("task spawned");
+        shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(5), handle).await.expect("task exits").unwrap();
+    }
+}